@@ -2,7 +2,7 @@
 //! 
 //! This module encapsulates the logic for rendering GUIs using `egui`.
 
-use egui::{Context, PlatformOutput, ViewportId};
+use egui::{Context, ViewportId};
 use egui_wgpu::Renderer as EguiRenderer;
 use egui_winit::State;
 use winit::event::WindowEvent;
@@ -32,12 +32,14 @@ impl GuiManager {
         }
     }
 
-    /// Handles window events and passes them to `egui`.
-    pub fn handle_event(&mut self, window: &Window, window_event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, window_event);
+    /// Handles window events and passes them to `egui`. Returns `true` if `egui`
+    /// consumed the event, e.g. because a widget was clicked.
+    pub fn handle_event(&mut self, window: &Window, window_event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, window_event).consumed
     }
 
     /// Renders the GUI.
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
         &mut self,
         window: &Window,
@@ -46,7 +48,7 @@ impl GuiManager {
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         system_monitor: &SystemMonitor,
-        debug_overlay: &DebugOverlay,
+        debug_overlay: &mut DebugOverlay,
         gpu_name: &str,
     ) {
         // Get `egui` input and run the UI logic.
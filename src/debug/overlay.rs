@@ -3,11 +3,48 @@
 //! This module defines the debug overlay that is displayed when F3 is pressed.
 
 use egui::{Context, Window};
+use crate::meshing::MeshMode;
 use crate::monitoring::SystemMonitor;
+use crate::renderer::CullingStats;
+use crate::simulation::{WorldTime, TICKS_PER_DAY, TIME_PRESETS};
+use crate::streaming::ChunkStats;
+use crate::world::{Biome, Light, RaycastHit};
 
-/// Contains the state of the debug overlay (e.g., whether it is visible).
+/// Contains the state of the debug overlay (e.g., whether it is visible)
+/// and the settings that can be changed from it.
 pub struct DebugOverlay {
     pub shown: bool,
+    pub mesh_mode: MeshMode,
+    pub reverse_z: bool,
+    pub wireframe: bool,
+    pub wireframe_supported: bool,
+    pub chunk_borders: bool,
+    /// Whether chunks outside of the camera's view are skipped when drawing.
+    pub frustum_culling: bool,
+    /// Whether sections hidden behind terrain are skipped when drawing.
+    pub cave_culling: bool,
+    /// Whether block corners are darkened by ambient occlusion.
+    pub ambient_occlusion: bool,
+    /// Whether the time of day advances with the game ticks.
+    pub daylight_cycle: bool,
+    /// The time of the world, shown in the overlay.
+    pub time: WorldTime,
+    /// A time of day picked in the overlay, to be applied to the world.
+    pub set_time: Option<u64>,
+    /// Whether screenshots include the overlay when it is shown.
+    pub screenshot_overlay: bool,
+    /// The radius around the camera within which chunks are loaded, in chunks.
+    pub render_distance: i32,
+    /// The biome under the camera, or `None` if its chunk is not loaded.
+    pub biome: Option<Biome>,
+    /// The light at the camera, or `None` if its chunk is not loaded.
+    pub light: Option<Light>,
+    /// The block the camera is looking at and its name, or `None` if no block is in reach.
+    pub target: Option<(RaycastHit, String)>,
+    /// The name of the block placed with the right mouse button.
+    pub selected_block: String,
+    pub chunks: ChunkStats,
+    pub culling: CullingStats,
 }
 
 impl Default for DebugOverlay {
@@ -19,7 +56,28 @@ impl Default for DebugOverlay {
 impl DebugOverlay {
     /// Creates a new `DebugOverlay`.
     pub fn new() -> Self {
-        Self { shown: false }
+        Self {
+            shown: false,
+            mesh_mode: MeshMode::default(),
+            reverse_z: true,
+            wireframe: false,
+            wireframe_supported: false,
+            chunk_borders: false,
+            frustum_culling: true,
+            cave_culling: true,
+            ambient_occlusion: true,
+            daylight_cycle: true,
+            time: WorldTime::default(),
+            set_time: None,
+            screenshot_overlay: false,
+            render_distance: 8,
+            biome: None,
+            light: None,
+            target: None,
+            selected_block: String::new(),
+            chunks: ChunkStats::default(),
+            culling: CullingStats::default(),
+        }
     }
 
     /// Toggles the visibility of the overlay.
@@ -28,7 +86,7 @@ impl DebugOverlay {
     }
 
     /// Draws the debug overlay UI.
-    pub fn ui(&self, ctx: &Context, monitor: &SystemMonitor, gpu_name: &str) {
+    pub fn ui(&mut self, ctx: &Context, monitor: &SystemMonitor, gpu_name: &str) {
         if !self.shown {
             return;
         }
@@ -41,6 +99,75 @@ impl DebugOverlay {
                 ui.label(format!("GPU ({}): {:.1}%", gpu_name, monitor.get_gpu_usage()));
                 ui.label(format!("RAM: {:.1}%", monitor.get_memory_usage_percent()));
                 ui.label(format!("Process: {:.1} MB", monitor.get_process_memory_mb()));
+                ui.separator();
+                ui.label(format!("Frame time: {:.2} ms", monitor.get_avg_frame_time_ms()));
+                ui.label(format!("Tick time: {:.2} ms ({} TPS)", monitor.get_avg_tick_time_ms(), monitor.get_tps()));
+                ui.label(format!("Triangles: {}", monitor.get_triangle_count()));
+                ui.label(format!(
+                    "Chunks: {} loaded, {} pending, {} meshed",
+                    self.chunks.loaded, self.chunks.pending, self.chunks.meshed
+                ));
+                ui.label(format!(
+                    "Sections: {} visible, {} culled, {} occluded",
+                    self.culling.visible, self.culling.culled, self.culling.occluded
+                ));
+                let (hours, minutes) = self.time.clock();
+                ui.label(format!("Time: day {}, {:02}:{:02} ({})", self.time.day(), hours, minutes, self.time.time_of_day()));
+                ui.label(format!("Biome: {}", self.biome.map_or("-", Biome::name)));
+                match self.light {
+                    Some(light) => ui.label(format!("Light: {} sky, {} block", light.sky(), light.block())),
+                    None => ui.label("Light: -"),
+                };
+                match &self.target {
+                    Some((hit, name)) => ui.label(format!(
+                        "Looking at: {} at {}, {}, {} ({} face, {:.1} blocks away)",
+                        name, hit.pos[0], hit.pos[1], hit.pos[2], face_name(hit.normal), hit.distance
+                    )),
+                    None => ui.label("Looking at: -"),
+                };
+                ui.label(format!("Selected block: {}", self.selected_block));
+                ui.horizontal(|ui| {
+                    ui.label("Meshing:");
+                    for mode in [MeshMode::Culled, MeshMode::Greedy] {
+                        ui.radio_value(&mut self.mesh_mode, mode, mode.name());
+                    }
+                });
+                ui.add(egui::Slider::new(&mut self.render_distance, 2..=16).text("Render distance"));
+                ui.checkbox(&mut self.reverse_z, "Reverse-Z depth");
+                ui.add_enabled(self.wireframe_supported, egui::Checkbox::new(&mut self.wireframe, "Wireframe"));
+                ui.checkbox(&mut self.chunk_borders, "Chunk borders");
+                ui.checkbox(&mut self.frustum_culling, "Frustum culling");
+                ui.checkbox(&mut self.cave_culling, "Cave culling");
+                ui.checkbox(&mut self.ambient_occlusion, "Ambient occlusion");
+                ui.horizontal(|ui| {
+                    let mut time = self.time.time_of_day();
+                    if ui.add(egui::Slider::new(&mut time, 0..=TICKS_PER_DAY - 1).text("Time of day")).changed() {
+                        self.set_time = Some(time);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Set time:");
+                    for (name, time) in TIME_PRESETS {
+                        if ui.button(name).clicked() {
+                            self.set_time = Some(time);
+                        }
+                    }
+                });
+                ui.checkbox(&mut self.daylight_cycle, "Daylight cycle");
+                ui.checkbox(&mut self.screenshot_overlay, "Overlay in screenshots (F2)");
             });
     }
 }
+
+/// Returns the name of the face of a block with the given outward normal.
+fn face_name(normal: [i32; 3]) -> &'static str {
+    match normal {
+        [1, 0, 0] => "east",
+        [-1, 0, 0] => "west",
+        [0, 1, 0] => "top",
+        [0, -1, 0] => "bottom",
+        [0, 0, 1] => "south",
+        [0, 0, -1] => "north",
+        _ => "inner",
+    }
+}
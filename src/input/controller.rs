@@ -0,0 +1,297 @@
+//! # Camera Controller
+//! 
+//! This module defines a first-person controller that flies the camera with the
+//! keyboard and turns it with raw mouse motion while the cursor is grabbed.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::time::Duration;
+
+use cgmath::Rad;
+use log::warn;
+use winit::event::ElementState;
+use winit::keyboard::KeyCode;
+use winit::window::{CursorGrabMode, Window};
+
+use crate::renderer::Camera;
+
+/// How far the camera can look up or down. It stops just short of vertical, where
+/// the view direction would be parallel to the up vector.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// The speed multiplier while sprinting.
+const SPRINT_MULTIPLIER: f32 = 2.5;
+
+/// The speed multiplier while crouching.
+const CROUCH_MULTIPLIER: f32 = 0.3;
+
+/// The movement keys that are held down.
+#[derive(Copy, Clone, Debug, Default)]
+struct Keys {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+    crouch: bool,
+}
+
+/// The movement requested by the held keys, relative to the camera's heading.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MovementInput {
+    /// Forward (positive) or backward (negative) movement. Together with `right`,
+    /// it has a length of at most one.
+    pub forward: f32,
+    /// Rightward (positive) or leftward (negative) movement.
+    pub right: f32,
+    /// Upward (positive) or downward (negative) movement.
+    pub up: f32,
+    pub sprint: bool,
+    pub crouch: bool,
+}
+
+impl MovementInput {
+    /// Returns the speed multiplier of the held modifiers.
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.sprint {
+            SPRINT_MULTIPLIER
+        } else if self.crouch {
+            CROUCH_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Moves the camera from keyboard and mouse input.
+///
+/// W, A, S and D move along the camera's heading, Space and C move up and down
+/// (Space jumps when walking), Left Control sprints and Left Shift crouches.
+pub struct CameraController {
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    keys: Keys,
+    /// Mouse motion since the last update, in pixels.
+    mouse_delta: (f64, f64),
+    grabbed: bool,
+    /// The movement speed, in blocks per second.
+    pub speed: f32,
+    /// How far the camera turns per pixel of mouse motion, in radians.
+    pub sensitivity: f32,
+}
+
+impl CameraController {
+    /// Creates a new `CameraController` that starts out looking where `camera` looks.
+    pub fn new(camera: &Camera) -> Self {
+        let (yaw, pitch) = camera.yaw_pitch();
+        Self {
+            yaw,
+            pitch: Rad(pitch.0.clamp(-MAX_PITCH, MAX_PITCH)),
+            keys: Keys::default(),
+            mouse_delta: (0.0, 0.0),
+            grabbed: false,
+            speed: 10.0,
+            sensitivity: 0.0025,
+        }
+    }
+
+    /// Returns `true` if the cursor is grabbed, so mouse motion turns the camera.
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Grabs and hides the cursor, or releases it.
+    pub fn set_cursor_grab(&mut self, window: &Window, grab: bool) {
+        if grab {
+            // Not every platform can lock the cursor in place, so fall back to
+            // confining it to the window.
+            let result = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = result {
+                warn!("Failed to grab the cursor: {}", e);
+            }
+        } else if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+            warn!("Failed to release the cursor: {}", e);
+        }
+        window.set_cursor_visible(!grab);
+        self.grabbed = grab;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    /// Releases every held key, e.g. when the window loses focus and would miss
+    /// the key releases.
+    pub fn release_keys(&mut self) {
+        self.keys = Keys::default();
+    }
+
+    /// Records a key press or release. Returns `true` if the key controls the camera.
+    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
+        let pressed = state == ElementState::Pressed;
+        let held = match key {
+            KeyCode::KeyW => &mut self.keys.forward,
+            KeyCode::KeyS => &mut self.keys.backward,
+            KeyCode::KeyA => &mut self.keys.left,
+            KeyCode::KeyD => &mut self.keys.right,
+            KeyCode::Space => &mut self.keys.up,
+            KeyCode::KeyC => &mut self.keys.down,
+            KeyCode::ControlLeft => &mut self.keys.sprint,
+            KeyCode::ShiftLeft => &mut self.keys.crouch,
+            _ => return false,
+        };
+        *held = pressed;
+        true
+    }
+
+    /// Records raw mouse motion, in pixels. Motion is ignored unless the cursor is grabbed.
+    pub fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.grabbed {
+            self.mouse_delta.0 += dx;
+            self.mouse_delta.1 += dy;
+        }
+    }
+
+    /// Returns the direction the camera faces around the vertical axis.
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    /// Returns the movement requested by the held keys.
+    pub fn movement(&self) -> MovementInput {
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let (mut forward, mut right) = (axis(self.keys.forward, self.keys.backward), axis(self.keys.right, self.keys.left));
+        // Moving diagonally is no faster than moving straight.
+        let length = (forward * forward + right * right).sqrt();
+        if length > 0.0 {
+            forward /= length;
+            right /= length;
+        }
+        MovementInput {
+            forward,
+            right,
+            up: axis(self.keys.up, self.keys.down),
+            sprint: self.keys.sprint,
+            crouch: self.keys.crouch,
+        }
+    }
+
+    /// Turns the camera by the mouse motion received since the last call.
+    pub fn turn(&mut self, camera: &mut Camera) {
+        let (dx, dy) = std::mem::take(&mut self.mouse_delta);
+        self.yaw = Rad((self.yaw.0 + dx as f32 * self.sensitivity).rem_euclid(TAU));
+        self.pitch = Rad((self.pitch.0 - dy as f32 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH));
+        camera.look(self.yaw, self.pitch);
+    }
+
+    /// Turns the camera and flies it by the input received over the last `dt`.
+    pub fn update(&mut self, camera: &mut Camera, dt: Duration) {
+        self.turn(camera);
+
+        let movement = self.movement();
+        let distance = self.speed * movement.speed_multiplier() * dt.as_secs_f32();
+        camera.move_forward(movement.forward * distance);
+        camera.move_right(movement.right * distance);
+        camera.move_up(movement.up * distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{InnerSpace, Point3, Vector3};
+
+    /// A camera at the origin looking down -Z.
+    fn camera() -> Camera {
+        Camera::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y(), 45.0, 1.0, 0.1, 100.0)
+    }
+
+    fn press(controller: &mut CameraController, keys: &[KeyCode]) {
+        for &key in keys {
+            assert!(controller.process_keyboard(key, ElementState::Pressed));
+        }
+    }
+
+    /// Returns how far the camera moves in one second with the given keys held.
+    fn distance_moved(keys: &[KeyCode]) -> f32 {
+        let mut camera = camera();
+        let mut controller = CameraController::new(&camera);
+        press(&mut controller, keys);
+        controller.update(&mut camera, Duration::from_secs(1));
+        (camera.position - Point3::new(0.0, 0.0, 0.0)).magnitude()
+    }
+
+    #[test]
+    fn movement_does_not_depend_on_the_frame_rate() {
+        let (mut slow, mut fast) = (camera(), camera());
+        let mut controller = CameraController::new(&slow);
+        press(&mut controller, &[KeyCode::KeyW, KeyCode::KeyD]);
+
+        controller.update(&mut slow, Duration::from_millis(500));
+        for _ in 0..50 {
+            controller.update(&mut fast, Duration::from_millis(10));
+        }
+        assert!((slow.position - fast.position).magnitude() < 1e-4);
+        // Forward is -Z and right is +X.
+        assert!(slow.position.z < 0.0 && slow.position.x > 0.0);
+    }
+
+    #[test]
+    fn modifiers_change_the_speed() {
+        let speed = CameraController::new(&camera()).speed;
+        assert!((distance_moved(&[KeyCode::KeyW]) - speed).abs() < 1e-4);
+        assert!((distance_moved(&[KeyCode::KeyW, KeyCode::KeyA]) - speed).abs() < 1e-4);
+        assert!((distance_moved(&[KeyCode::KeyS, KeyCode::ControlLeft]) - speed * SPRINT_MULTIPLIER).abs() < 1e-3);
+        assert!((distance_moved(&[KeyCode::Space, KeyCode::ShiftLeft]) - speed * CROUCH_MULTIPLIER).abs() < 1e-4);
+        assert_eq!(distance_moved(&[KeyCode::KeyW, KeyCode::KeyS]), 0.0);
+    }
+
+    #[test]
+    fn released_keys_stop_the_camera() {
+        let mut camera = camera();
+        let mut controller = CameraController::new(&camera);
+        press(&mut controller, &[KeyCode::KeyW, KeyCode::Space]);
+        controller.process_keyboard(KeyCode::KeyW, ElementState::Released);
+        controller.update(&mut camera, Duration::from_secs(1));
+        assert!((camera.position.y - controller.speed).abs() < 1e-4);
+
+        controller.release_keys();
+        let before = camera.position;
+        controller.update(&mut camera, Duration::from_secs(1));
+        assert_eq!(camera.position, before);
+        assert!(!controller.process_keyboard(KeyCode::KeyQ, ElementState::Pressed));
+    }
+
+    #[test]
+    fn mouse_motion_turns_the_camera_only_when_grabbed() {
+        let mut camera = camera();
+        let mut controller = CameraController::new(&camera);
+        controller.process_mouse_motion(400.0, 0.0);
+        controller.update(&mut camera, Duration::ZERO);
+        assert!((camera.forward() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-5);
+
+        controller.grabbed = true;
+        let quarter_turn = FRAC_PI_2 / controller.sensitivity;
+        controller.process_mouse_motion(quarter_turn as f64 / 2.0, 0.0);
+        controller.process_mouse_motion(quarter_turn as f64 / 2.0, 0.0);
+        controller.update(&mut camera, Duration::ZERO);
+        assert!((camera.forward() - Vector3::unit_x()).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = camera();
+        let mut controller = CameraController::new(&camera);
+        controller.grabbed = true;
+
+        controller.process_mouse_motion(0.0, -1.0e6);
+        controller.update(&mut camera, Duration::ZERO);
+        let (_, pitch) = camera.yaw_pitch();
+        assert!((pitch.0 - MAX_PITCH).abs() < 1e-4);
+
+        controller.process_mouse_motion(0.0, 1.0e6);
+        controller.update(&mut camera, Duration::ZERO);
+        let (_, pitch) = camera.yaw_pitch();
+        assert!((pitch.0 + MAX_PITCH).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,95 @@
+//! # Hotbar
+//! 
+//! This module tracks the block that is placed with the right mouse button. The
+//! number keys select one of the first nine blocks and the mouse wheel cycles
+//! through all of them.
+
+use winit::event::ElementState;
+use winit::keyboard::KeyCode;
+
+use crate::world::{BlockId, BlockRegistry, AIR};
+
+/// The keys that select the first nine blocks, in order.
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// The blocks that can be placed, and the one that is selected.
+pub struct Hotbar {
+    blocks: Vec<BlockId>,
+    selected: usize,
+}
+
+impl Hotbar {
+    /// Creates a new `Hotbar` holding every solid block of the registry, by ID.
+    pub fn new(registry: &BlockRegistry) -> Self {
+        Self {
+            blocks: registry.iter().filter(|def| def.id != AIR && def.solid).map(|def| def.id).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Returns the selected block, or `None` if there is nothing to place.
+    pub fn selected(&self) -> Option<BlockId> {
+        self.blocks.get(self.selected).copied()
+    }
+
+    /// Selects a block with the number keys. Returns `true` if the key selects a block.
+    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
+        let Some(slot) = SLOT_KEYS.iter().position(|&slot| slot == key) else {
+            return false;
+        };
+        if state == ElementState::Pressed && slot < self.blocks.len() {
+            self.selected = slot;
+        }
+        true
+    }
+
+    /// Cycles through the blocks by `steps`, forwards or backwards, wrapping around.
+    pub fn scroll(&mut self, steps: i32) {
+        if !self.blocks.is_empty() {
+            self.selected = (self.selected as i32 + steps).rem_euclid(self.blocks.len() as i32) as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_hotbar_holds_the_solid_blocks() {
+        let registry = BlockRegistry::builtin();
+        let mut hotbar = Hotbar::new(&registry);
+        assert_eq!(hotbar.selected(), registry.id_of("stone"));
+        assert!(hotbar.blocks.iter().all(|&id| registry.get(id).solid));
+        assert!(!hotbar.blocks.contains(&registry.id_of("water").unwrap()));
+
+        assert!(hotbar.process_keyboard(KeyCode::Digit2, ElementState::Pressed));
+        assert_eq!(hotbar.selected(), Some(hotbar.blocks[1]));
+        assert!(!hotbar.process_keyboard(KeyCode::KeyW, ElementState::Pressed));
+    }
+
+    #[test]
+    fn scrolling_wraps_around() {
+        let mut hotbar = Hotbar::new(&BlockRegistry::builtin());
+        let last = *hotbar.blocks.last().unwrap();
+        hotbar.scroll(-1);
+        assert_eq!(hotbar.selected(), Some(last));
+        hotbar.scroll(2);
+        assert_eq!(hotbar.selected(), Some(hotbar.blocks[1]));
+
+        let mut empty = Hotbar::new(&BlockRegistry::parse("[]").unwrap());
+        empty.scroll(3);
+        assert_eq!(empty.selected(), None);
+        assert!(empty.process_keyboard(KeyCode::Digit1, ElementState::Pressed));
+    }
+}
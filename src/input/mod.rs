@@ -0,0 +1,10 @@
+//! # Input Module
+//! 
+//! This module turns keyboard and mouse input into movement of the camera and the
+//! selection of the block to place.
+
+mod controller;
+mod hotbar;
+
+pub use controller::{CameraController, MovementInput};
+pub use hotbar::Hotbar;
@@ -0,0 +1,534 @@
+//! # Lighting Module
+//! 
+//! This module computes sky light and block light with breadth-first flood fills.
+//! A generated chunk is lit on its own first: sky light falls straight down each
+//! column and light-emitting blocks shine, and both spread outwards, losing a level
+//! with every block. Once the chunk is added to the world, light is spread across
+//! its borders into the loaded neighbours and back.
+//! 
+//! When a block changes, the light that may have passed through it is removed by a
+//! second flood fill, which stops at blocks lit from elsewhere; those then fill the
+//! darkened area again. Both fills cross chunk borders freely, and report the
+//! chunks whose meshes need rebuilding.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::meshing::Face;
+use crate::world::{
+    local_coord, BlockId, BlockRegistry, Chunk, ChunkPos, Light, LightKind, World, AIR, CHUNK_HEIGHT, CHUNK_SIZE,
+    MAX_LIGHT, SECTION_COUNT, SECTION_SIZE,
+};
+
+/// Blocks and their light, as seen by the flood fills.
+trait LightGrid {
+    /// Returns the block at the given world coordinates, or `None` if light cannot
+    /// reach it, e.g. because its chunk is not loaded.
+    fn block(&self, pos: [i32; 3]) -> Option<BlockId>;
+
+    /// Returns the light at the given world coordinates.
+    fn light(&self, pos: [i32; 3]) -> Light;
+
+    /// Sets the light at the given world coordinates.
+    fn set_light(&mut self, pos: [i32; 3], light: Light);
+}
+
+/// A chunk on its own, before it is added to the world. Light does not leave it.
+impl LightGrid for Chunk {
+    fn block(&self, [x, y, z]: [i32; 3]) -> Option<BlockId> {
+        let (origin_x, origin_z) = self.pos().origin();
+        let (x, z) = (x - origin_x, z - origin_z);
+        let size = CHUNK_SIZE as i32;
+        if !(0..size).contains(&x) || !(0..size).contains(&z) || !(0..CHUNK_HEIGHT as i32).contains(&y) {
+            return None;
+        }
+        Some(self.get(x as usize, y as usize, z as usize))
+    }
+
+    fn light(&self, [x, y, z]: [i32; 3]) -> Light {
+        self.light(local_coord(x), y as usize, local_coord(z))
+    }
+
+    fn set_light(&mut self, [x, y, z]: [i32; 3], light: Light) {
+        self.set_light(local_coord(x), y as usize, local_coord(z), light);
+    }
+}
+
+/// The loaded chunks of the world, recording the chunks whose meshes show changed light.
+struct WorldGrid<'a> {
+    world: &'a mut World,
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'a> WorldGrid<'a> {
+    fn new(world: &'a mut World) -> Self {
+        Self { world, changed: HashSet::new() }
+    }
+}
+
+impl LightGrid for WorldGrid<'_> {
+    fn block(&self, [x, y, z]: [i32; 3]) -> Option<BlockId> {
+        let loaded = self.world.is_loaded(ChunkPos::from_block(x, z));
+        (loaded && (0..CHUNK_HEIGHT as i32).contains(&y)).then(|| self.world.get_block(x, y, z))
+    }
+
+    fn light(&self, [x, y, z]: [i32; 3]) -> Light {
+        self.world.light(x, y, z).unwrap_or(Light::DARK)
+    }
+
+    fn set_light(&mut self, [x, y, z]: [i32; 3], light: Light) {
+        self.world.set_light(x, y, z, light);
+        // Meshes sample the light of the blocks around their faces, so blocks on a
+        // chunk border also change the meshes of the chunks next to them.
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                self.changed.insert(ChunkPos::from_block(x + dx, z + dz));
+            }
+        }
+    }
+}
+
+/// Computes the light of a generated chunk on its own, as if no other chunk were
+/// loaded. Light from and into the neighbours is added by `stitch_chunk` once the
+/// chunk is in the world.
+pub fn light_chunk(chunk: &mut Chunk, registry: &BlockRegistry) {
+    chunk.fill_light(Light::DARK);
+    let (origin_x, origin_z) = chunk.pos().origin();
+
+    // Sky light falls down each column until something blocks it.
+    let mut queue = VecDeque::new();
+    for z in 0..CHUNK_SIZE as i32 {
+        for x in 0..CHUNK_SIZE as i32 {
+            let mut level = MAX_LIGHT;
+            for y in (0..CHUNK_HEIGHT as i32).rev() {
+                let pos = [origin_x + x, y, origin_z + z];
+                level = spread(registry, LightKind::Sky, level, Face::NegY, LightGrid::block(chunk, pos).unwrap());
+                if level == 0 {
+                    break;
+                }
+                LightGrid::set_light(chunk, pos, Light::new(level, 0));
+                queue.push_back(pos);
+            }
+        }
+    }
+    increase(chunk, registry, LightKind::Sky, queue);
+
+    let mut queue = VecDeque::new();
+    for section_y in 0..SECTION_COUNT {
+        if chunk.sections()[section_y].is_empty() {
+            continue;
+        }
+        for y in 0..SECTION_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                for x in 0..CHUNK_SIZE as i32 {
+                    let pos = [origin_x + x, (section_y * SECTION_SIZE) as i32 + y, origin_z + z];
+                    let emission = emission(registry, LightKind::Block, LightGrid::block(chunk, pos).unwrap());
+                    if emission > 0 {
+                        let light = LightGrid::light(chunk, pos);
+                        LightGrid::set_light(chunk, pos, light.with(LightKind::Block, emission));
+                        queue.push_back(pos);
+                    }
+                }
+            }
+        }
+    }
+    increase(chunk, registry, LightKind::Block, queue);
+
+    chunk.compact_light();
+}
+
+/// Spreads light across the borders between a chunk that was just added to the
+/// world and its loaded neighbours, both ways. Returns the chunks whose meshes
+/// show changed light.
+pub fn stitch_chunk(world: &mut World, registry: &BlockRegistry, pos: ChunkPos) -> HashSet<ChunkPos> {
+    let mut grid = WorldGrid::new(world);
+    let (origin_x, origin_z) = pos.origin();
+    let last = CHUNK_SIZE as i32 - 1;
+
+    // Pairs of a block on the border of the chunk and the block across it.
+    let mut pairs = Vec::new();
+    for i in 0..CHUNK_SIZE as i32 {
+        pairs.push(([origin_x, origin_z + i], [origin_x - 1, origin_z + i]));
+        pairs.push(([origin_x + last, origin_z + i], [origin_x + last + 1, origin_z + i]));
+        pairs.push(([origin_x + i, origin_z], [origin_x + i, origin_z - 1]));
+        pairs.push(([origin_x + i, origin_z + last], [origin_x + i, origin_z + last + 1]));
+    }
+    pairs.retain(|&(_, [x, z])| grid.world.is_loaded(ChunkPos::from_block(x, z)));
+
+    for kind in LightKind::ALL {
+        let mut queue = VecDeque::new();
+        for &([x, z], [nx, nz]) in &pairs {
+            for y in 0..CHUNK_HEIGHT as i32 {
+                for pos in [[x, y, z], [nx, y, nz]] {
+                    if grid.light(pos).get(kind) > 1 {
+                        queue.push_back(pos);
+                    }
+                }
+            }
+        }
+        increase(&mut grid, registry, kind, queue);
+    }
+    grid.changed
+}
+
+/// Updates the light around a block that changed, e.g. because it was placed or
+/// broken. Returns the chunks whose meshes show changed light.
+pub fn update_block(world: &mut World, registry: &BlockRegistry, x: i32, y: i32, z: i32) -> HashSet<ChunkPos> {
+    let mut grid = WorldGrid::new(world);
+    let pos = [x, y, z];
+    let Some(block) = grid.block(pos) else {
+        return grid.changed;
+    };
+
+    for kind in LightKind::ALL {
+        // Remove the light the block had, and everything lit through it.
+        let light = grid.light(pos);
+        let source = emission(registry, kind, block);
+        grid.set_light(pos, light.with(kind, source));
+        let mut refill = decrease(&mut grid, registry, kind, VecDeque::from([(pos, light.get(kind))]));
+
+        // Let the block's own light and the light around it back in.
+        if source > 0 {
+            refill.push_back(pos);
+        }
+        for face in Face::ALL {
+            let neighbor = offset(pos, face);
+            if grid.block(neighbor).is_some() {
+                refill.push_back(neighbor);
+            } else if kind == LightKind::Sky && neighbor[1] >= CHUNK_HEIGHT as i32 {
+                // The top of the world is lit by the open sky.
+                let level = spread(registry, kind, MAX_LIGHT, Face::NegY, block);
+                if level > grid.light(pos).sky() {
+                    grid.set_light(pos, grid.light(pos).with(kind, level));
+                    refill.push_back(pos);
+                }
+            }
+        }
+        increase(&mut grid, registry, kind, refill);
+    }
+    grid.changed
+}
+
+/// Returns the light of one kind a block emits.
+fn emission(registry: &BlockRegistry, kind: LightKind, block: BlockId) -> u8 {
+    match kind {
+        LightKind::Sky => 0,
+        LightKind::Block => registry.get(block).light_emission,
+    }
+}
+
+/// Returns the level light of `level` has once it moves through `face` of its
+/// block into `neighbor`.
+///
+/// Opaque blocks stop light. Light fades by a level with every block, except full
+/// sky light falling straight down through air.
+fn spread(registry: &BlockRegistry, kind: LightKind, level: u8, face: Face, neighbor: BlockId) -> u8 {
+    if registry.is_opaque(neighbor) {
+        0
+    } else if kind == LightKind::Sky && level == MAX_LIGHT && face == Face::NegY && neighbor == AIR {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Returns the position next to `pos` across a face.
+fn offset(pos: [i32; 3], face: Face) -> [i32; 3] {
+    let normal = face.normal();
+    [0, 1, 2].map(|axis| pos[axis] + normal[axis])
+}
+
+/// Spreads the light of the queued blocks until every block is at least as bright
+/// as the light reaching it from its neighbours.
+fn increase(grid: &mut impl LightGrid, registry: &BlockRegistry, kind: LightKind, mut queue: VecDeque<[i32; 3]>) {
+    while let Some(pos) = queue.pop_front() {
+        let level = grid.light(pos).get(kind);
+        if level <= 1 {
+            continue;
+        }
+        for face in Face::ALL {
+            let neighbor = offset(pos, face);
+            let Some(block) = grid.block(neighbor) else {
+                continue;
+            };
+            let reached = spread(registry, kind, level, face, block);
+            let light = grid.light(neighbor);
+            if reached > light.get(kind) {
+                grid.set_light(neighbor, light.with(kind, reached));
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Removes the light that spread from the queued blocks, given with the level they
+/// had. Returns the blocks bordering the darkened area that are lit from elsewhere,
+/// to fill it again with `increase`.
+fn decrease(
+    grid: &mut impl LightGrid,
+    registry: &BlockRegistry,
+    kind: LightKind,
+    mut queue: VecDeque<([i32; 3], u8)>,
+) -> VecDeque<[i32; 3]> {
+    let mut refill = VecDeque::new();
+    while let Some((pos, level)) = queue.pop_front() {
+        for face in Face::ALL {
+            let neighbor = offset(pos, face);
+            let Some(block) = grid.block(neighbor) else {
+                continue;
+            };
+            let light = grid.light(neighbor);
+            let current = light.get(kind);
+            if current == 0 {
+                continue;
+            }
+
+            // A neighbour no brighter than the light that reached it may have been
+            // lit through the removed block.
+            if current <= spread(registry, kind, level, face, block) {
+                let source = emission(registry, kind, block);
+                grid.set_light(neighbor, light.with(kind, source));
+                if source > 0 {
+                    refill.push_back(neighbor);
+                }
+                queue.push_back((neighbor, current));
+            } else {
+                refill.push_back(neighbor);
+            }
+        }
+    }
+    refill
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // IDs of the test block definitions.
+    const STONE: BlockId = 1;
+    const TORCH: BlockId = 2;
+    const GLASS: BlockId = 3;
+    const LAMP: BlockId = 4;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::parse(r#"[
+            (id: 1, name: "stone", textures: All("stone")),
+            (id: 2, name: "torch", solid: false, transparent: true, textures: All("torch"), light_emission: 14),
+            (id: 3, name: "glass", transparent: true, textures: All("glass")),
+            (id: 4, name: "lamp", textures: All("lamp"), light_emission: 15),
+        ]"#).unwrap()
+    }
+
+    /// Builds a world of the given chunks with a stone floor at y = 0..=9, and lights
+    /// it the way chunks are lit when they are streamed in, one after another.
+    fn flat_world(registry: &BlockRegistry, chunks: &[ChunkPos]) -> World {
+        let mut world = World::new();
+        for &pos in chunks {
+            let mut chunk = Chunk::new(pos);
+            for y in 0..10 {
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        chunk.set(x, y, z, STONE);
+                    }
+                }
+            }
+            light_chunk(&mut chunk, registry);
+            world.insert_chunk(chunk);
+            stitch_chunk(&mut world, registry, pos);
+        }
+        world
+    }
+
+    /// Changes a block and updates the light around it.
+    fn set(world: &mut World, registry: &BlockRegistry, [x, y, z]: [i32; 3], block: BlockId) -> HashSet<ChunkPos> {
+        world.set_block(x, y, z, block);
+        update_block(world, registry, x, y, z)
+    }
+
+    fn sky(world: &World, [x, y, z]: [i32; 3]) -> u8 {
+        world.light(x, y, z).unwrap().sky()
+    }
+
+    fn block(world: &World, [x, y, z]: [i32; 3]) -> u8 {
+        world.light(x, y, z).unwrap().block()
+    }
+
+    /// Computes the light of every chunk from scratch, for comparison with
+    /// incremental updates.
+    fn relit(world: &World, registry: &BlockRegistry) -> World {
+        let mut fresh = World::new();
+        for chunk in world.chunks() {
+            let mut chunk = chunk.clone();
+            light_chunk(&mut chunk, registry);
+            fresh.insert_chunk(chunk);
+        }
+        let positions: Vec<ChunkPos> = fresh.chunks().map(Chunk::pos).collect();
+        for pos in positions {
+            stitch_chunk(&mut fresh, registry, pos);
+        }
+        fresh
+    }
+
+    fn assert_same_light(world: &World, expected: &World) {
+        for chunk in expected.chunks() {
+            let (origin_x, origin_z) = chunk.pos().origin();
+            for y in 0..CHUNK_HEIGHT as i32 {
+                for z in origin_z..origin_z + CHUNK_SIZE as i32 {
+                    for x in origin_x..origin_x + CHUNK_SIZE as i32 {
+                        assert_eq!(world.light(x, y, z), expected.light(x, y, z), "at {:?}", (x, y, z));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn open_sky_lights_everything_above_the_ground() {
+        let registry = registry();
+        let world = flat_world(&registry, &[ChunkPos::new(0, 0)]);
+        assert_eq!(sky(&world, [3, 10, 3]), 15);
+        assert_eq!(sky(&world, [3, 127, 3]), 15);
+        assert_eq!(sky(&world, [3, 9, 3]), 0);
+        assert_eq!(world.light(3, 200, 3), Some(Light::SKY));
+    }
+
+    #[test]
+    fn torches_light_their_surroundings_until_removed() {
+        let registry = registry();
+        let chunks = [ChunkPos::new(0, 0), ChunkPos::new(-1, 0)];
+        let mut world = flat_world(&registry, &chunks);
+        let torch = [2, 10, 8];
+
+        set(&mut world, &registry, torch, TORCH);
+        assert_eq!(block(&world, torch), 14);
+        assert_eq!(block(&world, [5, 10, 8]), 11);
+        assert_eq!(block(&world, [2, 12, 9]), 11);
+        // Light fades across the chunk border, and not through the stone floor.
+        assert_eq!(block(&world, [-1, 10, 8]), 11);
+        assert_eq!(block(&world, [-11, 10, 8]), 1);
+        assert_eq!(block(&world, [-12, 10, 8]), 0);
+        assert_eq!(block(&world, [2, 9, 8]), 0);
+        assert_same_light(&world, &relit(&world, &registry));
+
+        let changed = set(&mut world, &registry, torch, AIR);
+        assert!(changed.contains(&ChunkPos::new(0, 0)) && changed.contains(&ChunkPos::new(-1, 0)));
+        assert_eq!(block(&world, torch), 0);
+        assert_eq!(block(&world, [-1, 10, 8]), 0);
+        // Without the torch, there is no block light anywhere.
+        assert_same_light(&world, &relit(&world, &registry));
+    }
+
+    #[test]
+    fn removing_one_of_two_torches_keeps_the_other_lit() {
+        let registry = registry();
+        let mut world = flat_world(&registry, &[ChunkPos::new(0, 0)]);
+        set(&mut world, &registry, [2, 10, 2], TORCH);
+        set(&mut world, &registry, [6, 10, 2], TORCH);
+        assert_eq!(block(&world, [4, 10, 2]), 12);
+
+        set(&mut world, &registry, [2, 10, 2], AIR);
+        assert_eq!(block(&world, [4, 10, 2]), 12);
+        assert_eq!(block(&world, [2, 10, 2]), 10);
+        assert_same_light(&world, &relit(&world, &registry));
+    }
+
+    #[test]
+    fn roofs_block_sky_light() {
+        let registry = registry();
+        let mut world = flat_world(&registry, &[ChunkPos::new(0, 0), ChunkPos::new(1, 0)]);
+
+        // A roof over x = 4..=19 and z = 4..=11, across the chunk border, at y = 14.
+        for z in 4..12 {
+            for x in 4..20 {
+                set(&mut world, &registry, [x, 14, z], STONE);
+            }
+        }
+        assert_eq!(sky(&world, [12, 15, 8]), 15);
+        // Under the roof, the sky light only comes in from the sides.
+        assert_eq!(sky(&world, [12, 13, 8]), 11);
+        assert_eq!(sky(&world, [12, 10, 8]), 11);
+        assert_eq!(sky(&world, [16, 10, 11]), 14);
+        assert_eq!(sky(&world, [3, 10, 8]), 15);
+        assert_same_light(&world, &relit(&world, &registry));
+
+        // Glass lets the light through, but not as full sky light.
+        set(&mut world, &registry, [12, 14, 8], GLASS);
+        assert_eq!(sky(&world, [12, 14, 8]), 14);
+        assert_eq!(sky(&world, [12, 13, 8]), 13);
+        assert_same_light(&world, &relit(&world, &registry));
+
+        // Opening the roof lets full sky light fall to the ground again.
+        set(&mut world, &registry, [12, 14, 8], AIR);
+        assert_eq!(sky(&world, [12, 10, 8]), 15);
+        assert_eq!(sky(&world, [11, 10, 8]), 14);
+        assert_same_light(&world, &relit(&world, &registry));
+    }
+
+    #[test]
+    fn opaque_blocks_can_emit_light() {
+        let registry = registry();
+        let mut world = flat_world(&registry, &[ChunkPos::new(0, 0)]);
+        set(&mut world, &registry, [8, 9, 8], LAMP);
+        assert_eq!(block(&world, [8, 9, 8]), 15);
+        assert_eq!(block(&world, [8, 10, 8]), 14);
+        // The stone around the lamp stays dark.
+        assert_eq!(block(&world, [8, 8, 8]), 0);
+
+        // Covered up, the lamp's light cannot get out.
+        set(&mut world, &registry, [8, 10, 8], STONE);
+        assert_eq!(block(&world, [8, 10, 8]), 0);
+        assert_eq!(block(&world, [8, 11, 8]), 0);
+        assert_same_light(&world, &relit(&world, &registry));
+    }
+
+    #[test]
+    fn chunks_loaded_later_receive_and_pass_on_light() {
+        let registry = registry();
+        let mut world = flat_world(&registry, &[ChunkPos::new(0, 0)]);
+        set(&mut world, &registry, [1, 10, 8], TORCH);
+
+        // A chunk with nothing but a roof at y = 12.
+        let mut chunk = Chunk::new(ChunkPos::new(-1, 0));
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, 12, z, STONE);
+            }
+        }
+        light_chunk(&mut chunk, &registry);
+        world.insert_chunk(chunk);
+        let changed = stitch_chunk(&mut world, &registry, ChunkPos::new(-1, 0));
+
+        assert!(changed.contains(&ChunkPos::new(-1, 0)));
+        // Torch light and sky light from the old chunk reach under the new chunk's roof.
+        assert_eq!(block(&world, [-1, 9, 8]), 11);
+        assert_eq!(sky(&world, [-1, 10, 8]), 14);
+        assert_eq!(sky(&world, [-16, 10, 8]), 0);
+        assert_eq!(sky(&world, [-16, 13, 8]), 15);
+        assert_same_light(&world, &relit(&world, &registry));
+    }
+
+    #[test]
+    fn random_edits_match_lighting_from_scratch() {
+        let registry = registry();
+        let chunks = [ChunkPos::new(0, 0), ChunkPos::new(1, 0), ChunkPos::new(0, -1)];
+        let mut world = flat_world(&registry, &chunks);
+
+        // A small deterministic xorshift generator.
+        let mut state = 0x2545_F491u32;
+        let mut next = |range: i32| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % range as u32) as i32
+        };
+        for i in 0..300 {
+            // Somewhere in the loaded chunks, near the floor.
+            let z = next(28) - 12;
+            let pos = [if z < 0 { next(16) } else { next(32) }, 8 + next(8), z];
+            let block = [AIR, AIR, STONE, STONE, TORCH, GLASS, LAMP][next(7) as usize];
+            set(&mut world, &registry, pos, block);
+            if i % 100 == 99 {
+                assert_same_light(&world, &relit(&world, &registry));
+            }
+        }
+    }
+}
@@ -6,19 +6,42 @@
 mod renderer;
 mod monitoring;
 mod debug;
+mod meshing;
+mod lighting;
+mod occlusion;
+mod world;
+mod worldgen;
+mod streaming;
+mod input;
+mod player;
+mod simulation;
 
 use std::sync::Arc;
 use winit::{
-    event::{Event, WindowEvent, KeyEvent, ElementState},
-    event_loop::{ControlFlow, EventLoop},
+    event::{DeviceEvent, Event, WindowEvent, KeyEvent, ElementState, MouseButton, MouseScrollDelta},
+    event_loop::EventLoop,
     window::WindowBuilder,
     keyboard::{PhysicalKey, KeyCode},
 };
 use log::{info, warn, error};
 
-use crate::renderer::Renderer;
+use crate::renderer::{Aabb, AtlasBuilder, Renderer};
 use crate::monitoring::SystemMonitor;
 use crate::debug::overlay::DebugOverlay;
+use crate::input::{CameraController, Hotbar};
+use crate::player::{BlockBreaker, Player, EYE_HEIGHT, REACH};
+use crate::meshing::BlockTextureMap;
+use crate::simulation::{FixedTimestep, WorldTime, TICK, TIME_PRESETS};
+use crate::streaming::{ChunkManager, ChunkScheduler};
+use crate::world::{raycast, BlockRegistry, ChunkPos, World, AIR};
+use crate::worldgen::{GeneratorPipeline, OreVein};
+
+/// The seed of the generated world.
+const WORLD_SEED: u64 = 0x00C0_FFEE_5EED;
+
+/// How many chunks past the render distance chunks stay loaded, so they do not
+/// thrash when the camera moves back and forth across a chunk border.
+const UNLOAD_MARGIN: i32 = 2;
 
 /// The main entry point of the application.
 #[tokio::main]
@@ -44,10 +67,23 @@ async fn main() -> anyhow::Result<()> {
           window.inner_size().width, 
           window.inner_size().height);
 
+    // Load the block definitions and pack their textures into an atlas.
+    let registry = Arc::new(BlockRegistry::load("assets/blocks.ron", "assets/textures/blocks")?);
+    info!("Loaded {} block definitions", registry.iter().count());
+
+    let atlas = AtlasBuilder::new(4).add_dir("assets/textures/blocks")?.build()?;
+    let block_textures = Arc::new(BlockTextureMap::new(&registry, &atlas));
+    info!("Built {}x{} texture atlas", atlas.size(), atlas.size());
+
     // Initialize the wgpu renderer.
-    let mut renderer = Renderer::new(Arc::clone(&window)).await?;
+    let mut renderer = Renderer::new(Arc::clone(&window), &atlas).await?;
     info!("Renderer initialized with wgpu");
 
+    // Set up world generation on the worker threads.
+    let ore_veins = OreVein::load("assets/ores.ron")?;
+    let generator = GeneratorPipeline::standard(WORLD_SEED, &registry, &ore_veins)?;
+    info!("World generation stages: {}", generator.stage_names().collect::<Vec<_>>().join(", "));
+
     // Initialize the system monitor for debug statistics.
     let mut system_monitor = SystemMonitor::new();
     info!("System monitor initialized");
@@ -56,6 +92,43 @@ async fn main() -> anyhow::Result<()> {
     let mut debug_overlay = DebugOverlay::new();
     info!("Debug overlay initialized");
 
+    debug_overlay.reverse_z = renderer.reverse_z();
+    debug_overlay.wireframe_supported = renderer.supports_wireframe();
+
+    // Generate and mesh the world in the background, with the mode selected in the overlay.
+    let mut mesh_mode = debug_overlay.mesh_mode;
+    let threads = std::thread::available_parallelism().map_or(2, |n| n.get()).saturating_sub(1).max(1);
+    let scheduler = ChunkScheduler::new(Arc::new(generator), Arc::clone(&registry), block_textures, mesh_mode, threads);
+    let mut chunk_manager = ChunkManager::new(scheduler, debug_overlay.render_distance, UNLOAD_MARGIN);
+    let mut world = World::new();
+    let mut loading = Some(std::time::Instant::now());
+    info!("Streaming chunks on {} worker threads", threads);
+
+    // Fly the camera with the keyboard and mouse, or walk with the player.
+    let mut controller = CameraController::new(renderer.camera());
+    let eye = renderer.camera().position;
+    let mut player = Player::new(cgmath::Vector3::new(eye.x, eye.y - EYE_HEIGHT, eye.z));
+    let mut flying = true;
+
+    // Break blocks by holding the left mouse button, and place the selected block
+    // with the right one.
+    let mut hotbar = Hotbar::new(&registry);
+    let mut breaker = BlockBreaker::new();
+    let mut breaking = false;
+    // Only solid blocks can be targeted, so water and air are reached through. Their
+    // hit boxes are as high as the blocks.
+    let targetable = {
+        let registry = Arc::clone(&registry);
+        move |block| {
+            let def = registry.get(block);
+            def.solid.then_some(def.height)
+        }
+    };
+
+    // Simulate the game at a fixed tick rate, independent of the frame rate,
+    // starting in the morning.
+    let mut timestep = FixedTimestep::new(TICK);
+    let mut world_time = WorldTime::new(TIME_PRESETS[0].1);
     let mut last_render_time = std::time::Instant::now();
     
     // Start the event loop.
@@ -66,7 +139,7 @@ async fn main() -> anyhow::Result<()> {
                 window_id,
             } if window_id == window.id() => {
                 // Pass events to the GUI for processing.
-                renderer.gui_manager.handle_event(&window, event);
+                let consumed = renderer.handle_gui_event(&window, event);
                 
                 match event {
                     WindowEvent::CloseRequested => {
@@ -89,6 +162,96 @@ async fn main() -> anyhow::Result<()> {
                         // Toggle the debug overlay with F3.
                         debug_overlay.toggle();
                     }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(KeyCode::F2),
+                            state: ElementState::Pressed,
+                            repeat: false,
+                            ..
+                        },
+                        ..
+                    } => {
+                        // Save a screenshot of the next frame with F2.
+                        renderer.request_screenshot(debug_overlay.screenshot_overlay);
+                    }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(KeyCode::KeyF),
+                            state: ElementState::Pressed,
+                            repeat: false,
+                            ..
+                        },
+                        ..
+                    } if !consumed => {
+                        // Switch between flying and walking with F.
+                        flying = !flying;
+                        info!("{}", if flying { "Flying" } else { "Walking" });
+                    }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(KeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                        ..
+                    } => {
+                        // Release the cursor with Escape.
+                        controller.set_cursor_grab(&window, false);
+                    }
+                    WindowEvent::KeyboardInput {
+                        event: KeyEvent {
+                            physical_key: PhysicalKey::Code(key),
+                            state,
+                            ..
+                        },
+                        ..
+                    } if !consumed || *state == ElementState::Released => {
+                        // The number keys select a block; the other keys move the camera.
+                        // Releases always pass, so keys held when the GUI took focus stop.
+                        let _ = hotbar.process_keyboard(*key, *state) || controller.process_keyboard(*key, *state);
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if !consumed && !controller.is_grabbed() => {
+                        // Grab the cursor when the scene is clicked.
+                        controller.set_cursor_grab(&window, true);
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } if controller.is_grabbed() || *state == ElementState::Released => {
+                        breaking = *state == ElementState::Pressed;
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Right,
+                        ..
+                    } if controller.is_grabbed() => {
+                        // Place the selected block against the face of the targeted block,
+                        // unless the player is in the way.
+                        let camera = renderer.camera();
+                        let target = raycast(&world, camera.position, camera.forward(), REACH, &targetable);
+                        if let (Some([x, y, z]), Some(block)) = (target.and_then(|hit| hit.adjacent()), hotbar.selected()) {
+                            if !registry.get(world.get_block(x, y, z)).solid && !player.overlaps_block([x, y, z]) {
+                                chunk_manager.set_block(&mut world, x, y, z, block);
+                            }
+                        }
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let steps = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => -y.signum() as i32,
+                            MouseScrollDelta::PixelDelta(position) => -position.y.signum() as i32,
+                        };
+                        hotbar.scroll(steps);
+                    }
+                    WindowEvent::Focused(false) => {
+                        controller.set_cursor_grab(&window, false);
+                        controller.release_keys();
+                        breaking = false;
+                    }
                     WindowEvent::RedrawRequested => {
                         let now = std::time::Instant::now();
                         let dt = now - last_render_time;
@@ -97,10 +260,89 @@ async fn main() -> anyhow::Result<()> {
                         // Update the system monitor.
                         system_monitor.update();
 
+                        // Turn the camera, and fly it when not walking.
+                        let camera = renderer.camera_mut();
+                        if flying {
+                            controller.update(camera, dt);
+                            player.teleport(cgmath::Vector3::new(camera.position.x, camera.position.y - EYE_HEIGHT, camera.position.z));
+                        } else {
+                            controller.turn(camera);
+                        }
+
+                        // Apply a time of day picked in the overlay.
+                        if let Some(time) = debug_overlay.set_time.take() {
+                            world_time.set_time_of_day(time);
+                        }
+
+                        // Run the game ticks that are due.
+                        for _ in 0..timestep.advance(dt) {
+                            let start = std::time::Instant::now();
+
+                            if debug_overlay.daylight_cycle {
+                                world_time.tick();
+                            }
+
+                            // Hold the player in place until the ground under them is loaded.
+                            let feet = player.position();
+                            if !flying && world.is_loaded(ChunkPos::from_block(feet.x.floor() as i32, feet.z.floor() as i32)) {
+                                player.tick(controller.movement(), controller.yaw(), &world, &registry);
+                            }
+
+                            // Break the block the camera is looking at while the button is held.
+                            let camera = renderer.camera();
+                            let target = raycast(&world, camera.position, camera.forward(), REACH, &targetable);
+                            if let Some([x, y, z]) = breaker.update(TICK, breaking && controller.is_grabbed(), target.as_ref(), &registry) {
+                                chunk_manager.set_block(&mut world, x, y, z, AIR);
+                            }
+
+                            system_monitor.record_tick(start.elapsed());
+                        }
+
+                        // Draw the sky and the walking player between the last two ticks.
+                        let alpha = if debug_overlay.daylight_cycle { timestep.alpha() } else { 0.0 };
+                        renderer.set_time_of_day(world_time.fraction(alpha));
+                        debug_overlay.time = world_time;
+                        if !flying {
+                            let camera = renderer.camera_mut();
+                            camera.translate(player.eye(timestep.alpha()) - camera.position);
+                        }
+
+                        // Stream the chunks around the camera.
+                        let eye = renderer.camera().position;
+                        if debug_overlay.render_distance != chunk_manager.render_distance() {
+                            chunk_manager.set_render_distance(debug_overlay.render_distance);
+                        }
+                        chunk_manager.update(eye.x, eye.z, &mut world, &mut renderer);
+                        debug_overlay.chunks = chunk_manager.stats(&world);
+                        if let Some(start) = loading.filter(|_| chunk_manager.is_idle()) {
+                            info!(
+                                "Generated and meshed {} chunks in {:.2}ms",
+                                world.chunk_count(),
+                                start.elapsed().as_secs_f64() * 1000.0
+                            );
+                            loading = None;
+                        }
+
+                        // Look up the biome and the light at the camera.
+                        let (x, y, z) = (eye.x.floor() as i32, eye.y.floor() as i32, eye.z.floor() as i32);
+                        debug_overlay.biome = world.biome_at(x, z);
+                        debug_overlay.light = world.light(x, y, z);
+
+                        // Outline the targeted block, and show it in the overlay.
+                        let target = raycast(&world, eye, renderer.camera().forward(), REACH, &targetable);
+                        renderer.set_block_outline(target.map(|hit| {
+                            let min = cgmath::Vector3::from(hit.pos.map(|v| v as f32));
+                            let size = cgmath::Vector3::new(1.0, registry.get(hit.block).height, 1.0);
+                            (Aabb::new(min, min + size), breaker.progress().unwrap_or(0.0))
+                        }));
+                        debug_overlay.target = target.map(|hit| (hit, registry.get(hit.block).name.clone()));
+                        debug_overlay.selected_block = hotbar.selected().map_or("-", |block| &registry.get(block).name).to_string();
+
                         // Render the scene.
-                        match renderer.render(&window, &debug_overlay, &system_monitor) {
+                        match renderer.render(&window, &mut debug_overlay, &system_monitor) {
                             Ok(_) => {
                                 system_monitor.record_frame(dt);
+                                system_monitor.record_triangles(renderer.triangle_count());
                             }
                             Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                                 // Recreate the swap chain if it's lost or outdated.
@@ -114,10 +356,26 @@ async fn main() -> anyhow::Result<()> {
                                 warn!("Render error: {:?}", e);
                             }
                         }
+
+                        // Remesh the world if the overlay switched meshing modes.
+                        if debug_overlay.mesh_mode != mesh_mode {
+                            mesh_mode = debug_overlay.mesh_mode;
+                            chunk_manager.set_mesh_mode(&world, mesh_mode);
+                            system_monitor.reset_frame_times();
+                        }
+                        if debug_overlay.reverse_z != renderer.reverse_z() {
+                            renderer.set_reverse_z(debug_overlay.reverse_z);
+                        }
                     }
                     _ => {}
                 }
             }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                controller.process_mouse_motion(delta.0, delta.1);
+            }
             Event::AboutToWait => {
                 // Request a redraw on the next cycle.
                 window.request_redraw();
@@ -0,0 +1,279 @@
+//! # Culled Mesher
+//! 
+//! This module implements the basic mesher, which emits one quad for every block
+//! face that touches air.
+
+use crate::meshing::{face_shading, is_face_visible, BlockTextureMap, ChunkMesh, Face};
+use crate::world::{BlockRegistry, ChunkNeighborhood, AIR, CHUNK_SIZE, SECTION_SIZE};
+
+/// Builds the mesh of the center chunk of a neighborhood, emitting only faces that
+/// are not hidden by an opaque neighbour.
+///
+/// Neighbouring chunks are consulted for faces on the chunk border; unloaded
+/// neighbours count as air.
+pub fn mesh_chunk(neighborhood: &ChunkNeighborhood, registry: &BlockRegistry, textures: &BlockTextureMap) -> ChunkMesh {
+    let chunk = neighborhood.center();
+    let (origin_x, origin_z) = chunk.pos().origin();
+    let mut mesh = ChunkMesh::default();
+
+    for (section_y, section) in chunk.sections().iter().enumerate() {
+        if section.is_empty() {
+            continue;
+        }
+
+        for y in 0..SECTION_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let block = section.get(x, y, z);
+                    if block == AIR {
+                        continue;
+                    }
+
+                    let (x, y, z) = (x as i32, (section_y * SECTION_SIZE + y) as i32, z as i32);
+                    let height = registry.get(block).height;
+                    for face in Face::ALL {
+                        let [nx, ny, nz] = face.normal();
+                        if !is_face_visible(registry, block, neighborhood.get(x + nx, y + ny, z + nz), face) {
+                            continue;
+                        }
+                        let origin = [(origin_x + x) as f32, y as f32, (origin_z + z) as f32];
+                        let texture = textures.face(block, face);
+                        let tint = chunk.column(x as usize, z as usize).tint_color(texture.tint);
+                        let shading = face_shading(neighborhood, registry, face, [x, y, z]);
+                        mesh.push_quad(face, origin, 1, 1, texture, tint, shading, height);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{BlockId, ChunkPos, World};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const DIRT: BlockId = 2;
+    const WATER: BlockId = 5;
+    const SLAB: BlockId = 15;
+
+    fn mesh_at(world: &World, pos: ChunkPos) -> ChunkMesh {
+        let registry = BlockRegistry::builtin();
+        mesh_chunk(&world.neighborhood(pos).unwrap(), &registry, &BlockTextureMap::untextured(&registry))
+    }
+
+    #[test]
+    fn single_block_has_six_faces() {
+        let mut world = World::new();
+        world.set_block(4, 10, 4, STONE);
+
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert_eq!(mesh.quad_count(), 6);
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_eq!(mesh.indices.len(), 36);
+    }
+
+    #[test]
+    fn adjacent_blocks_hide_shared_faces() {
+        let mut world = World::new();
+        world.set_block(4, 10, 4, STONE);
+        world.set_block(5, 10, 4, DIRT);
+
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, 0)).quad_count(), 10);
+    }
+
+    #[test]
+    fn solid_cube_only_has_surface_faces() {
+        let mut world = World::new();
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    world.set_block(x, y + 30, z, STONE);
+                }
+            }
+        }
+
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, 0)).quad_count(), 6 * 9);
+    }
+
+    #[test]
+    fn hollow_shell_has_inner_and_outer_faces() {
+        let mut world = World::new();
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    if (x, y, z) != (1, 1, 1) {
+                        world.set_block(x, y + 30, z, STONE);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, 0)).quad_count(), 6 * 9 + 6);
+    }
+
+    #[test]
+    fn faces_are_culled_across_chunk_borders() {
+        let mut world = World::new();
+        world.set_block(-1, 10, 0, STONE);
+        world.set_block(0, 10, 0, STONE);
+        world.set_block(0, 10, -1, STONE);
+
+        // Each block hides one face towards its neighbour in the other chunk.
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, 0)).quad_count(), 4);
+        assert_eq!(mesh_at(&world, ChunkPos::new(-1, 0)).quad_count(), 5);
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, -1)).quad_count(), 5);
+    }
+
+    #[test]
+    fn translucent_faces_are_indexed_separately() {
+        let mut world = World::new();
+        world.set_block(4, 10, 4, STONE);
+        world.set_block(5, 10, 4, WATER);
+        world.set_block(6, 10, 4, WATER);
+
+        // The stone face behind the water stays visible, the water faces against the
+        // stone and between the two water blocks are hidden.
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert_eq!(mesh.indices.len(), 6 * 6);
+        assert_eq!(mesh.translucent_indices.len(), 9 * 6);
+        assert_eq!(mesh.quad_count(), 15);
+    }
+
+    #[test]
+    fn unloaded_neighbours_count_as_air() {
+        let mut world = World::new();
+        world.set_block(15, 10, 15, STONE);
+
+        assert_eq!(mesh_at(&world, ChunkPos::new(0, 0)).quad_count(), 6);
+    }
+
+    #[test]
+    fn vertices_are_in_world_space() {
+        let mut world = World::new();
+        world.set_block(-16, 3, 17, STONE);
+
+        let mesh = mesh_at(&world, ChunkPos::new(-1, 1));
+        for vertex in &mesh.vertices {
+            let [x, y, z] = vertex.position;
+            assert!((-16.0..=-15.0).contains(&x));
+            assert!((3.0..=4.0).contains(&y));
+            assert!((17.0..=18.0).contains(&z));
+        }
+    }
+
+    #[test]
+    fn slabs_are_drawn_half_high_and_hide_nothing() {
+        let mut world = World::new();
+        world.set_block(4, 10, 4, STONE);
+        world.set_block(5, 10, 4, SLAB);
+
+        // The stone face beside the slab stays visible; the slab face against the stone is hidden.
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert_eq!(mesh.quad_count(), 6 + 5);
+        let slab: Vec<_> = mesh.vertices.iter().filter(|vertex| vertex.position[0] > 5.0).collect();
+        assert!(slab.iter().all(|vertex| (10.0..=10.5).contains(&vertex.position[1])));
+        assert!(slab.iter().any(|vertex| vertex.position[1] == 10.5));
+
+        // Side faces show the bottom half of their texture.
+        let sides = mesh.vertices.chunks(4).filter(|quad| quad[0].position[0] > 5.0 && quad[0].position[1] != quad[2].position[1]);
+        for quad in sides {
+            assert_eq!(quad.iter().map(|vertex| vertex.tex_coords[1]).fold(1.0, f32::min), 0.5);
+        }
+    }
+
+    #[test]
+    fn stacked_slabs_show_the_gap_between_them() {
+        let mut world = World::new();
+        world.set_block(4, 10, 4, SLAB);
+        world.set_block(4, 11, 4, SLAB);
+
+        // The top of the lower slab and the bottom of the upper one face each other.
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert_eq!(mesh.quad_count(), 12);
+        let flat_at = |y: f32| mesh.vertices.chunks(4).filter(|quad| quad.iter().all(|vertex| vertex.position[1] == y)).count();
+        assert_eq!([10.0, 10.5, 11.0, 11.5].map(flat_at), [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn face_winding_matches_normals() {
+        for face in Face::ALL {
+            let [a, b, c, _] = face.corners();
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let cross = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            assert_eq!(cross.map(|c| c as i32), face.normal(), "{:?}", face);
+        }
+    }
+
+    /// Returns the ambient occlusion at the corners of the top face of the block at
+    /// (4, 3, 4), the lowest block of the world, and the indices of its two triangles.
+    fn top_face_shading(occluders: &[[i32; 3]]) -> ([f32; 4], Vec<u32>) {
+        let mut world = World::new();
+        world.set_block(4, 3, 4, STONE);
+        for &[x, y, z] in occluders {
+            world.set_block(x, y, z, STONE);
+        }
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        let quad = Face::PosY.index();
+        let ao = [0, 1, 2, 3].map(|i| mesh.vertices[quad * 4 + i].ao);
+        let indices = mesh.indices[quad * 6..quad * 6 + 6].iter().map(|&i| i - quad as u32 * 4).collect();
+        (ao, indices)
+    }
+
+    #[test]
+    fn neighbouring_blocks_occlude_face_corners() {
+        // Top face corners run (0, 1), (1, 1), (1, 0), (0, 0) in X and Z.
+        assert_eq!(top_face_shading(&[]).0, [1.0; 4]);
+
+        // A block beside the face occludes the two corners along its edge.
+        let (ao, _) = top_face_shading(&[[5, 4, 4]]);
+        assert_eq!(ao, [1.0, 2.0 / 3.0, 2.0 / 3.0, 1.0]);
+
+        // Two edges fully occlude the corner between them, whatever the diagonal.
+        let (ao, _) = top_face_shading(&[[5, 4, 4], [4, 4, 5]]);
+        assert_eq!(ao[1], 0.0);
+        let (ao, _) = top_face_shading(&[[5, 4, 4], [4, 4, 5], [5, 4, 5]]);
+        assert_eq!(ao, [2.0 / 3.0, 0.0, 2.0 / 3.0, 1.0]);
+
+        // Transparent blocks do not occlude.
+        let mut world = World::new();
+        world.set_block(4, 3, 4, STONE);
+        world.set_block(5, 4, 4, WATER);
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert!(mesh.vertices[..24].iter().all(|vertex| vertex.ao == 1.0));
+    }
+
+    #[test]
+    fn quads_are_split_away_from_occluded_corners() {
+        // Occluding the second corner keeps the default diagonal from the first to the third.
+        let (ao, indices) = top_face_shading(&[[5, 4, 5]]);
+        assert_eq!(ao, [1.0, 2.0 / 3.0, 1.0, 1.0]);
+        assert_eq!(indices, [0, 1, 2, 2, 3, 0]);
+
+        // Occluding the first corner flips the diagonal so it runs from the second to the fourth.
+        let (ao, indices) = top_face_shading(&[[3, 4, 5]]);
+        assert_eq!(ao, [2.0 / 3.0, 1.0, 1.0, 1.0]);
+        assert_eq!(indices, [1, 2, 3, 3, 0, 1]);
+    }
+
+    #[test]
+    fn indices_are_grouped_by_section() {
+        let mut world = World::new();
+        world.set_block(4, 3, 4, STONE);
+        world.set_block(4, 40, 4, STONE);
+        world.set_block(6, 40, 4, WATER);
+
+        let mesh = mesh_at(&world, ChunkPos::new(0, 0));
+        assert_eq!(mesh.section_ends, [36, 36, 72, 72, 72, 72, 72, 72]);
+        assert_eq!(mesh.translucent_section_ends, [0, 0, 36, 36, 36, 36, 36, 36]);
+    }
+}
@@ -0,0 +1,350 @@
+//! # Greedy Mesher
+//! 
+//! This module implements greedy meshing: visible faces that share a plane, a
+//! block type, a tint colour and even shading are merged into the largest
+//! rectangles possible, which drastically reduces the vertex count of flat terrain.
+
+use crate::meshing::{face_shading, is_face_visible, BlockTextureMap, ChunkMesh, Face, FaceShading};
+use crate::world::{BlockId, BlockRegistry, ChunkNeighborhood, AIR, CHUNK_SIZE, SECTION_SIZE};
+
+// Faces are merged within a single section, so every slice is a square.
+const _: () = assert!(CHUNK_SIZE == SECTION_SIZE);
+
+/// A visible face in the merge mask. Faces only merge if all fields match.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Cell {
+    block: BlockId,
+    tint: [f32; 3],
+    /// The light and ambient occlusion at each corner of the face.
+    shading: FaceShading,
+    /// The height of the block, from the registry.
+    height: f32,
+}
+
+impl Cell {
+    /// A cell without a visible face.
+    const EMPTY: Cell = Cell { block: AIR, tint: [1.0; 3], shading: FaceShading { light: [[0.0; 2]; 4], ao: [0.0; 4] }, height: 1.0 };
+
+    /// Returns `true` if the face may be merged with equal faces. Shading is only
+    /// stored at the corners of a quad, so only evenly shaded faces can be merged
+    /// without changing how they look. Faces of blocks shorter than a full block are
+    /// cut to the block, so they are never merged.
+    fn is_mergeable(&self) -> bool {
+        self.shading.is_uniform() && self.height >= 1.0
+    }
+}
+
+/// Builds the mesh of the center chunk of a neighborhood, merging coplanar faces of
+/// the same block and tint.
+///
+/// Quads never span more than one section. Neighbouring chunks are consulted for
+/// faces on the chunk border; unloaded neighbours count as air.
+pub fn mesh_chunk(neighborhood: &ChunkNeighborhood, registry: &BlockRegistry, textures: &BlockTextureMap) -> ChunkMesh {
+    let chunk = neighborhood.center();
+    let (origin_x, origin_z) = chunk.pos().origin();
+    let mut mesh = ChunkMesh::default();
+    let mut mask = [Cell::EMPTY; SECTION_SIZE * SECTION_SIZE];
+
+    for (section_y, section) in chunk.sections().iter().enumerate() {
+        if section.is_empty() {
+            continue;
+        }
+        let base = [0, (section_y * SECTION_SIZE) as i32, 0];
+
+        for face in Face::ALL {
+            let (n, u, v) = face.axes();
+            let normal = face.normal();
+
+            for slice in 0..SECTION_SIZE {
+                // Collect the visible faces of this slice.
+                for (i, cell) in mask.iter_mut().enumerate() {
+                    let mut p = base;
+                    p[n] += slice as i32;
+                    p[u] += (i % SECTION_SIZE) as i32;
+                    p[v] += (i / SECTION_SIZE) as i32;
+
+                    let block = neighborhood.get(p[0], p[1], p[2]);
+                    let neighbor = neighborhood.get(p[0] + normal[0], p[1] + normal[1], p[2] + normal[2]);
+                    *cell = if block != AIR && is_face_visible(registry, block, neighbor, face) {
+                        let tint = textures.face(block, face).tint;
+                        Cell {
+                            block,
+                            tint: chunk.column(p[0] as usize, p[2] as usize).tint_color(tint),
+                            shading: face_shading(neighborhood, registry, face, p),
+                            height: registry.get(block).height,
+                        }
+                    } else {
+                        Cell::EMPTY
+                    };
+                }
+
+                // Merge them into rectangles.
+                for_each_rectangle(&mut mask, |cu, cv, width, height, cell| {
+                    let mut p = base;
+                    p[n] += slice as i32;
+                    p[u] += cu as i32;
+                    p[v] += cv as i32;
+                    let origin = [(origin_x + p[0]) as f32, p[1] as f32, (origin_z + p[2]) as f32];
+                    mesh.push_quad(face, origin, width as u32, height as u32, textures.face(cell.block, face), cell.tint, cell.shading, cell.height);
+                });
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Greedily partitions the non-empty cells of a square mask into rectangles of equal,
+/// mergeable cells, calling `emit(u, v, width, height, cell)` for each one. The mask
+/// is cleared in the process.
+fn for_each_rectangle(mask: &mut [Cell; SECTION_SIZE * SECTION_SIZE], mut emit: impl FnMut(usize, usize, usize, usize, Cell)) {
+    const N: usize = SECTION_SIZE;
+
+    for v in 0..N {
+        let mut u = 0;
+        while u < N {
+            let cell = mask[v * N + u];
+            if cell.block == AIR {
+                u += 1;
+                continue;
+            }
+
+            let mergeable = cell.is_mergeable();
+            let mut width = 1;
+            while mergeable && u + width < N && mask[v * N + u + width] == cell {
+                width += 1;
+            }
+
+            let mut height = 1;
+            while mergeable && v + height < N && mask[(v + height) * N + u..(v + height) * N + u + width].iter().all(|&c| c == cell) {
+                height += 1;
+            }
+
+            for row in v..v + height {
+                mask[row * N + u..row * N + u + width].fill(Cell::EMPTY);
+            }
+
+            emit(u, v, width, height, cell);
+            u += width;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meshing::culled;
+    use crate::world::{Biome, BlockTint, Chunk, ChunkPos, ColumnInfo, World, CHUNK_HEIGHT};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const DIRT: BlockId = 2;
+    const SLAB: BlockId = 15;
+
+    fn quad_counts(world: &World, pos: ChunkPos) -> (usize, usize) {
+        let neighborhood = world.neighborhood(pos).unwrap();
+        let registry = BlockRegistry::builtin();
+        let textures = BlockTextureMap::untextured(&registry);
+        (
+            culled::mesh_chunk(&neighborhood, &registry, &textures).quad_count(),
+            mesh_chunk(&neighborhood, &registry, &textures).quad_count(),
+        )
+    }
+
+    /// Returns the total surface area covered by the quads of a mesh.
+    fn surface_area(mesh: &ChunkMesh) -> f32 {
+        mesh.vertices
+            .chunks(4)
+            .map(|quad| {
+                let a = quad[0].position;
+                let b = quad[1].position;
+                let d = quad[3].position;
+                let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let v = [d[0] - a[0], d[1] - a[1], d[2] - a[2]];
+                let cross = [
+                    u[1] * v[2] - u[2] * v[1],
+                    u[2] * v[0] - u[0] * v[2],
+                    u[0] * v[1] - u[1] * v[0],
+                ];
+                (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt()
+            })
+            .sum()
+    }
+
+    /// A small deterministic xorshift generator for reproducible fills.
+    fn xorshift(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    #[test]
+    fn flat_slab_collapses_to_six_quads() {
+        let mut world = World::new();
+        for z in 0..16 {
+            for x in 0..16 {
+                world.set_block(x, 4, z, STONE);
+            }
+        }
+
+        let (culled, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        assert_eq!(culled, 256 * 2 + 16 * 4);
+        assert_eq!(greedy, 6);
+    }
+
+    #[test]
+    fn merged_quads_repeat_their_texture() {
+        let mut world = World::new();
+        for z in 0..16 {
+            for x in 0..4 {
+                world.set_block(x, 4, z, STONE);
+            }
+        }
+
+        let neighborhood = world.neighborhood(ChunkPos::new(0, 0)).unwrap();
+        let registry = BlockRegistry::builtin();
+        let mesh = mesh_chunk(&neighborhood, &registry, &BlockTextureMap::untextured(&registry));
+
+        // The top face spans 4 blocks along X and 16 along Z.
+        let top = mesh.vertices.chunks(4).find(|quad| quad.iter().all(|v| v.position[1] == 5.0)).unwrap();
+        let max_u = top.iter().map(|v| v.tex_coords[0]).fold(0.0, f32::max);
+        let max_v = top.iter().map(|v| v.tex_coords[1]).fold(0.0, f32::max);
+        assert_eq!((max_u, max_v), (4.0, 16.0));
+    }
+
+    #[test]
+    fn half_blocks_are_cut_to_height_and_not_merged() {
+        let mut world = World::new();
+        for z in 0..4 {
+            for x in 0..4 {
+                world.set_block(x, 4, z, SLAB);
+            }
+        }
+
+        let (culled, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        assert_eq!((culled, greedy), (16 * 2 + 4 * 4, 16 * 2 + 4 * 4));
+
+        let neighborhood = world.neighborhood(ChunkPos::new(0, 0)).unwrap();
+        let registry = BlockRegistry::builtin();
+        let mesh = mesh_chunk(&neighborhood, &registry, &BlockTextureMap::untextured(&registry));
+        assert!(mesh.vertices.iter().all(|vertex| (4.0..=4.5).contains(&vertex.position[1])));
+        assert_eq!(surface_area(&mesh), 16.0 * 2.0 + 16.0 * 0.5);
+    }
+
+    #[test]
+    fn slab_with_two_blocks_splits_top_face() {
+        let mut world = World::new();
+        for z in 0..16 {
+            for x in 0..16 {
+                let block = if x < 8 { STONE } else { DIRT };
+                world.set_block(x, 4, z, block);
+            }
+        }
+
+        // Top and bottom split in two halves; the +X/-X sides stay whole and the
+        // +Z/-Z sides split at the block boundary.
+        let (_, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        assert_eq!(greedy, 2 + 2 + 2 + 4);
+    }
+
+    #[test]
+    fn faces_with_different_tints_are_not_merged() {
+        let registry = BlockRegistry::builtin();
+        let grass = registry.id_of("grass").unwrap();
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        for z in 0..16 {
+            for x in 0..16 {
+                chunk.set(x, 4, z, grass);
+                let biome = if x < 8 { Biome::Desert } else { Biome::Jungle };
+                chunk.set_column(x, z, ColumnInfo::new(biome));
+            }
+        }
+        let mut world = World::new();
+        world.insert_chunk(chunk);
+
+        let neighborhood = world.neighborhood(ChunkPos::new(0, 0)).unwrap();
+        let mesh = mesh_chunk(&neighborhood, &registry, &BlockTextureMap::untextured(&registry));
+
+        // Only the grass-tinted top face splits at the biome border.
+        assert_eq!(mesh.quad_count(), 7);
+        let tops: Vec<_> = mesh.vertices.chunks(4).filter(|quad| quad[0].position[1] == 5.0).collect();
+        assert_eq!(tops.len(), 2);
+        for quad in tops {
+            let biome = if quad[0].position[0] < 8.0 { Biome::Desert } else { Biome::Jungle };
+            let expected = ColumnInfo::new(biome).tint_color(BlockTint::Grass);
+            assert!(quad.iter().all(|v| v.color == expected));
+        }
+    }
+
+    #[test]
+    fn checkerboard_cannot_be_merged() {
+        let mut world = World::new();
+        for y in 0..16 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    if (x + y + z) % 2 == 0 {
+                        world.set_block(x, y, z, STONE);
+                    }
+                }
+            }
+        }
+
+        let (culled, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        assert_eq!(greedy, culled);
+    }
+
+    #[test]
+    fn random_fill_reduces_quads_and_preserves_area() {
+        let mut world = World::new();
+        let mut state = 0x9E37_79B9;
+        for y in 0..32 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let roll = xorshift(&mut state) % 4;
+                    let block = match roll {
+                        0 => AIR,
+                        1 => DIRT,
+                        _ => STONE,
+                    };
+                    world.set_block(x, y, z, block);
+                }
+            }
+        }
+
+        let neighborhood = world.neighborhood(ChunkPos::new(0, 0)).unwrap();
+        let registry = BlockRegistry::builtin();
+        let textures = BlockTextureMap::untextured(&registry);
+        let culled = culled::mesh_chunk(&neighborhood, &registry, &textures);
+        let greedy = mesh_chunk(&neighborhood, &registry, &textures);
+
+        assert!(greedy.quad_count() < culled.quad_count());
+        assert_eq!(surface_area(&greedy), surface_area(&culled));
+        assert_eq!(surface_area(&culled), culled.quad_count() as f32);
+    }
+
+    #[test]
+    fn quads_do_not_cross_sections() {
+        let mut world = World::new();
+        for y in 0..CHUNK_HEIGHT as i32 {
+            world.set_block(3, y, 3, STONE);
+        }
+
+        let (_, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        let sections = CHUNK_HEIGHT / SECTION_SIZE;
+        assert_eq!(greedy, 4 * sections + 2);
+    }
+
+    #[test]
+    fn greedy_culls_across_chunk_borders() {
+        let mut world = World::new();
+        for z in 0..16 {
+            world.set_block(-1, 0, z, STONE);
+            world.set_block(0, 0, z, STONE);
+        }
+
+        // Top, bottom and the two Z ends of the row; the -X face is hidden by the neighbour.
+        let (_, greedy) = quad_counts(&world, ChunkPos::new(0, 0));
+        assert_eq!(greedy, 5);
+    }
+}
@@ -0,0 +1,385 @@
+//! # Meshing Module
+//! 
+//! This module turns chunks of blocks into the vertex and index data consumed by
+//! the renderer. Meshing runs entirely on the CPU so it can be tested without a GPU.
+
+mod culled;
+mod greedy;
+
+use log::warn;
+
+use crate::occlusion::ChunkVisibility;
+use crate::renderer::{AtlasRegion, TextureAtlas, TexturedVertex};
+use crate::world::{BlockId, BlockRegistry, BlockTint, ChunkNeighborhood, MAX_LIGHT, SECTION_COUNT, SECTION_SIZE};
+
+/// Selects the algorithm used to mesh chunks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MeshMode {
+    /// One quad per visible block face.
+    Culled,
+    /// Coplanar faces of the same block are merged into larger quads.
+    #[default]
+    Greedy,
+}
+
+impl MeshMode {
+    /// Returns a human-readable name for the mode.
+    pub fn name(self) -> &'static str {
+        match self {
+            MeshMode::Culled => "Culled",
+            MeshMode::Greedy => "Greedy",
+        }
+    }
+}
+
+/// Builds the mesh of the center chunk of a neighborhood using the given mode.
+///
+/// The mesh also carries the visibility graph of the chunk's sections, used for
+/// cave culling.
+pub fn mesh_chunk(neighborhood: &ChunkNeighborhood, registry: &BlockRegistry, textures: &BlockTextureMap, mode: MeshMode) -> ChunkMesh {
+    let mut mesh = match mode {
+        MeshMode::Culled => culled::mesh_chunk(neighborhood, registry, textures),
+        MeshMode::Greedy => greedy::mesh_chunk(neighborhood, registry, textures),
+    };
+    mesh.visibility = ChunkVisibility::build(neighborhood.center(), registry);
+    mesh
+}
+
+/// Returns `true` if `face` of `block`, which touches `neighbor`, should be drawn.
+///
+/// Faces are hidden by opaque neighbours and between two blocks of the same
+/// transparent type (e.g. inside a body of water). The top of a block shorter than a
+/// full block is below the block above it, so it is always drawn, and so is the
+/// bottom of the same block stacked on it.
+fn is_face_visible(registry: &BlockRegistry, block: BlockId, neighbor: BlockId, face: Face) -> bool {
+    match face {
+        Face::PosY if registry.get(block).height < 1.0 => true,
+        Face::NegY if registry.get(neighbor).height < 1.0 => !registry.is_opaque(neighbor),
+        _ => !registry.is_opaque(neighbor) && neighbor != block,
+    }
+}
+
+/// The light and ambient occlusion at the corners of a face, in the order of
+/// `Face::corners`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct FaceShading {
+    /// The sky and block light at each corner, from 0 to 1.
+    light: [[f32; 2]; 4],
+    /// How unoccluded each corner is, from 0 (tucked into an inner corner) to 1.
+    ao: [f32; 4],
+}
+
+impl FaceShading {
+    /// Returns `true` if every corner is shaded the same.
+    fn is_uniform(&self) -> bool {
+        self.light.iter().all(|&light| light == self.light[0]) && self.ao.iter().all(|&ao| ao == self.ao[0])
+    }
+
+    /// Returns `true` if the quad should be split along the diagonal from its second
+    /// to its fourth corner instead of from its first to its third. The split keeps
+    /// the darker pair of corners apart, so occlusion in one corner does not bleed
+    /// across the whole quad and looks the same whichever way the face is turned.
+    fn flips_diagonal(&self) -> bool {
+        self.ao[0] + self.ao[2] < self.ao[1] + self.ao[3]
+    }
+}
+
+/// Returns the shading of the face of the block at `pos`. `pos` is relative to the
+/// center chunk's origin.
+///
+/// Each corner averages the light of the blocks in front of the face that touch it,
+/// leaving out opaque ones and those below the world, so light fades smoothly
+/// from block to block. Its ambient occlusion counts the opaque blocks among the
+/// two edge neighbours and the corner neighbour; two edges fully occlude it. The
+/// top of a block shorter than a full block lies inside the block itself, so it is
+/// shaded by the blocks around it rather than above it.
+fn face_shading(neighborhood: &ChunkNeighborhood, registry: &BlockRegistry, face: Face, pos: [i32; 3]) -> FaceShading {
+    let (n, u, v) = face.axes();
+    let mut front = pos;
+    if face != Face::PosY || registry.get(neighborhood.get(pos[0], pos[1], pos[2])).height >= 1.0 {
+        front[n] += face.normal()[n];
+    }
+    let is_opaque = |p: [i32; 3]| registry.is_opaque(neighborhood.get(p[0], p[1], p[2]));
+
+    let mut light = [[0.0; 2]; 4];
+    let mut ao = [0.0; 4];
+    for (i, corner) in face.corners().into_iter().enumerate() {
+        let (du, dv) = (corner[u] as i32 * 2 - 1, corner[v] as i32 * 2 - 1);
+        let at = |su: i32, sv: i32| {
+            let mut p = front;
+            p[u] += su;
+            p[v] += sv;
+            p
+        };
+
+        let (side_u, side_v, diagonal) = (is_opaque(at(du, 0)), is_opaque(at(0, dv)), is_opaque(at(du, dv)));
+        let occluded = if side_u && side_v { 3 } else { [side_u, side_v, diagonal].into_iter().filter(|&o| o).count() };
+        ao[i] = (3 - occluded) as f32 / 3.0;
+
+        let (mut sky, mut block, mut count) = (0, 0, 0);
+        for (p, opaque) in [(front, false), (at(du, 0), side_u), (at(0, dv), side_v), (at(du, dv), diagonal)] {
+            if p != front && (opaque || p[1] < 0) {
+                continue;
+            }
+            let level = neighborhood.light(p[0], p[1], p[2]);
+            sky += level.sky() as u32;
+            block += level.block() as u32;
+            count += 1;
+        }
+        let max = (count * MAX_LIGHT as u32) as f32;
+        light[i] = [sky as f32 / max, block as f32 / max];
+    }
+    FaceShading { light, ao }
+}
+
+/// One of the six faces of a block.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Face {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl Face {
+    /// All six faces, in a fixed order.
+    pub const ALL: [Face; 6] = [Face::PosX, Face::NegX, Face::PosY, Face::NegY, Face::PosZ, Face::NegZ];
+
+    /// Returns the outward unit normal of the face.
+    pub fn normal(self) -> [i32; 3] {
+        match self {
+            Face::PosX => [1, 0, 0],
+            Face::NegX => [-1, 0, 0],
+            Face::PosY => [0, 1, 0],
+            Face::NegY => [0, -1, 0],
+            Face::PosZ => [0, 0, 1],
+            Face::NegZ => [0, 0, -1],
+        }
+    }
+
+    /// Returns the position of the face in `Face::ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the face on the other side of a block.
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::PosX => Face::NegX,
+            Face::NegX => Face::PosX,
+            Face::PosY => Face::NegY,
+            Face::NegY => Face::PosY,
+            Face::PosZ => Face::NegZ,
+            Face::NegZ => Face::PosZ,
+        }
+    }
+
+    /// Returns the axis along the face normal followed by the two axes spanning the face.
+    fn axes(self) -> (usize, usize, usize) {
+        match self {
+            Face::PosX | Face::NegX => (0, 2, 1),
+            Face::PosY | Face::NegY => (1, 0, 2),
+            Face::PosZ | Face::NegZ => (2, 0, 1),
+        }
+    }
+
+    /// Returns the corners of the face on a unit cube, counter-clockwise when seen from outside.
+    fn corners(self) -> [[f32; 3]; 4] {
+        match self {
+            Face::PosX => [[1.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0]],
+            Face::NegX => [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0]],
+            Face::PosY => [[0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+            Face::NegY => [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+            Face::PosZ => [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]],
+            Face::NegZ => [[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]],
+        }
+    }
+
+    /// Returns the brightness multiplier applied to the face to fake directional lighting.
+    fn shade(self) -> f32 {
+        match self {
+            Face::PosY => 1.0,
+            Face::NegY => 0.5,
+            Face::PosX | Face::NegX => 0.8,
+            Face::PosZ | Face::NegZ => 0.65,
+        }
+    }
+}
+
+/// The texture and tint of a single block face.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FaceTexture {
+    pub region: AtlasRegion,
+    /// The biome colour multiplied into the face.
+    pub tint: BlockTint,
+    /// Whether the face is drawn in the alpha-blended pass.
+    pub translucent: bool,
+}
+
+/// The atlas region and tint of every face of every block, resolved once from the
+/// block registry and the texture atlas.
+pub struct BlockTextureMap {
+    faces: Vec<[FaceTexture; 6]>,
+}
+
+impl BlockTextureMap {
+    /// Resolves the face textures of every registered block.
+    ///
+    /// Textures missing from the atlas fall back to the whole atlas so the face stays visible.
+    pub fn new(registry: &BlockRegistry, atlas: &TextureAtlas) -> Self {
+        Self::resolve(registry, |name| {
+            atlas.region(name).unwrap_or_else(|| {
+                warn!("Texture '{}' is not in the atlas", name);
+                AtlasRegion::FULL
+            })
+        })
+    }
+
+    fn resolve(registry: &BlockRegistry, mut region: impl FnMut(&str) -> AtlasRegion) -> Self {
+        let mut faces = Vec::new();
+        for def in registry.iter() {
+            let id = def.id as usize;
+            if faces.len() <= id {
+                faces.resize(id + 1, [FaceTexture { region: AtlasRegion::FULL, tint: BlockTint::None, translucent: false }; 6]);
+            }
+
+            for face in Face::ALL {
+                let name = match face {
+                    Face::PosY => def.textures.top(),
+                    Face::NegY => def.textures.bottom(),
+                    _ => def.textures.side(),
+                };
+                let tint = match (def.tint, face) {
+                    (BlockTint::Grass, Face::PosY) => BlockTint::Grass,
+                    (BlockTint::Foliage, _) => BlockTint::Foliage,
+                    _ => BlockTint::None,
+                };
+                faces[id][face.index()] = FaceTexture {
+                    region: region(name),
+                    tint,
+                    translucent: def.translucent,
+                };
+            }
+        }
+        Self { faces }
+    }
+
+    /// Returns a map where every face samples the whole atlas, for tests that do not
+    /// care about textures.
+    #[cfg(test)]
+    pub fn untextured(registry: &BlockRegistry) -> Self {
+        Self::resolve(registry, |_| AtlasRegion::FULL)
+    }
+
+    /// Returns the texture of a block face.
+    pub fn face(&self, block: BlockId, face: Face) -> FaceTexture {
+        self.faces[block as usize][face.index()]
+    }
+}
+
+/// The CPU-side geometry of a chunk.
+///
+/// Opaque and translucent faces share the vertex list but are indexed separately,
+/// so translucent faces can be drawn in a second pass. Both index lists are ordered
+/// by section, so sections can also be drawn on their own.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<TexturedVertex>,
+    pub indices: Vec<u32>,
+    pub translucent_indices: Vec<u32>,
+    /// The end of each section's range in `indices`. Section `i` starts where
+    /// section `i - 1` ends.
+    pub section_ends: [u32; SECTION_COUNT],
+    /// The end of each section's range in `translucent_indices`.
+    pub translucent_section_ends: [u32; SECTION_COUNT],
+    pub visibility: ChunkVisibility,
+}
+
+impl ChunkMesh {
+    /// Returns `true` if the mesh has no geometry.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty() && self.translucent_indices.is_empty()
+    }
+
+    /// Returns the size of the vertex and index data, in bytes.
+    pub fn byte_size(&self) -> usize {
+        let indices = self.indices.len() + self.translucent_indices.len();
+        self.vertices.len() * std::mem::size_of::<TexturedVertex>() + indices * std::mem::size_of::<u32>()
+    }
+
+    /// Returns the number of quads in the mesh.
+    #[cfg(test)]
+    pub fn quad_count(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    /// Appends a quad covering `width` x `height` block faces, starting at `origin`
+    /// (the minimum corner of the first block) and extending along the face's axes.
+    /// `tint` is the biome colour resolved for the face's tint, and `shading` the light
+    /// and ambient occlusion at each corner, as returned by `face_shading`.
+    ///
+    /// `block_height` is the height of the block, from the registry. The faces of
+    /// blocks shorter than a full block are cut to size, so their quads must not
+    /// span more than one block vertically.
+    ///
+    /// Quads must be appended section by section, from the bottom up.
+    #[allow(clippy::too_many_arguments)]
+    fn push_quad(
+        &mut self,
+        face: Face,
+        origin: [f32; 3],
+        width: u32,
+        height: u32,
+        texture: FaceTexture,
+        tint: [f32; 3],
+        shading: FaceShading,
+        block_height: f32,
+    ) {
+        debug_assert!(block_height >= 1.0 || face.axes().2 != 1 || height == 1);
+        let base = self.vertices.len() as u32;
+        let color = tint.map(|c| c * face.shade());
+        let (_, u, v) = face.axes();
+        let (w, h) = (width as f32, height as f32);
+        // Corners run bottom-left, bottom-right, top-right, top-left across the face.
+        // Side faces of short blocks show the bottom of their texture.
+        let top = if v == 1 { 1.0 - block_height } else { 0.0 };
+        let tex_coords = [[0.0, h], [w, h], [w, top], [0.0, top]];
+
+        for (i, (corner, tex_coords)) in face.corners().into_iter().zip(tex_coords).enumerate() {
+            let mut position = origin;
+            for axis in 0..3 {
+                let scale = if axis == u {
+                    width as f32
+                } else if axis == v {
+                    height as f32
+                } else {
+                    1.0
+                };
+                let scale = if axis == 1 { scale * block_height } else { scale };
+                position[axis] += corner[axis] * scale;
+            }
+            self.vertices.push(TexturedVertex {
+                position,
+                tex_coords,
+                atlas_rect: texture.region.to_array(),
+                color,
+                light: shading.light[i],
+                ao: shading.ao[i],
+            });
+        }
+        let (indices, ends) = if texture.translucent {
+            (&mut self.translucent_indices, &mut self.translucent_section_ends)
+        } else {
+            (&mut self.indices, &mut self.section_ends)
+        };
+        if shading.flips_diagonal() {
+            indices.extend_from_slice(&[base + 1, base + 2, base + 3, base + 3, base, base + 1]);
+        } else {
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+        let section = origin[1] as usize / SECTION_SIZE;
+        ends[section..].fill(indices.len() as u32);
+    }
+}
@@ -12,6 +12,7 @@ pub struct FrameProfiler {
     max_samples: usize,
     frames_recorded: usize,
     current_fps: usize,
+    triangle_count: u32,
 }
 
 impl FrameProfiler {
@@ -24,6 +25,7 @@ impl FrameProfiler {
             max_samples,
             frames_recorded: 0,
             current_fps: 0,
+            triangle_count: 0,
         }
     }
 
@@ -38,12 +40,19 @@ impl FrameProfiler {
         self.current_fps = (1.0 / frame_time.as_secs_f64()).round() as usize;
     }
 
+    /// Records the number of triangles drawn in the latest frame.
+    pub fn record_triangles(&mut self, count: u32) {
+        self.triangle_count = count;
+    }
+
     /// Returns `true` if a new set of statistics should be logged.
+    #[allow(dead_code)]
     pub fn should_log(&self) -> bool {
-        self.frames_recorded % 120 == 0
+        self.frames_recorded.is_multiple_of(120)
     }
 
     /// Logs the frame time statistics to the console.
+    #[allow(dead_code)]
     pub fn log(&self) {
         if self.frame_times.is_empty() {
             return;
@@ -67,14 +76,15 @@ impl FrameProfiler {
         let drop_pct = (drops as f64 / self.frame_times.len() as f64) * 100.0;
 
         info!(
-            "🎮 Render | FPS: {} | Frame Time: avg={:.2}ms min={:.2}ms max={:.2}ms p95={:.2}ms p99={:.2}ms | Drops: {:.1}%",
+            "🎮 Render | FPS: {} | Frame Time: avg={:.2}ms min={:.2}ms max={:.2}ms p95={:.2}ms p99={:.2}ms | Drops: {:.1}% | Triangles: {}",
             self.current_fps,
             avg.as_secs_f64() * 1000.0,
             min.as_secs_f64() * 1000.0,
             max.as_secs_f64() * 1000.0,
             p95.as_secs_f64() * 1000.0,
             p99.as_secs_f64() * 1000.0,
-            drop_pct
+            drop_pct,
+            self.triangle_count
         );
     }
 
@@ -88,4 +98,14 @@ impl FrameProfiler {
         let avg = total / self.frame_times.len() as u32;
         avg.as_secs_f64() * 1000.0
     }
+
+    /// Returns the number of triangles drawn in the latest frame.
+    pub fn get_triangle_count(&self) -> u32 {
+        self.triangle_count
+    }
+
+    /// Discards the recorded frame times, e.g. after a change that affects performance.
+    pub fn reset(&mut self) {
+        self.frame_times.clear();
+    }
 }
\ No newline at end of file
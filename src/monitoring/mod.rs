@@ -4,14 +4,17 @@
 
 pub mod frame_profiler;
 pub mod system_stats;
+pub mod tick_profiler;
 
 use frame_profiler::FrameProfiler;
 use system_stats::SystemStats;
-use std::time::Duration;
+use tick_profiler::TickProfiler;
+use std::time::{Duration, Instant};
 
-/// Aggregates the frame profiler and system statistics into a single struct.
+/// Aggregates the frame and tick profilers and system statistics into a single struct.
 pub struct SystemMonitor {
     frame_profiler: FrameProfiler,
+    tick_profiler: TickProfiler,
     system_stats: SystemStats,
 }
 
@@ -20,6 +23,7 @@ impl SystemMonitor {
     pub fn new() -> Self {
         Self {
             frame_profiler: FrameProfiler::new(120),
+            tick_profiler: TickProfiler::new(100),
             system_stats: SystemStats::new(),
         }
     }
@@ -34,11 +38,41 @@ impl SystemMonitor {
         self.frame_profiler.record(frame_time);
     }
 
+    /// Records the time it took to simulate a game tick that just ran.
+    pub fn record_tick(&mut self, tick_time: Duration) {
+        self.tick_profiler.record(tick_time, Instant::now());
+    }
+
+    /// Records the number of triangles drawn in the latest frame.
+    pub fn record_triangles(&mut self, count: u32) {
+        self.frame_profiler.record_triangles(count);
+    }
+
+    /// Discards the recorded frame times so averages reflect only what follows.
+    pub fn reset_frame_times(&mut self) {
+        self.frame_profiler.reset();
+    }
+
     /// Returns the average frame time in milliseconds.
     pub fn get_avg_frame_time_ms(&self) -> f64 {
         self.frame_profiler.get_avg_frame_time_ms()
     }
 
+    /// Returns the average time taken to simulate a game tick, in milliseconds.
+    pub fn get_avg_tick_time_ms(&self) -> f64 {
+        self.tick_profiler.get_avg_tick_time_ms()
+    }
+
+    /// Returns the number of game ticks that ran in the last second.
+    pub fn get_tps(&self) -> usize {
+        self.tick_profiler.get_tps()
+    }
+
+    /// Returns the number of triangles drawn in the latest frame.
+    pub fn get_triangle_count(&self) -> u32 {
+        self.frame_profiler.get_triangle_count()
+    }
+
     /// Returns the CPU brand.
     pub fn get_cpu_brand(&self) -> &str {
         self.system_stats.get_cpu_brand()
@@ -0,0 +1,130 @@
+//! # Tick Profiler
+//! 
+//! This module provides a `TickProfiler` struct for measuring how long game ticks
+//! take to simulate and how many ticks actually run per second, separately from
+//! the frame times.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use log::info;
+
+/// Measures and calculates statistics about game ticks.
+pub struct TickProfiler {
+    tick_times: VecDeque<Duration>,
+    max_samples: usize,
+    /// When each tick of the last second ran.
+    recent_ticks: VecDeque<Instant>,
+    ticks_recorded: usize,
+}
+
+impl TickProfiler {
+    /// Creates a new `TickProfiler`.
+    ///
+    /// - `max_samples`: The maximum number of tick times to store for a moving average.
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            tick_times: VecDeque::with_capacity(max_samples),
+            max_samples,
+            recent_ticks: VecDeque::new(),
+            ticks_recorded: 0,
+        }
+    }
+
+    /// Records the time it took to simulate a tick that ran at `at`.
+    pub fn record(&mut self, tick_time: Duration, at: Instant) {
+        self.tick_times.push_back(tick_time);
+        if self.tick_times.len() > self.max_samples {
+            self.tick_times.pop_front();
+        }
+
+        self.recent_ticks.push_back(at);
+        while self.recent_ticks.front().is_some_and(|&tick| at.duration_since(tick) >= Duration::from_secs(1)) {
+            self.recent_ticks.pop_front();
+        }
+        self.ticks_recorded += 1;
+    }
+
+    /// Returns `true` if a new set of statistics should be logged.
+    #[allow(dead_code)]
+    pub fn should_log(&self) -> bool {
+        self.ticks_recorded.is_multiple_of(self.max_samples)
+    }
+
+    /// Logs the tick statistics to the console.
+    #[allow(dead_code)]
+    pub fn log(&self) {
+        let Some(max) = self.tick_times.iter().max() else {
+            return;
+        };
+        info!(
+            "⏱️ Simulation | TPS: {} | Tick Time: avg={:.2}ms max={:.2}ms",
+            self.get_tps(),
+            self.get_avg_tick_time_ms(),
+            max.as_secs_f64() * 1000.0
+        );
+    }
+
+    /// Returns the average time taken to simulate a tick, in milliseconds.
+    pub fn get_avg_tick_time_ms(&self) -> f64 {
+        if self.tick_times.is_empty() {
+            return 0.0;
+        }
+
+        let total: Duration = self.tick_times.iter().sum();
+        let avg = total / self.tick_times.len() as u32;
+        avg.as_secs_f64() * 1000.0
+    }
+
+    /// Returns the number of ticks that ran in the last second.
+    pub fn get_tps(&self) -> usize {
+        self.recent_ticks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn tps_counts_the_ticks_of_the_last_second() {
+        let (mut profiler, start) = (TickProfiler::new(100), Instant::now());
+        for i in 0..20 {
+            profiler.record(ms(1), start + ms(50 * i));
+        }
+        assert_eq!(profiler.get_tps(), 20);
+
+        // A tick exactly a second after the first one pushes it out of the window.
+        profiler.record(ms(1), start + ms(1000));
+        assert_eq!(profiler.get_tps(), 20);
+
+        // After a stall, only the latest tick is in the window.
+        profiler.record(ms(1), start + ms(3000));
+        assert_eq!(profiler.get_tps(), 1);
+    }
+
+    #[test]
+    fn tick_times_are_averaged_over_the_latest_samples() {
+        let (mut profiler, start) = (TickProfiler::new(4), Instant::now());
+        assert_eq!(profiler.get_avg_tick_time_ms(), 0.0);
+        for i in 1..=6 {
+            profiler.record(ms(i), start + ms(50 * i));
+        }
+        assert!((profiler.get_avg_tick_time_ms() - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn statistics_are_logged_once_per_window_of_samples() {
+        let (mut profiler, start) = (TickProfiler::new(3), Instant::now());
+        let logged: Vec<bool> = (0..7)
+            .map(|i| {
+                profiler.record(ms(1), start + ms(50 * i));
+                profiler.should_log()
+            })
+            .collect();
+        assert_eq!(logged, [false, false, true, false, false, true, false]);
+    }
+}
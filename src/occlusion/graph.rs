@@ -0,0 +1,229 @@
+//! # Visibility Graph
+//! 
+//! This module computes which faces of a chunk section are connected through
+//! non-opaque blocks. A line of sight can only pass through a section between two
+//! connected faces, which lets whole caves be skipped when they cannot be seen.
+
+use crate::meshing::Face;
+use crate::world::{BlockId, BlockRegistry, Chunk, ChunkSection, SECTION_COUNT, SECTION_SIZE};
+
+/// The number of blocks in a section.
+const SECTION_VOLUME: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+/// The pairs of faces of a section that can see each other through non-opaque blocks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SectionVisibility {
+    /// Bit `a * 6 + b` is set if face `a` connects to face `b`.
+    bits: u64,
+}
+
+impl SectionVisibility {
+    /// A section through which no face can see another.
+    pub const NONE: SectionVisibility = SectionVisibility { bits: 0 };
+
+    /// A section through which every face can see every other face.
+    pub const ALL: SectionVisibility = SectionVisibility { bits: (1 << 36) - 1 };
+
+    /// Computes the connected faces of a section by flood filling its non-opaque blocks.
+    pub fn build(section: &ChunkSection, registry: &BlockRegistry) -> Self {
+        if section.is_empty() {
+            return Self::ALL;
+        }
+
+        let mut visibility = Self::NONE;
+        let mut visited = vec![false; SECTION_VOLUME];
+        let mut stack = Vec::new();
+
+        for start in 0..SECTION_VOLUME {
+            if visited[start] || registry.is_opaque(block_at(section, start)) {
+                continue;
+            }
+
+            // Collect the faces touched by the region of non-opaque blocks around `start`.
+            let mut touched = 0u8;
+            visited[start] = true;
+            stack.push(start);
+            while let Some(index) = stack.pop() {
+                let [x, y, z] = coords(index);
+                for face in Face::ALL {
+                    let [nx, ny, nz] = face.normal();
+                    let (nx, ny, nz) = (x as i32 + nx, y as i32 + ny, z as i32 + nz);
+                    let size = SECTION_SIZE as i32;
+                    if !(0..size).contains(&nx) || !(0..size).contains(&ny) || !(0..size).contains(&nz) {
+                        touched |= 1 << face.index();
+                        continue;
+                    }
+
+                    let neighbor = ((ny * size + nz) * size + nx) as usize;
+                    if !visited[neighbor] && !registry.is_opaque(block_at(section, neighbor)) {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+
+            for a in Face::ALL {
+                for b in Face::ALL {
+                    if touched & (1 << a.index()) != 0 && touched & (1 << b.index()) != 0 {
+                        visibility.connect(a, b);
+                    }
+                }
+            }
+            if visibility == Self::ALL {
+                break;
+            }
+        }
+
+        visibility
+    }
+
+    /// Returns `true` if face `a` can see face `b` through the section.
+    pub fn connects(self, a: Face, b: Face) -> bool {
+        self.bits & Self::bit(a, b) != 0
+    }
+
+    fn connect(&mut self, a: Face, b: Face) {
+        self.bits |= Self::bit(a, b) | Self::bit(b, a);
+    }
+
+    fn bit(a: Face, b: Face) -> u64 {
+        1 << (a.index() * 6 + b.index())
+    }
+}
+
+/// The visibility graphs of every section of a chunk.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    sections: [SectionVisibility; SECTION_COUNT],
+}
+
+impl Default for ChunkVisibility {
+    fn default() -> Self {
+        Self {
+            sections: [SectionVisibility::ALL; SECTION_COUNT],
+        }
+    }
+}
+
+impl ChunkVisibility {
+    /// Computes the visibility graph of every section of a chunk.
+    pub fn build(chunk: &Chunk, registry: &BlockRegistry) -> Self {
+        let mut visibility = Self::default();
+        for (graph, section) in visibility.sections.iter_mut().zip(chunk.sections()) {
+            *graph = SectionVisibility::build(section, registry);
+        }
+        visibility
+    }
+
+    /// Returns the visibility graph of the section at index `y`, counted from the bottom.
+    pub fn section(&self, y: usize) -> SectionVisibility {
+        self.sections[y]
+    }
+}
+
+/// Returns the block at a section-local index.
+fn block_at(section: &ChunkSection, index: usize) -> BlockId {
+    let [x, y, z] = coords(index);
+    section.get(x, y, z)
+}
+
+/// Returns the section-local coordinates of an index.
+fn coords(index: usize) -> [usize; 3] {
+    [index % SECTION_SIZE, index / (SECTION_SIZE * SECTION_SIZE), index / SECTION_SIZE % SECTION_SIZE]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{ChunkPos, AIR, CHUNK_SIZE};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const LEAVES: BlockId = 7;
+
+    /// Returns the graph of the bottom section of a chunk filled with `fill`, after
+    /// `carve` is applied to it.
+    fn graph(fill: BlockId, carve: impl Fn(&mut Chunk)) -> SectionVisibility {
+        let registry = BlockRegistry::builtin();
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        for y in 0..SECTION_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, fill);
+                }
+            }
+        }
+        carve(&mut chunk);
+        ChunkVisibility::build(&chunk, &registry).section(0)
+    }
+
+    /// Returns every pair of faces connected in a graph.
+    fn pairs(graph: SectionVisibility) -> Vec<(Face, Face)> {
+        Face::ALL
+            .into_iter()
+            .flat_map(|a| Face::ALL.into_iter().map(move |b| (a, b)))
+            .filter(|&(a, b)| graph.connects(a, b))
+            .collect()
+    }
+
+    #[test]
+    fn empty_and_transparent_sections_connect_everything() {
+        let registry = BlockRegistry::builtin();
+        assert_eq!(SectionVisibility::build(&ChunkSection::default(), &registry), SectionVisibility::ALL);
+        assert_eq!(graph(LEAVES, |_| ()), SectionVisibility::ALL);
+    }
+
+    #[test]
+    fn solid_sections_connect_nothing() {
+        assert_eq!(graph(STONE, |_| ()), SectionVisibility::NONE);
+        // An enclosed cave touches no face.
+        let cave = graph(STONE, |chunk| {
+            for y in 4..8 {
+                chunk.set(5, y, 5, AIR);
+            }
+        });
+        assert_eq!(cave, SectionVisibility::NONE);
+    }
+
+    #[test]
+    fn tunnels_connect_the_faces_they_reach() {
+        // A straight tunnel along X.
+        let tunnel = graph(STONE, |chunk| {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, 8, 8, AIR);
+            }
+        });
+        let mut expected = vec![(Face::PosX, Face::PosX), (Face::PosX, Face::NegX), (Face::NegX, Face::PosX), (Face::NegX, Face::NegX)];
+        expected.sort_by_key(|&(a, b)| (a.index(), b.index()));
+        assert_eq!(pairs(tunnel), expected);
+
+        // A shaft from the top that bends towards -Z.
+        let bend = graph(STONE, |chunk| {
+            for y in 4..SECTION_SIZE {
+                chunk.set(3, y, 7, AIR);
+            }
+            for z in 0..7 {
+                chunk.set(3, 4, z, AIR);
+            }
+        });
+        assert!(bend.connects(Face::PosY, Face::NegZ));
+        assert!(bend.connects(Face::NegZ, Face::PosY));
+        assert!(!bend.connects(Face::PosY, Face::NegY));
+        assert!(!bend.connects(Face::PosX, Face::NegX));
+    }
+
+    #[test]
+    fn separate_caves_do_not_connect_each_other() {
+        // Two tunnels, along X at the bottom and along Z at the top, that never meet.
+        let caves = graph(STONE, |chunk| {
+            for i in 0..CHUNK_SIZE {
+                chunk.set(i, 2, 2, AIR);
+                chunk.set(12, 13, i, AIR);
+            }
+        });
+        assert!(caves.connects(Face::PosX, Face::NegX));
+        assert!(caves.connects(Face::PosZ, Face::NegZ));
+        assert!(!caves.connects(Face::PosX, Face::PosZ));
+        assert!(!caves.connects(Face::NegX, Face::NegZ));
+    }
+}
@@ -0,0 +1,267 @@
+//! # Occlusion Module
+//! 
+//! This module implements cave culling. Each chunk section records which of its
+//! faces can see each other through non-opaque blocks, and a breadth-first search
+//! from the camera's section walks only through those connections, never turning
+//! back towards the camera. Sections the search does not reach are hidden behind
+//! terrain and need not be drawn.
+
+mod graph;
+
+pub use graph::ChunkVisibility;
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::meshing::Face;
+use crate::world::{ChunkPos, CHUNK_SIZE, SECTION_COUNT, SECTION_SIZE};
+
+// The sections of a chunk are stored as the bits of a `u8`.
+const _: () = assert!(SECTION_COUNT <= 8);
+
+/// The position of a chunk section, in sections.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SectionPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl SectionPos {
+    /// Creates a new `SectionPos`.
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns the section containing the given world position.
+    pub fn containing(x: f32, y: f32, z: f32) -> Self {
+        let section = |v: f32, size: usize| (v.floor() as i32).div_euclid(size as i32);
+        Self::new(section(x, CHUNK_SIZE), section(y, SECTION_SIZE), section(z, CHUNK_SIZE))
+    }
+
+    /// Returns the chunk the section belongs to.
+    pub fn chunk(&self) -> ChunkPos {
+        ChunkPos::new(self.x, self.z)
+    }
+
+    /// Returns the world coordinates of the section's minimum corner.
+    pub fn origin(&self) -> [i32; 3] {
+        [self.x * CHUNK_SIZE as i32, self.y * SECTION_SIZE as i32, self.z * CHUNK_SIZE as i32]
+    }
+
+    /// Returns `true` if the section lies within the height of the world.
+    fn in_world(&self) -> bool {
+        (0..SECTION_COUNT as i32).contains(&self.y)
+    }
+
+    /// Returns the section next to this one across a face.
+    fn neighbor(&self, face: Face) -> Self {
+        let [dx, dy, dz] = face.normal();
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+}
+
+/// The sections reached by a cave culling search.
+#[derive(Clone, Debug, Default)]
+pub struct VisibleSections {
+    /// A mask of the reached sections of each chunk, with bit `y` for section `y`.
+    chunks: HashMap<ChunkPos, u8>,
+}
+
+impl VisibleSections {
+    /// Returns `true` if a section was reached.
+    pub fn contains(&self, pos: SectionPos) -> bool {
+        pos.in_world() && self.chunks.get(&pos.chunk()).is_some_and(|mask| mask & (1 << pos.y) != 0)
+    }
+
+    /// Returns the number of reached sections.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(|mask| mask.count_ones() as usize).sum()
+    }
+
+    /// Marks a section as reached.
+    fn insert(&mut self, pos: SectionPos) {
+        *self.chunks.entry(pos.chunk()).or_default() |= 1 << pos.y;
+    }
+}
+
+/// A section waiting to be searched from.
+struct Step {
+    pos: SectionPos,
+    /// The face through which the search entered the section, or `None` for the
+    /// camera's own section.
+    entry: Option<Face>,
+    /// The directions travelled to reach the section, as a mask of face indices.
+    directions: u8,
+}
+
+/// Searches the sections visible from the camera at `eye`.
+///
+/// `graphs` holds the visibility graphs of the loaded chunks; the search never
+/// leaves them. `in_view` decides whether a section may be entered at all, e.g.
+/// because it is inside the view frustum. Returns `None` if the camera's chunk is
+/// not loaded, in which case nothing can be culled.
+pub fn visible_sections(
+    eye: [f32; 3],
+    graphs: &HashMap<ChunkPos, ChunkVisibility>,
+    in_view: impl Fn(SectionPos) -> bool,
+) -> Option<VisibleSections> {
+    let start = SectionPos::containing(eye[0], eye[1], eye[2]);
+    if !graphs.contains_key(&start.chunk()) {
+        return None;
+    }
+
+    let mut visible = VisibleSections::default();
+    let mut queue = VecDeque::new();
+    if start.in_world() {
+        visible.insert(start);
+        queue.push_back(Step { pos: start, entry: None, directions: 0 });
+    } else {
+        // From above or below the world, the search enters through the top or bottom
+        // layer of sections.
+        let (y, entry, direction) = if start.y < 0 {
+            (0, Face::NegY, Face::PosY)
+        } else {
+            (SECTION_COUNT as i32 - 1, Face::PosY, Face::NegY)
+        };
+        for pos in graphs.keys() {
+            let pos = SectionPos::new(pos.x, y, pos.z);
+            if in_view(pos) {
+                visible.insert(pos);
+                queue.push_back(Step { pos, entry: Some(entry), directions: 1 << direction.index() });
+            }
+        }
+    }
+
+    while let Some(step) = queue.pop_front() {
+        let graph = graphs[&step.pos.chunk()].section(step.pos.y as usize);
+        for face in Face::ALL {
+            // Never turn back towards the camera.
+            if step.directions & (1 << face.opposite().index()) != 0 {
+                continue;
+            }
+            if step.entry.is_some_and(|entry| !graph.connects(entry, face)) {
+                continue;
+            }
+
+            let next = step.pos.neighbor(face);
+            if !next.in_world() || !graphs.contains_key(&next.chunk()) || visible.contains(next) || !in_view(next) {
+                continue;
+            }
+            visible.insert(next);
+            queue.push_back(Step {
+                pos: next,
+                entry: Some(face.opposite()),
+                directions: step.directions | 1 << face.index(),
+            });
+        }
+    }
+
+    Some(visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{BlockId, BlockRegistry, Chunk, AIR, CHUNK_HEIGHT};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+
+    /// Returns chunks in a `radius` square filled with stone up to `height`, after
+    /// `carve(x, y, z)` selects blocks to remove (in world coordinates).
+    fn graphs(radius: i32, height: usize, carve: impl Fn(i32, i32, i32) -> bool) -> HashMap<ChunkPos, ChunkVisibility> {
+        let registry = BlockRegistry::builtin();
+        let mut graphs = HashMap::new();
+        for cz in -radius..=radius {
+            for cx in -radius..=radius {
+                let pos = ChunkPos::new(cx, cz);
+                let (ox, oz) = pos.origin();
+                let mut chunk = Chunk::new(pos);
+                for y in 0..height {
+                    for z in 0..CHUNK_SIZE {
+                        for x in 0..CHUNK_SIZE {
+                            let (wx, wz) = (ox + x as i32, oz + z as i32);
+                            let block = if carve(wx, y as i32, wz) { AIR } else { STONE };
+                            chunk.set(x, y, z, block);
+                        }
+                    }
+                }
+                graphs.insert(pos, ChunkVisibility::build(&chunk, &registry));
+            }
+        }
+        graphs
+    }
+
+    #[test]
+    fn open_air_reaches_every_section() {
+        let graphs = graphs(2, 0, |_, _, _| false);
+        let visible = visible_sections([8.0, 70.0, 8.0], &graphs, |_| true).unwrap();
+        assert_eq!(visible.len(), 25 * SECTION_COUNT);
+    }
+
+    #[test]
+    fn solid_ground_hides_the_sections_below() {
+        // Solid stone up to y = 64, with the camera standing on it.
+        let graphs = graphs(2, 64, |_, _, _| false);
+        let visible = visible_sections([8.0, 66.0, 8.0], &graphs, |_| true).unwrap();
+
+        // Every section above ground is visible, as is the top layer of stone.
+        for pos in graphs.keys() {
+            for y in 3..SECTION_COUNT as i32 {
+                assert!(visible.contains(SectionPos::new(pos.x, y, pos.z)), "{:?} {}", pos, y);
+            }
+        }
+        // Nothing is seen through the stone.
+        for y in 0..3 {
+            assert!(!visible.contains(SectionPos::new(0, y, 0)));
+            assert!(!visible.contains(SectionPos::new(2, y, -1)));
+        }
+    }
+
+    #[test]
+    fn caves_are_only_visible_through_their_openings() {
+        // A shaft from the surface at (8, 8) down to y = 20, opening into a tunnel
+        // along +X, and a separate cave under another chunk.
+        let graphs = graphs(2, 64, |x, y, z| {
+            let shaft = x == 8 && z == 8 && y >= 20;
+            let tunnel = z == 8 && y == 20 && (8..40).contains(&x);
+            let cave = (-24..-20).contains(&x) && (-24..-20).contains(&z) && (20..24).contains(&y);
+            shaft || tunnel || cave
+        });
+
+        // From the surface, the shaft and the tunnel can be seen, but not the cave.
+        let visible = visible_sections([8.5, 70.0, 8.5], &graphs, |_| true).unwrap();
+        assert!(visible.contains(SectionPos::new(0, 1, 0)));
+        assert!(visible.contains(SectionPos::new(2, 1, 0)));
+        assert!(!visible.contains(SectionPos::new(-2, 1, -2)));
+        assert!(!visible.contains(SectionPos::new(0, 0, 0)));
+
+        // From inside the cave, nothing past the sections around it can be seen.
+        let visible = visible_sections([-22.0, 22.0, -22.0], &graphs, |_| true).unwrap();
+        assert!(visible.contains(SectionPos::new(-2, 1, -2)));
+        assert!(visible.contains(SectionPos::new(-1, 1, -2)));
+        assert_eq!(visible.len(), 5);
+    }
+
+    #[test]
+    fn the_search_respects_the_view() {
+        let graphs = graphs(2, 0, |_, _, _| false);
+        // Only the sections in front of the camera, along +X, may be entered.
+        let visible = visible_sections([8.0, 70.0, 8.0], &graphs, |pos| pos.x >= 0).unwrap();
+        assert_eq!(visible.len(), 15 * SECTION_COUNT);
+        assert!(!visible.contains(SectionPos::new(-1, 4, 0)));
+    }
+
+    #[test]
+    fn the_search_starts_at_the_top_of_the_world_from_above() {
+        let graphs = graphs(1, 64, |_, _, _| false);
+        let above = (CHUNK_HEIGHT + 10) as f32;
+        let visible = visible_sections([8.0, above, 8.0], &graphs, |_| true).unwrap();
+        assert!(visible.contains(SectionPos::new(1, SECTION_COUNT as i32 - 1, -1)));
+        assert!(visible.contains(SectionPos::new(0, 3, 0)));
+        assert!(!visible.contains(SectionPos::new(0, 2, 0)));
+
+        assert!(visible_sections([100.0, 70.0, 100.0], &graphs, |_| true).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+//! # Block Interaction
+//! 
+//! This module times how long blocks take to break. A block breaks once the break
+//! button has been held on it for its hardness, in seconds; looking at another
+//! block or releasing the button starts over.
+
+use std::time::Duration;
+
+use crate::world::{BlockId, BlockRegistry, RaycastHit};
+
+/// The pause after a block breaks before the next one starts breaking, so holding
+/// the button on soft blocks does not clear a block every frame.
+const BREAK_COOLDOWN: Duration = Duration::from_millis(250);
+
+/// Tracks the progress of breaking the targeted block.
+#[derive(Debug, Default)]
+pub struct BlockBreaker {
+    /// The position and type of the block being broken.
+    target: Option<([i32; 3], BlockId)>,
+    /// How long the block has been broken for.
+    elapsed: Duration,
+    /// How long the block takes to break.
+    duration: Duration,
+    /// The time left before another block can start breaking.
+    cooldown: Duration,
+}
+
+impl BlockBreaker {
+    /// Creates a new `BlockBreaker`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advances breaking by `dt` while the break button is `held` on `target`.
+    /// Returns the position of the block that finished breaking, if any.
+    pub fn update(&mut self, dt: Duration, held: bool, target: Option<&RaycastHit>, registry: &BlockRegistry) -> Option<[i32; 3]> {
+        self.cooldown = self.cooldown.saturating_sub(dt);
+        let Some(hit) = target.filter(|_| held) else {
+            self.target = None;
+            return None;
+        };
+
+        if self.target != Some((hit.pos, hit.block)) {
+            self.target = Some((hit.pos, hit.block));
+            self.elapsed = Duration::ZERO;
+            self.duration = Duration::from_secs_f32(registry.get(hit.block).hardness.max(0.0));
+        }
+        if !self.cooldown.is_zero() {
+            return None;
+        }
+
+        self.elapsed += dt;
+        if self.elapsed < self.duration {
+            return None;
+        }
+        self.target = None;
+        self.cooldown = BREAK_COOLDOWN;
+        Some(hit.pos)
+    }
+
+    /// Returns how far the targeted block has been broken, from 0 to 1, or `None` if
+    /// no block is being broken.
+    pub fn progress(&self) -> Option<f32> {
+        self.target?;
+        if self.duration.is_zero() {
+            return Some(1.0);
+        }
+        Some((self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const DIRT: BlockId = 2;
+
+    fn hit(pos: [i32; 3], block: BlockId) -> RaycastHit {
+        RaycastHit { pos, normal: [0, 1, 0], distance: 2.0, block }
+    }
+
+    #[test]
+    fn blocks_break_after_their_hardness() {
+        let registry = BlockRegistry::builtin();
+        let mut breaker = BlockBreaker::new();
+        let stone = hit([1, 2, 3], STONE);
+        let step = Duration::from_millis(100);
+        assert_eq!(registry.get(STONE).hardness, 1.5);
+
+        for _ in 0..14 {
+            assert_eq!(breaker.update(step, true, Some(&stone), &registry), None);
+        }
+        assert!((breaker.progress().unwrap() - 14.0 / 15.0).abs() < 1e-4);
+        assert_eq!(breaker.update(step, true, Some(&stone), &registry), Some([1, 2, 3]));
+        assert_eq!(breaker.progress(), None);
+    }
+
+    #[test]
+    fn looking_away_or_releasing_starts_over() {
+        let registry = BlockRegistry::builtin();
+        let mut breaker = BlockBreaker::new();
+        let (stone, dirt) = (hit([1, 2, 3], STONE), hit([1, 3, 3], DIRT));
+        let step = Duration::from_millis(400);
+
+        breaker.update(step, true, Some(&stone), &registry);
+        breaker.update(step, true, Some(&dirt), &registry);
+        assert!((breaker.progress().unwrap() - 0.8).abs() < 1e-4);
+
+        breaker.update(step, false, Some(&dirt), &registry);
+        assert_eq!(breaker.progress(), None);
+        assert_eq!(breaker.update(step, true, Some(&dirt), &registry), None);
+        assert_eq!(breaker.update(step, true, None, &registry), None);
+        assert_eq!(breaker.progress(), None);
+    }
+
+    #[test]
+    fn soft_blocks_break_one_at_a_time() {
+        let registry = BlockRegistry::parse(r#"[(id: 1, name: "flower", textures: All("flower"))]"#).unwrap();
+        let mut breaker = BlockBreaker::new();
+        let frame = Duration::from_millis(50);
+
+        let broken = (0..20)
+            .filter(|&i| breaker.update(frame, true, Some(&hit([i, 0, 0], 1)), &registry).is_some())
+            .count();
+        // One block breaks at once, then one after every cooldown.
+        assert_eq!(broken, 4);
+    }
+}
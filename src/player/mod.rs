@@ -0,0 +1,205 @@
+//! # Player Module
+//! 
+//! This module defines the player, who walks through the world under gravity and
+//! collides with solid blocks. Physics advances once per game tick, so the player
+//! moves the same way at any frame rate, and is interpolated between ticks when
+//! drawn.
+
+mod interaction;
+mod physics;
+
+pub use interaction::BlockBreaker;
+
+use cgmath::{Point3, Rad, Vector3};
+
+use crate::input::MovementInput;
+use crate::player::physics::Body;
+use crate::simulation::TICK;
+use crate::world::{BlockRegistry, BlockView};
+
+/// The width of the player's collision box, in blocks.
+const WIDTH: f32 = 0.6;
+
+/// The height of the player's collision box, in blocks.
+const HEIGHT: f32 = 1.8;
+
+/// The height of the player's eyes above their feet, in blocks.
+pub const EYE_HEIGHT: f32 = 1.62;
+
+/// How far away the player can reach blocks, in blocks.
+pub const REACH: f32 = 5.0;
+
+/// The downward acceleration, in blocks per second squared.
+const GRAVITY: f32 = 28.0;
+
+/// The fastest the player can fall, in blocks per second.
+const TERMINAL_VELOCITY: f32 = 60.0;
+
+/// The upward speed of a jump, in blocks per second. It lifts the player about a
+/// block and a quarter.
+const JUMP_SPEED: f32 = 9.0;
+
+/// The walking speed, in blocks per second, before the sprint or crouch multiplier.
+const WALK_SPEED: f32 = 4.3;
+
+/// The player, standing in the world.
+pub struct Player {
+    body: Body,
+    /// The position of the player's feet at the previous tick.
+    previous: Vector3<f32>,
+}
+
+impl Player {
+    /// Creates a new `Player` with their feet at `position`.
+    pub fn new(position: Vector3<f32>) -> Self {
+        Self {
+            body: Body::new(position, WIDTH, HEIGHT),
+            previous: position,
+        }
+    }
+
+    /// Returns the position of the player's feet.
+    pub fn position(&self) -> Vector3<f32> {
+        self.body.position()
+    }
+
+    /// Returns the position of the player's eyes, `alpha` of the way from the
+    /// previous tick to the latest one.
+    pub fn eye(&self, alpha: f32) -> Point3<f32> {
+        let position = self.previous + (self.position() - self.previous) * alpha;
+        Point3::new(position.x, position.y + EYE_HEIGHT, position.z)
+    }
+
+    /// Returns `true` if the player is standing on a block.
+    #[cfg(test)]
+    pub fn on_ground(&self) -> bool {
+        self.body.on_ground
+    }
+
+    /// Returns `true` if the player overlaps the block at `pos`.
+    pub fn overlaps_block(&self, pos: [i32; 3]) -> bool {
+        self.body.overlaps_block(pos)
+    }
+
+    /// Moves the player's feet to `position` and stops them.
+    pub fn teleport(&mut self, position: Vector3<f32>) {
+        self.body.teleport(position);
+        self.previous = position;
+    }
+
+    /// Advances the player by a tick, walking in the direction of `input` relative
+    /// to the heading `yaw`. Holding up jumps.
+    pub fn tick(&mut self, input: MovementInput, yaw: Rad<f32>, view: &impl BlockView, registry: &BlockRegistry) {
+        self.previous = self.position();
+        let dt = TICK.as_secs_f32();
+        let (sin, cos) = yaw.0.sin_cos();
+        let forward = Vector3::new(sin, 0.0, -cos);
+        let right = Vector3::new(cos, 0.0, sin);
+        let walk = (forward * input.forward + right * input.right) * WALK_SPEED * input.speed_multiplier();
+
+        let velocity = &mut self.body.velocity;
+        velocity.x = walk.x;
+        velocity.z = walk.z;
+        if input.up > 0.0 && self.body.on_ground {
+            velocity.y = JUMP_SPEED;
+        }
+        velocity.y = (velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+
+        let motion = self.body.velocity * dt;
+        self.body.move_by(motion, view, registry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::TICKS_PER_SECOND;
+    use crate::world::{BlockId, World};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+
+    /// A world with a floor of stone at y = 0, reaching `half_extent` blocks from the
+    /// origin along X and Z. The physics tests share it.
+    pub(super) fn floor(half_extent: i32) -> World {
+        let mut world = World::new();
+        for z in -half_extent..half_extent {
+            for x in -half_extent..half_extent {
+                world.set_block(x, 0, z, STONE);
+            }
+        }
+        world
+    }
+
+    /// Runs the player for a number of ticks.
+    fn run(player: &mut Player, world: &World, input: MovementInput, yaw: Rad<f32>, ticks: u32) {
+        let registry = BlockRegistry::builtin();
+        for _ in 0..ticks {
+            player.tick(input, yaw, world, &registry);
+        }
+    }
+
+    #[test]
+    fn players_fall_onto_the_floor() {
+        let world = floor(16);
+        let mut player = Player::new(Vector3::new(0.5, 20.0, 0.5));
+        run(&mut player, &world, MovementInput::default(), Rad(0.0), 2 * TICKS_PER_SECOND);
+        assert_eq!(player.position().y, 1.0);
+        assert!(player.on_ground());
+    }
+
+    #[test]
+    fn jumps_clear_a_block() {
+        let world = floor(16);
+        let mut player = Player::new(Vector3::new(0.5, 1.0, 0.5));
+        run(&mut player, &world, MovementInput::default(), Rad(0.0), TICKS_PER_SECOND);
+
+        let jump = MovementInput { up: 1.0, ..Default::default() };
+        let mut peak: f32 = 0.0;
+        for _ in 0..TICKS_PER_SECOND {
+            run(&mut player, &world, jump, Rad(0.0), 1);
+            peak = peak.max(player.position().y);
+        }
+        assert!((2.1..2.5).contains(&peak), "jumped to {}", peak);
+        // Once jump is released, the player lands again.
+        run(&mut player, &world, MovementInput::default(), Rad(0.0), 2 * TICKS_PER_SECOND);
+        assert_eq!(player.position().y, 1.0);
+    }
+
+    #[test]
+    fn the_eye_is_interpolated_between_ticks() {
+        let world = floor(16);
+        let mut player = Player::new(Vector3::new(0.5, 1.0, 0.5));
+        let walk = MovementInput { forward: 1.0, ..Default::default() };
+        run(&mut player, &world, walk, Rad(0.0), 1);
+
+        let step = WALK_SPEED * TICK.as_secs_f32();
+        assert!((player.eye(0.0).z - 0.5).abs() < 1e-5);
+        assert!((player.eye(0.5).z - (0.5 - step / 2.0)).abs() < 1e-5);
+        assert!((player.eye(1.0).z - (0.5 - step)).abs() < 1e-5);
+        assert!((player.eye(1.0).y - (1.0 + EYE_HEIGHT)).abs() < 1e-5);
+
+        // Teleporting does not interpolate.
+        player.teleport(Vector3::new(10.0, 5.0, 10.0));
+        assert_eq!(player.eye(0.0), player.eye(1.0));
+    }
+
+    #[test]
+    fn walking_follows_the_heading_and_modifiers() {
+        let world = floor(16);
+        let mut walking = Player::new(Vector3::new(0.5, 1.0, 0.5));
+        let mut sprinting = Player::new(Vector3::new(0.5, 1.0, 0.5));
+
+        let walk = MovementInput { forward: 1.0, ..Default::default() };
+        let sprint = MovementInput { sprint: true, ..walk };
+        // Facing +X.
+        let yaw = Rad(std::f32::consts::FRAC_PI_2);
+        run(&mut walking, &world, walk, yaw, TICKS_PER_SECOND);
+        run(&mut sprinting, &world, sprint, yaw, TICKS_PER_SECOND);
+
+        let walked = walking.position() - Vector3::new(0.5, 1.0, 0.5);
+        assert!((walked.x - WALK_SPEED).abs() < 0.1 && walked.z.abs() < 1e-3);
+        assert!(sprinting.position().x > walking.position().x);
+        assert_eq!(walking.position().y, 1.0);
+    }
+}
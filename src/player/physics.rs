@@ -0,0 +1,308 @@
+//! # Physics
+//! 
+//! This module moves axis-aligned boxes through the voxel world. Motion is resolved
+//! one axis at a time against every solid block along the whole path, so fast
+//! bodies cannot pass through thin walls, and bodies on the ground step up onto
+//! low obstacles instead of stopping at them.
+
+use cgmath::Vector3;
+
+use crate::world::{BlockRegistry, BlockView};
+
+/// The tallest obstacle a body on the ground walks onto without jumping, in blocks.
+pub const STEP_HEIGHT: f32 = 0.6;
+
+/// How far boxes may overlap through rounding errors and still count as touching.
+const EPSILON: f32 = 1e-4;
+
+/// An axis-aligned box, in world coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Bounds {
+    /// Returns the box moved by `distance` along `axis`.
+    fn offset(mut self, axis: usize, distance: f32) -> Self {
+        self.min[axis] += distance;
+        self.max[axis] += distance;
+        self
+    }
+
+    /// Returns the box grown by `distance` along `axis`, in the direction of its sign.
+    fn expand(mut self, axis: usize, distance: f32) -> Self {
+        if distance < 0.0 {
+            self.min[axis] += distance;
+        } else {
+            self.max[axis] += distance;
+        }
+        self
+    }
+
+    /// Returns `true` if the boxes overlap along `axis` by more than rounding errors.
+    fn overlaps(&self, other: &Bounds, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] - EPSILON && self.max[axis] > other.min[axis] + EPSILON
+    }
+
+    /// Shortens a move of this box by `distance` along `axis` so it stops at `other`.
+    fn clip(&self, other: &Bounds, axis: usize, distance: f32) -> f32 {
+        if (0..3).any(|a| a != axis && !self.overlaps(other, a)) {
+            return distance;
+        }
+        if distance > 0.0 && other.min[axis] >= self.max[axis] - EPSILON {
+            distance.min(other.min[axis] - self.max[axis])
+        } else if distance < 0.0 && other.max[axis] <= self.min[axis] + EPSILON {
+            distance.max(other.max[axis] - self.min[axis])
+        } else {
+            distance
+        }
+    }
+}
+
+/// Moves `bounds` along each axis in turn, vertical first, stopping at `colliders`.
+/// Returns the motion that was applied.
+fn sweep(bounds: &mut Bounds, motion: [f32; 3], colliders: &[Bounds]) -> [f32; 3] {
+    let mut moved = [0.0; 3];
+    for axis in [1, 0, 2] {
+        let distance = colliders.iter().fold(motion[axis], |distance, collider| bounds.clip(collider, axis, distance));
+        *bounds = bounds.offset(axis, distance);
+        moved[axis] = distance;
+    }
+    moved
+}
+
+/// A box that moves through the world, such as the player.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Body {
+    /// The box itself is the source of truth for the position, so resting against a
+    /// block never drifts into it through rounding.
+    bounds: Bounds,
+    /// The velocity, in blocks per second.
+    pub velocity: Vector3<f32>,
+    /// Whether the body is standing on a block.
+    pub on_ground: bool,
+}
+
+impl Body {
+    /// Creates a body `width` blocks wide and `height` blocks tall, with the center of
+    /// its base at `position`.
+    pub fn new(position: Vector3<f32>, width: f32, height: f32) -> Self {
+        let half = width / 2.0;
+        Self {
+            bounds: Bounds {
+                min: [position.x - half, position.y, position.z - half],
+                max: [position.x + half, position.y + height, position.z + half],
+            },
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            on_ground: false,
+        }
+    }
+
+    /// Returns the center of the body's base.
+    pub fn position(&self) -> Vector3<f32> {
+        let Bounds { min, max } = self.bounds;
+        Vector3::new((min[0] + max[0]) / 2.0, min[1], (min[2] + max[2]) / 2.0)
+    }
+
+    /// Moves the body so the center of its base is at `position`, and stops it.
+    pub fn teleport(&mut self, position: Vector3<f32>) {
+        let offset = position - self.position();
+        for axis in 0..3 {
+            self.bounds = self.bounds.offset(axis, offset[axis]);
+        }
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.on_ground = false;
+    }
+
+    /// Returns `true` if the body overlaps the full block at `pos`, e.g. so a block
+    /// is not placed inside it.
+    pub fn overlaps_block(&self, pos: [i32; 3]) -> bool {
+        let min = pos.map(|v| v as f32);
+        let block = Bounds { min, max: min.map(|v| v + 1.0) };
+        (0..3).all(|axis| self.bounds.overlaps(&block, axis))
+    }
+
+    /// Moves the body by `motion`, stopping at solid blocks, and returns the motion
+    /// that was applied. Velocity along a blocked axis is cancelled.
+    pub fn move_by(&mut self, motion: Vector3<f32>, view: &impl BlockView, registry: &BlockRegistry) -> Vector3<f32> {
+        let motion: [f32; 3] = motion.into();
+
+        // Every block the body could touch on the way, including when stepping up.
+        let mut reach = self.bounds;
+        for (axis, &distance) in motion.iter().enumerate() {
+            reach = reach.expand(axis, distance);
+        }
+        let colliders = colliders(reach.expand(1, STEP_HEIGHT), view, registry);
+
+        let mut bounds = self.bounds;
+        let mut moved = sweep(&mut bounds, motion, &colliders);
+        let landed = motion[1] < 0.0 && moved[1] > motion[1];
+        let mut on_ground = landed;
+
+        // If walking into an obstacle, try climbing it and keep whichever attempt
+        // got further.
+        let blocked = moved[0] != motion[0] || moved[2] != motion[2];
+        if blocked && (self.on_ground || landed) {
+            let mut stepped = self.bounds;
+            let up = sweep(&mut stepped, [0.0, STEP_HEIGHT, 0.0], &colliders)[1];
+            let across = sweep(&mut stepped, [motion[0], 0.0, motion[2]], &colliders);
+            let down = sweep(&mut stepped, [0.0, -up, 0.0], &colliders)[1];
+
+            let horizontal = |m: [f32; 3]| m[0] * m[0] + m[2] * m[2];
+            if horizontal(across) > horizontal(moved) {
+                bounds = stepped;
+                moved = [across[0], up + down, across[2]];
+                on_ground = down > -up;
+            }
+        }
+
+        for axis in 0..3 {
+            if moved[axis] != motion[axis] {
+                self.velocity[axis] = 0.0;
+            }
+        }
+        self.on_ground = on_ground;
+        self.bounds = bounds;
+        moved.into()
+    }
+}
+
+/// Returns the collision boxes of the solid blocks overlapping `reach`.
+fn colliders(reach: Bounds, view: &impl BlockView, registry: &BlockRegistry) -> Vec<Bounds> {
+    let range = |axis: usize| reach.min[axis].floor() as i32..=reach.max[axis].floor() as i32;
+    let mut colliders = Vec::new();
+    for y in range(1) {
+        for z in range(2) {
+            for x in range(0) {
+                let def = registry.get(view.block(x, y, z));
+                if def.solid {
+                    let min = [x as f32, y as f32, z as f32];
+                    colliders.push(Bounds { min, max: [min[0] + 1.0, min[1] + def.height, min[2] + 1.0] });
+                }
+            }
+        }
+    }
+    colliders
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    use crate::player::tests::floor;
+    use crate::world::BlockId;
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const WATER: BlockId = 5;
+    const SLAB: BlockId = 15;
+
+    fn player(x: f32, y: f32, z: f32) -> Body {
+        Body::new(Vector3::new(x, y, z), 0.6, 1.8)
+    }
+
+    fn assert_near(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-4, "{:?} is not {:?}", actual, expected);
+    }
+
+    #[test]
+    fn falling_bodies_land_on_the_floor() {
+        let (world, registry) = (floor(8), BlockRegistry::builtin());
+        let mut body = player(0.5, 5.0, 0.5);
+        let moved = body.move_by(Vector3::new(0.0, -10.0, 0.0), &world, &registry);
+
+        assert_eq!(moved.y, -4.0);
+        assert_near(body.position(), Vector3::new(0.5, 1.0, 0.5));
+        assert!(body.on_ground);
+
+        // After walking off the edge of the floor, the body falls.
+        body.move_by(Vector3::new(10.0, -0.1, 0.0), &world, &registry);
+        body.move_by(Vector3::new(0.0, -0.1, 0.0), &world, &registry);
+        assert!(!body.on_ground);
+        assert_near(body.position(), Vector3::new(10.5, 0.9, 0.5));
+    }
+
+    #[test]
+    fn non_solid_blocks_are_passed_through() {
+        let (mut world, registry) = (floor(8), BlockRegistry::builtin());
+        world.set_block(0, 1, 0, WATER);
+        let mut body = player(0.5, 3.0, 0.5);
+        body.move_by(Vector3::new(0.0, -5.0, 0.0), &world, &registry);
+        assert_eq!(body.position().y, 1.0);
+    }
+
+    #[test]
+    fn bodies_slide_along_walls() {
+        let (mut world, registry) = (floor(8), BlockRegistry::builtin());
+        for z in -8..8 {
+            for y in 1..4 {
+                world.set_block(2, y, z, STONE);
+            }
+        }
+        let mut body = player(0.5, 1.0, 0.5);
+        body.velocity = Vector3::new(5.0, 0.0, 5.0);
+        let moved = body.move_by(Vector3::new(3.0, 0.0, 3.0), &world, &registry);
+
+        // The body stops against the wall at x = 2 but keeps moving along Z.
+        assert!((moved.x - 1.2).abs() < 1e-5);
+        assert_eq!(moved.z, 3.0);
+        assert!((body.position().x - 1.7).abs() < 1e-5);
+        assert_eq!(body.velocity, Vector3::new(0.0, 0.0, 5.0));
+        assert!(!body.overlaps_block([2, 1, 3]));
+        assert!(body.overlaps_block([1, 2, 3]));
+        assert!(!body.overlaps_block([1, 3, 3]));
+
+        // Pushing into the wall again does not move the body into it.
+        let moved = body.move_by(Vector3::new(0.5, 0.0, 0.0), &world, &registry);
+        assert!(moved.x.abs() < 1e-5);
+        assert!((body.position().x - 1.7).abs() < 1e-5);
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel() {
+        let (mut world, registry) = (floor(8), BlockRegistry::builtin());
+        world.set_block(0, 60, 0, STONE);
+
+        // Falling from far above stops on the floor rather than passing through it.
+        let mut body = player(0.5, 50.0, 0.5);
+        body.move_by(Vector3::new(0.0, -1000.0, 0.0), &world, &registry);
+        assert_eq!(body.position().y, 1.0);
+
+        // A single thin block stops a body rising fast beneath it.
+        let mut body = player(0.5, 30.0, 0.5);
+        body.move_by(Vector3::new(0.0, 500.0, 0.0), &world, &registry);
+        assert!((body.position().y - 58.2).abs() < 1e-4);
+
+        // A wall one block thick stops a body moving sideways fast.
+        world.set_block(4, 1, 0, STONE);
+        let mut body = player(0.5, 1.0, 0.5);
+        body.move_by(Vector3::new(100.0, 0.0, 0.0), &world, &registry);
+        assert!((body.position().x - 3.7).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bodies_step_onto_half_blocks_but_not_full_blocks() {
+        let (mut world, registry) = (floor(8), BlockRegistry::builtin());
+        world.set_block(2, 1, 0, SLAB);
+        world.set_block(0, 1, 2, STONE);
+
+        let mut body = player(0.5, 1.0, 0.5);
+        body.on_ground = true;
+        body.move_by(Vector3::new(2.0, 0.0, 0.0), &world, &registry);
+        assert_near(body.position(), Vector3::new(2.5, 1.5, 0.5));
+        assert!(body.on_ground);
+
+        let mut body = player(0.5, 1.0, 0.5);
+        body.on_ground = true;
+        body.move_by(Vector3::new(0.0, 0.0, 2.0), &world, &registry);
+        assert!((body.position().z - 1.7).abs() < 1e-5);
+        assert_eq!(body.position().y, 1.0);
+
+        // Bodies in the air do not step.
+        let mut body = player(0.5, 1.0, 0.5);
+        body.move_by(Vector3::new(2.0, 0.0, 0.0), &world, &registry);
+        assert!((body.position().x - 1.7).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,534 @@
+//! # Texture Atlas
+//! 
+//! This module packs individual block textures into a single atlas texture.
+//! Every texture is surrounded by a border of extruded edge pixels so that
+//! filtering and mipmapping never bleed neighbouring textures into each other.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use image::RgbaImage;
+
+/// The largest atlas edge length, in pixels, that the builder will produce.
+const MAX_ATLAS_SIZE: u32 = 8192;
+
+/// The smallest atlas edge length, in pixels.
+const MIN_ATLAS_SIZE: u32 = 64;
+
+/// The location of a texture inside the atlas, in normalized texture coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// The top-left corner of the texture.
+    pub min: [f32; 2],
+    /// The size of the texture.
+    pub size: [f32; 2],
+}
+
+impl AtlasRegion {
+    /// A region covering the whole atlas.
+    pub const FULL: AtlasRegion = AtlasRegion { min: [0.0, 0.0], size: [1.0, 1.0] };
+
+    /// Returns the region packed as `[min.x, min.y, size.x, size.y]`, as stored in vertices.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.min[0], self.min[1], self.size[0], self.size[1]]
+    }
+}
+
+/// An error raised while building a texture atlas.
+#[derive(Debug)]
+pub enum AtlasError {
+    /// A texture file could not be read or decoded.
+    Image { name: String, error: image::ImageError },
+    /// The texture directory could not be read.
+    Io(std::io::Error),
+    /// The padding is not a power of two.
+    InvalidPadding(u32),
+    /// The textures do not fit into the largest supported atlas.
+    TooLarge,
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::Image { name, error } => write!(f, "failed to load texture '{}': {}", name, error),
+            AtlasError::Io(err) => write!(f, "failed to read texture directory: {}", err),
+            AtlasError::InvalidPadding(padding) => write!(f, "atlas padding {} is not a power of two", padding),
+            AtlasError::TooLarge => write!(f, "textures do not fit into a {}x{} atlas", MAX_ATLAS_SIZE, MAX_ATLAS_SIZE),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+/// Collects textures and packs them into a `TextureAtlas`.
+pub struct AtlasBuilder {
+    padding: u32,
+    textures: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    /// Creates a new `AtlasBuilder`.
+    ///
+    /// - `padding`: The number of extruded border pixels around each texture. It must
+    ///   be a power of two, and also limits the number of mip levels so that the
+    ///   smallest level still has at least one pixel of padding.
+    pub fn new(padding: u32) -> Self {
+        Self {
+            padding,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Adds a texture to the atlas.
+    pub fn add(&mut self, name: impl Into<String>, image: RgbaImage) -> &mut Self {
+        self.textures.push((name.into(), image));
+        self
+    }
+
+    /// Adds every PNG file in a directory, named after the file stem.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>) -> Result<&mut Self, AtlasError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(AtlasError::Io)? {
+            let path = entry.map_err(AtlasError::Io)?.path();
+            if path.extension().is_some_and(|ext| ext == "png") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+            let image = image::open(&path)
+                .map_err(|error| AtlasError::Image { name: name.clone(), error })?
+                .to_rgba8();
+            self.add(name, image);
+        }
+
+        Ok(self)
+    }
+
+    /// Packs the textures into an atlas and generates its mip levels.
+    pub fn build(&self) -> Result<TextureAtlas, AtlasError> {
+        if !self.padding.is_power_of_two() {
+            return Err(AtlasError::InvalidPadding(self.padding));
+        }
+        let mip_level_count = self.padding.trailing_zeros() + 1;
+        // Cells are aligned so that texture borders fall on whole pixels at every mip level.
+        let align = 1 << (mip_level_count - 1);
+
+        // Pack the tallest textures first to keep shelves tight; ties are broken by
+        // name so the layout is deterministic.
+        let mut order: Vec<usize> = (0..self.textures.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (name_a, image_a) = &self.textures[a];
+            let (name_b, image_b) = &self.textures[b];
+            image_b.height().cmp(&image_a.height()).then_with(|| name_a.cmp(name_b))
+        });
+
+        let cells: Vec<(u32, u32)> = order
+            .iter()
+            .map(|&i| {
+                let image = &self.textures[i].1;
+                let cell = |extent: u32| (extent + 2 * self.padding).next_multiple_of(align);
+                (cell(image.width()), cell(image.height()))
+            })
+            .collect();
+
+        let mut size = MIN_ATLAS_SIZE;
+        let positions = loop {
+            if let Some(positions) = pack_shelves(&cells, size) {
+                break positions;
+            }
+            size *= 2;
+            if size > MAX_ATLAS_SIZE {
+                return Err(AtlasError::TooLarge);
+            }
+        };
+
+        let mut base = RgbaImage::new(size, size);
+        let mut regions = HashMap::new();
+        for (&i, &(x, y)) in order.iter().zip(&positions) {
+            let (name, image) = &self.textures[i];
+            blit_padded(&mut base, image, x, y, self.padding);
+
+            let (w, h) = image.dimensions();
+            regions.insert(name.clone(), AtlasRegion {
+                min: [(x + self.padding) as f32 / size as f32, (y + self.padding) as f32 / size as f32],
+                size: [w as f32 / size as f32, h as f32 / size as f32],
+            });
+        }
+
+        let mut mips = vec![base];
+        for _ in 1..mip_level_count {
+            let next = downsample(mips.last().unwrap());
+            mips.push(next);
+        }
+
+        Ok(TextureAtlas { size, mips, regions })
+    }
+}
+
+/// Places cells on horizontal shelves inside a square of the given size, returning
+/// the top-left corner of each cell, or `None` if they do not fit.
+fn pack_shelves(cells: &[(u32, u32)], size: u32) -> Option<Vec<(u32, u32)>> {
+    let mut positions = Vec::with_capacity(cells.len());
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+    for &(w, h) in cells {
+        if w > size {
+            return None;
+        }
+        if x + w > size {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        if y + h > size {
+            return None;
+        }
+
+        positions.push((x, y));
+        x += w;
+        shelf_height = shelf_height.max(h);
+    }
+
+    Some(positions)
+}
+
+/// Copies `image` into `atlas` at `(x + padding, y + padding)` and fills the
+/// surrounding border by repeating the nearest edge pixel.
+fn blit_padded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let (w, h) = image.dimensions();
+    for dy in 0..h + 2 * padding {
+        for dx in 0..w + 2 * padding {
+            let sx = (dx as i64 - padding as i64).clamp(0, w as i64 - 1) as u32;
+            let sy = (dy as i64 - padding as i64).clamp(0, h as i64 - 1) as u32;
+            atlas.put_pixel(x + dx, y + dy, *image.get_pixel(sx, sy));
+        }
+    }
+}
+
+/// Halves an sRGB image with a 2x2 box filter, averaging colours in linear space.
+fn downsample(image: &RgbaImage) -> RgbaImage {
+    let (w, h) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+    let to_linear = |c: u8| (c as f32 / 255.0).powf(2.2);
+    let to_srgb = |c: f32| (c.powf(1.0 / 2.2) * 255.0).round() as u8;
+
+    RgbaImage::from_fn(w, h, |x, y| {
+        let mut sum = [0.0f32; 4];
+        for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let pixel = image.get_pixel((2 * x + sx).min(image.width() - 1), (2 * y + sy).min(image.height() - 1));
+            for c in 0..3 {
+                sum[c] += to_linear(pixel[c]);
+            }
+            sum[3] += pixel[3] as f32;
+        }
+        image::Rgba([
+            to_srgb(sum[0] / 4.0),
+            to_srgb(sum[1] / 4.0),
+            to_srgb(sum[2] / 4.0),
+            (sum[3] / 4.0).round() as u8,
+        ])
+    })
+}
+
+/// A packed texture atlas with its mip chain, stored on the CPU.
+pub struct TextureAtlas {
+    size: u32,
+    mips: Vec<RgbaImage>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    /// Returns the edge length of the square atlas, in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the mip levels of the atlas, starting with the full resolution image.
+    pub fn mips(&self) -> &[RgbaImage] {
+        &self.mips
+    }
+
+    /// Returns the region of the texture with the given name.
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    /// Uploads the atlas to the GPU.
+    pub fn upload(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> AtlasTexture {
+        AtlasTexture::new(device, queue, self)
+    }
+}
+
+/// A texture atlas uploaded to the GPU, with the bind group used to sample it.
+pub struct AtlasTexture {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl AtlasTexture {
+    /// Uploads every mip level of the atlas and creates its sampler and bind group.
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, atlas: &TextureAtlas) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Block Atlas"),
+            size: wgpu::Extent3d {
+                width: atlas.size(),
+                height: atlas.size(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: atlas.mips().len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, mip) in atlas.mips().iter().enumerate() {
+            let (width, height) = mip.dimensions();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                mip.as_raw(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Block Atlas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("atlas_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("atlas_bind_group"),
+        });
+
+        Self {
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Returns the layout of the atlas bind group.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Returns the bind group containing the atlas texture and its sampler.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba(color))
+    }
+
+    /// Returns the pixel rectangle `(x, y, w, h)` covered by a region at mip level 0.
+    fn pixel_rect(atlas: &TextureAtlas, name: &str) -> (u32, u32, u32, u32) {
+        let region = atlas.region(name).unwrap();
+        let size = atlas.size() as f32;
+        (
+            (region.min[0] * size) as u32,
+            (region.min[1] * size) as u32,
+            (region.size[0] * size) as u32,
+            (region.size[1] * size) as u32,
+        )
+    }
+
+    #[test]
+    fn packs_textures_without_overlap() {
+        let mut builder = AtlasBuilder::new(4);
+        for i in 0..40u8 {
+            let size = if i % 3 == 0 { 32 } else { 16 };
+            builder.add(format!("tex{}", i), solid(size, size, [i, 0, 0, 255]));
+        }
+        let atlas = builder.build().unwrap();
+        assert!(atlas.size().is_power_of_two());
+
+        let padded: Vec<_> = (0..40)
+            .map(|i| {
+                let (x, y, w, h) = pixel_rect(&atlas, &format!("tex{}", i));
+                assert!(x >= 4 && y >= 4 && x + w + 4 <= atlas.size() && y + h + 4 <= atlas.size());
+                (x - 4, y - 4, w + 8, h + 8)
+            })
+            .collect();
+
+        for (i, a) in padded.iter().enumerate() {
+            for b in &padded[i + 1..] {
+                let overlap = a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3;
+                assert!(!overlap, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn textures_are_copied_into_their_regions() {
+        let mut builder = AtlasBuilder::new(2);
+        builder.add("red", solid(16, 16, [255, 0, 0, 255]));
+        builder.add("blue", solid(16, 16, [0, 0, 255, 255]));
+        let atlas = builder.build().unwrap();
+
+        let (x, y, w, h) = pixel_rect(&atlas, "blue");
+        assert_eq!((w, h), (16, 16));
+        for py in y..y + h {
+            for px in x..x + w {
+                assert_eq!(atlas.mips()[0].get_pixel(px, py).0, [0, 0, 255, 255]);
+            }
+        }
+    }
+
+    #[test]
+    fn padding_repeats_edge_pixels() {
+        let mut image = solid(4, 4, [0, 0, 0, 255]);
+        image.put_pixel(0, 0, image::Rgba([10, 20, 30, 255]));
+        image.put_pixel(3, 3, image::Rgba([40, 50, 60, 255]));
+
+        let mut builder = AtlasBuilder::new(4);
+        builder.add("corner", image);
+        let atlas = builder.build().unwrap();
+        let base = &atlas.mips()[0];
+
+        let (x, y, w, h) = pixel_rect(&atlas, "corner");
+        for d in 1..=4 {
+            assert_eq!(base.get_pixel(x - d, y - d).0, [10, 20, 30, 255]);
+            assert_eq!(base.get_pixel(x + w - 1 + d, y + h - 1 + d).0, [40, 50, 60, 255]);
+        }
+    }
+
+    #[test]
+    fn mip_levels_are_limited_by_padding() {
+        let mut builder = AtlasBuilder::new(4);
+        builder.add("a", solid(16, 16, [255, 255, 255, 255]));
+        let atlas = builder.build().unwrap();
+
+        assert_eq!(atlas.mips().len(), 3);
+        for (level, mip) in atlas.mips().iter().enumerate() {
+            assert_eq!(mip.width(), atlas.size() >> level);
+        }
+    }
+
+    #[test]
+    fn mip_levels_do_not_bleed_between_textures() {
+        let mut builder = AtlasBuilder::new(4);
+        builder.add("black", solid(16, 16, [0, 0, 0, 255]));
+        builder.add("white", solid(16, 16, [255, 255, 255, 255]));
+        let atlas = builder.build().unwrap();
+
+        let (x, y, w, h) = pixel_rect(&atlas, "black");
+        for (level, mip) in atlas.mips().iter().enumerate() {
+            // The texture and one pixel of its padding stay untouched at every level.
+            let scale = 1 << level;
+            for py in (y - scale) / scale..(y + h + scale) / scale {
+                for px in (x - scale) / scale..(x + w + scale) / scale {
+                    assert_eq!(mip.get_pixel(px, py).0, [0, 0, 0, 255], "level {} ({}, {})", level, px, py);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grows_the_atlas_until_textures_fit() {
+        let mut builder = AtlasBuilder::new(1);
+        builder.add("big", solid(100, 100, [0; 4]));
+        assert_eq!(builder.build().unwrap().size(), 128);
+
+        let mut builder = AtlasBuilder::new(1);
+        builder.add("huge", solid(MAX_ATLAS_SIZE, 1, [0; 4]));
+        assert!(matches!(builder.build(), Err(AtlasError::TooLarge)));
+    }
+
+    #[test]
+    fn rejects_padding_that_is_not_a_power_of_two() {
+        assert!(matches!(AtlasBuilder::new(3).build(), Err(AtlasError::InvalidPadding(3))));
+    }
+
+    #[test]
+    fn layout_is_deterministic() {
+        let build = |reverse: bool| {
+            let mut names: Vec<_> = (0..10).map(|i| format!("t{}", i)).collect();
+            if reverse {
+                names.reverse();
+            }
+            let mut builder = AtlasBuilder::new(2);
+            for name in names {
+                builder.add(name, solid(16, 16, [0; 4]));
+            }
+            builder.build().unwrap()
+        };
+
+        let (a, b) = (build(false), build(true));
+        for i in 0..10 {
+            let name = format!("t{}", i);
+            assert_eq!(a.region(&name), b.region(&name));
+        }
+    }
+
+    #[test]
+    fn bundled_textures_pack() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/textures/blocks");
+        let mut builder = AtlasBuilder::new(4);
+        builder.add_dir(dir).unwrap();
+        let atlas = builder.build().unwrap();
+        assert!(atlas.region("stone").is_some());
+    }
+}
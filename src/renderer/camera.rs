@@ -2,7 +2,26 @@
 //! 
 //! This module defines a 3D perspective camera that can be moved and rotated.
 
-use cgmath::{Matrix4, Point3, Vector3, perspective, Deg, InnerSpace, Rad, Matrix3};
+use cgmath::{Matrix4, Point3, Vector3, perspective, Deg, InnerSpace, Rad};
+
+/// Converts OpenGL clip space depth (-1 to 1), as produced by `cgmath`, into the
+/// 0 to 1 range used by `wgpu`.
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// Flips `wgpu` clip space depth so the near plane maps to 1 and the far plane to 0.
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0,  0.0, 0.0,
+    0.0, 1.0,  0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0,  1.0, 1.0,
+);
 
 /// Represents a camera in 3D space.
 pub struct Camera {
@@ -13,6 +32,7 @@ pub struct Camera {
     pub aspect: f32,
     pub near: f32,
     pub far: f32,
+    pub reverse_z: bool,
 }
 
 impl Camera {
@@ -34,6 +54,7 @@ impl Camera {
             aspect,
             near,
             far,
+            reverse_z: false,
         }
     }
     
@@ -42,9 +63,15 @@ impl Camera {
         Matrix4::look_at_rh(self.position, self.target, self.up)
     }
     
-    /// Calculates the camera's projection matrix.
+    /// Calculates the camera's projection matrix, mapping depth into `wgpu`'s 0 to 1
+    /// range (reversed if `reverse_z` is set).
     pub fn get_projection_matrix(&self) -> Matrix4<f32> {
-        perspective(Deg(self.fovy), self.aspect, self.near, self.far)
+        let projection = OPENGL_TO_WGPU_MATRIX * perspective(Deg(self.fovy), self.aspect, self.near, self.far);
+        if self.reverse_z {
+            REVERSE_Z_MATRIX * projection
+        } else {
+            projection
+        }
     }
     
     /// Sets the camera's aspect ratio.
@@ -52,27 +79,111 @@ impl Camera {
         self.aspect = aspect;
     }
     
-    /// Moves the camera forward or backward.
+    /// Returns the unit vector the camera looks along.
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.position).normalize()
+    }
+
+    /// Points the camera along a yaw and pitch, keeping its position. A yaw of zero
+    /// looks along -Z, positive yaw turns right and positive pitch looks up.
+    pub fn look(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        let direction = Vector3::new(yaw.0.sin() * pitch.0.cos(), pitch.0.sin(), -yaw.0.cos() * pitch.0.cos());
+        self.target = self.position + direction;
+    }
+
+    /// Returns the yaw and pitch the camera looks along, as accepted by `look`.
+    pub fn yaw_pitch(&self) -> (Rad<f32>, Rad<f32>) {
+        let forward = self.forward();
+        (Rad(forward.x.atan2(-forward.z)), Rad(forward.y.clamp(-1.0, 1.0).asin()))
+    }
+
+    /// Moves the camera forward or backward along its heading, ignoring its pitch.
     pub fn move_forward(&mut self, distance: f32) {
-        let forward = (self.target - self.position).normalize();
-        self.position += forward * distance;
-        self.target += forward * distance;
+        let forward = self.forward();
+        let heading = Vector3::new(forward.x, 0.0, forward.z).normalize();
+        self.translate(heading * distance);
     }
-    
+
     /// Moves the camera to the right or left.
     pub fn move_right(&mut self, distance: f32) {
-        let forward = (self.target - self.position).normalize();
-        let right = forward.cross(self.up).normalize();
-        self.position += right * distance;
-        self.target += right * distance;
+        let right = self.forward().cross(self.up).normalize();
+        self.translate(right * distance);
     }
-    
-    /// Rotates the camera around its target.
-    pub fn rotate_around_target(&mut self, yaw: f32, pitch: f32) {
-        let offset: Vector3<f32> = self.position - self.target;
-        let rotation: Matrix3<f32> = Matrix3::from_angle_y(Rad(yaw)) * Matrix3::from_angle_x(Rad(pitch));
-        let rotated_offset = rotation * offset;
 
-        self.position = self.target + rotated_offset;
+    /// Moves the camera up or down.
+    pub fn move_up(&mut self, distance: f32) {
+        self.translate(Vector3::unit_y() * distance);
     }
-}
\ No newline at end of file
+
+    /// Moves the camera and its target by an offset.
+    pub fn translate(&mut self, offset: Vector3<f32>) {
+        self.position += offset;
+        self.target += offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector4;
+
+    fn depth_at(camera: &Camera, distance: f32) -> f32 {
+        let clip = camera.get_projection_matrix() * Vector4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+            45.0,
+            16.0 / 9.0,
+            0.1,
+            500.0,
+        )
+    }
+
+    #[test]
+    fn depth_spans_zero_to_one() {
+        let camera = camera();
+        assert!(depth_at(&camera, 0.1).abs() < 1e-5);
+        assert!((depth_at(&camera, 500.0) - 1.0).abs() < 1e-5);
+        assert!(depth_at(&camera, 10.0) < depth_at(&camera, 20.0));
+    }
+
+    #[test]
+    fn reverse_z_flips_depth() {
+        let mut camera = camera();
+        camera.reverse_z = true;
+        assert!((depth_at(&camera, 0.1) - 1.0).abs() < 1e-5);
+        assert!(depth_at(&camera, 500.0).abs() < 1e-5);
+        assert!(depth_at(&camera, 10.0) > depth_at(&camera, 20.0));
+    }
+
+    #[test]
+    fn look_round_trips_through_yaw_and_pitch() {
+        let mut camera = camera();
+        assert!((camera.forward() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-6);
+
+        camera.look(Rad(std::f32::consts::FRAC_PI_2), Rad(0.0));
+        assert!((camera.forward() - Vector3::unit_x()).magnitude() < 1e-6);
+
+        camera.look(Rad(-2.5), Rad(0.7));
+        let (yaw, pitch) = camera.yaw_pitch();
+        assert!((yaw.0 + 2.5).abs() < 1e-5 && (pitch.0 - 0.7).abs() < 1e-5);
+    }
+
+    #[test]
+    fn moving_forward_ignores_pitch() {
+        let mut camera = camera();
+        camera.look(Rad(std::f32::consts::FRAC_PI_2), Rad(-1.2));
+        camera.move_forward(2.0);
+        camera.move_right(1.0);
+        camera.move_up(0.5);
+        assert!((camera.position - Point3::new(2.0, 0.5, 1.0)).magnitude() < 1e-5);
+        // The camera still looks the same way.
+        let (_, pitch) = camera.yaw_pitch();
+        assert!((pitch.0 + 1.2).abs() < 1e-5);
+    }
+}
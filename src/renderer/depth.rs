@@ -0,0 +1,82 @@
+//! # Depth Buffer
+//! 
+//! This module defines the depth texture used for depth testing in the main
+//! render pass, with optional reverse-Z for better precision at long distances.
+
+/// A depth texture sized to the render surface.
+pub struct DepthTexture {
+    // Kept alive for as long as the view is in use.
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    reverse_z: bool,
+}
+
+impl DepthTexture {
+    /// The format of the depth texture. A floating point format is required for
+    /// reverse-Z to improve precision.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    /// Creates a new depth texture.
+    ///
+    /// - `reverse_z`: Whether depth is mapped from 1 at the near plane to 0 at the far plane.
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, reverse_z: bool) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            _texture: texture,
+            view,
+            reverse_z,
+        }
+    }
+
+    /// Returns `true` if the texture uses reverse-Z.
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// Returns the value the depth buffer is cleared to at the start of a frame.
+    pub fn clear_value(&self) -> f32 {
+        if self.reverse_z { 0.0 } else { 1.0 }
+    }
+
+    /// Returns the depth state for pipelines that render into this texture.
+    pub fn depth_stencil_state(&self, depth_write_enabled: bool) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: Self::FORMAT,
+            depth_write_enabled,
+            depth_compare: if self.reverse_z {
+                wgpu::CompareFunction::GreaterEqual
+            } else {
+                wgpu::CompareFunction::LessEqual
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    /// Returns the depth attachment for a render pass that clears the buffer.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(self.clear_value()),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
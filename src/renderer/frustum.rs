@@ -0,0 +1,200 @@
+//! # Frustum
+//! 
+//! This module extracts the view frustum of a camera and tests axis-aligned
+//! bounding boxes against it, so chunks outside of the view can be skipped.
+
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+use crate::renderer::camera::Camera;
+
+/// The number of chunk sections drawn and skipped in the latest frame. Only
+/// sections with geometry are counted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub visible: usize,
+    /// Sections outside of the view frustum.
+    pub culled: usize,
+    /// Sections hidden behind terrain by cave culling.
+    pub occluded: usize,
+}
+
+/// An axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// Creates a new `Aabb` from its minimum and maximum corners.
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing all of the given points, or `None` if
+    /// there are no points.
+    pub fn enclosing(points: impl IntoIterator<Item = [f32; 3]>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = Vector3::from(points.next()?);
+        Some(points.fold(Self::new(first, first), |aabb, [x, y, z]| Self {
+            min: Vector3::new(aabb.min.x.min(x), aabb.min.y.min(y), aabb.min.z.min(z)),
+            max: Vector3::new(aabb.max.x.max(x), aabb.max.y.max(y), aabb.max.z.max(z)),
+        }))
+    }
+}
+
+/// A plane of points `p` where `normal · p + distance = 0`. Points with a positive
+/// signed distance are in front of the plane.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Creates a plane from the coefficients `(a, b, c, d)` of `ax + by + cz + d = 0`,
+    /// normalized so signed distances are in world units.
+    fn from_coefficients(coefficients: Vector4<f32>) -> Self {
+        let normal = coefficients.truncate();
+        let length = normal.magnitude();
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    /// Returns the signed distance from the plane to a point.
+    pub fn signed_distance(&self, point: Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// The six planes bounding the volume visible to a camera, facing inwards.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes, in that order.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a combined projection and view matrix whose
+    /// clip space has depth in `0..=1`, as used by `wgpu`, with the near plane at 0.
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let m = view_projection;
+        let [x, y, z, w] = [m.row(0), m.row(1), m.row(2), m.row(3)];
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(Plane::from_coefficients),
+        }
+    }
+
+    /// Extracts the frustum of a camera.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let mut frustum = Self::from_matrix(camera.get_projection_matrix() * camera.get_view_matrix());
+        if camera.reverse_z {
+            frustum.planes.swap(4, 5);
+        }
+        frustum
+    }
+
+    /// Returns `true` if a point is inside the frustum.
+    #[cfg(test)]
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Returns `true` if a box is at least partly inside the frustum.
+    ///
+    /// This is conservative: a box near a corner of the frustum may be reported as
+    /// visible even though it lies just outside.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal.
+            let corner = Vector3::new(
+                if plane.normal.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.normal.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.normal.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Point3;
+
+    /// A camera at the origin looking down -Z with a 90 degree vertical field of view.
+    fn camera(reverse_z: bool) -> Camera {
+        let mut camera = Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+            90.0,
+            1.0,
+            0.1,
+            100.0,
+        );
+        camera.reverse_z = reverse_z;
+        camera
+    }
+
+    fn cube(center: [f32; 3], half: f32) -> Aabb {
+        let center = Vector3::from(center);
+        Aabb::new(center - Vector3::new(half, half, half), center + Vector3::new(half, half, half))
+    }
+
+    #[test]
+    fn planes_match_the_camera_volume() {
+        for reverse_z in [false, true] {
+            let frustum = Frustum::from_camera(&camera(reverse_z));
+            let [left, right, bottom, top, near, far] = frustum.planes;
+
+            for plane in frustum.planes {
+                assert!((plane.normal.magnitude() - 1.0).abs() < 1e-5);
+            }
+            // Signed distances are in world units.
+            assert!((near.signed_distance(Vector3::new(0.0, 0.0, -1.1)) - 1.0).abs() < 1e-3);
+            assert!((far.signed_distance(Vector3::new(0.0, 0.0, -90.0)) - 10.0).abs() < 1e-2);
+            // With a 90 degree field of view, the side planes are at 45 degrees.
+            let s = std::f32::consts::FRAC_1_SQRT_2;
+            assert!((left.normal - Vector3::new(s, 0.0, -s)).magnitude() < 1e-5);
+            assert!((right.normal - Vector3::new(-s, 0.0, -s)).magnitude() < 1e-5);
+            assert!((bottom.normal - Vector3::new(0.0, s, -s)).magnitude() < 1e-5);
+            assert!((top.normal - Vector3::new(0.0, -s, -s)).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn points_are_classified() {
+        let frustum = Frustum::from_camera(&camera(false));
+        assert!(frustum.contains_point(Vector3::new(0.0, 0.0, -10.0)));
+        assert!(frustum.contains_point(Vector3::new(9.0, -9.0, -10.0)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(Vector3::new(11.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 11.0, -10.0)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(Vector3::new(0.0, 0.0, -101.0)));
+    }
+
+    #[test]
+    fn boxes_are_culled_only_when_fully_outside() {
+        let frustum = Frustum::from_camera(&camera(false));
+        // In front, behind, beside and beyond the far plane.
+        assert!(frustum.intersects(&cube([0.0, 0.0, -20.0], 1.0)));
+        assert!(!frustum.intersects(&cube([0.0, 0.0, 20.0], 1.0)));
+        assert!(!frustum.intersects(&cube([30.0, 0.0, -10.0], 1.0)));
+        assert!(!frustum.intersects(&cube([0.0, 0.0, -110.0], 5.0)));
+        // Straddling a side plane, the far plane, and containing the camera.
+        assert!(frustum.intersects(&cube([11.0, 0.0, -10.0], 2.0)));
+        assert!(frustum.intersects(&cube([0.0, 0.0, -102.0], 5.0)));
+        assert!(frustum.intersects(&cube([0.0, 0.0, 0.0], 8.0)));
+    }
+
+    #[test]
+    fn enclosing_box_covers_every_point() {
+        let aabb = Aabb::enclosing([[1.0, 5.0, -2.0], [-3.0, 2.0, 4.0], [0.0, 7.0, 0.0]]).unwrap();
+        assert_eq!(aabb, Aabb::new(Vector3::new(-3.0, 2.0, -2.0), Vector3::new(1.0, 7.0, 4.0)));
+        assert!(Aabb::enclosing([]).is_none());
+    }
+}
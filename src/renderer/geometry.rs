@@ -1,85 +1,342 @@
 //! # Geometry
 //! 
-//! This module defines the geometry of the objects that are rendered in the scene.
+//! This module defines the GPU-side geometry of the objects that are rendered in the scene.
+
+use std::ops::Range;
 
-use crate::renderer::vertex::Vertex;
 use wgpu::util::DeviceExt;
 
-/// Represents a cube with its vertex and index buffers.
-pub struct Cube {
+use cgmath::Vector3;
+
+use crate::meshing::ChunkMesh;
+use crate::renderer::frustum::Aabb;
+use crate::renderer::vertex::Vertex;
+use crate::world::{ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE, SECTION_COUNT, SECTION_SIZE};
+
+/// The colour of chunk border lines.
+const CHUNK_BORDER_COLOR: [f32; 3] = [1.0, 0.85, 0.1];
+
+/// The colour of the outline around the targeted block, which fades to
+/// `BREAKING_OUTLINE_COLOR` as the block breaks.
+const OUTLINE_COLOR: [f32; 3] = [0.05, 0.05, 0.05];
+const BREAKING_OUTLINE_COLOR: [f32; 3] = [0.95, 0.95, 0.95];
+
+/// How far the block outline stands off the block's faces, so it is not hidden by them.
+const OUTLINE_OFFSET: f32 = 0.002;
+
+/// The colours of the sun and the moon.
+const SUN_COLOR: [f32; 3] = [1.0, 0.92, 0.7];
+const MOON_COLOR: [f32; 3] = [0.8, 0.84, 0.92];
+
+/// Represents an indexed mesh with its vertex and index buffers.
+pub struct Mesh {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
 }
 
-impl Cube {
-    /// Creates a new cube.
-    pub fn new(device: &wgpu::Device) -> Self {
-        let vertices = vec![
-            // Front face
-            Vertex::new([-0.5, -0.5,  0.5], [1.0, 0.0, 0.0]),
-            Vertex::new([ 0.5, -0.5,  0.5], [0.0, 1.0, 0.0]),
-            Vertex::new([ 0.5,  0.5,  0.5], [0.0, 0.0, 1.0]),
-            Vertex::new([-0.5,  0.5,  0.5], [1.0, 1.0, 0.0]),
-            
-            // Back face
-            Vertex::new([-0.5, -0.5, -0.5], [1.0, 0.0, 1.0]),
-            Vertex::new([ 0.5, -0.5, -0.5], [0.0, 1.0, 1.0]),
-            Vertex::new([ 0.5,  0.5, -0.5], [0.5, 0.5, 0.5]),
-            Vertex::new([-0.5,  0.5, -0.5], [1.0, 0.5, 0.0]),
-        ];
-
-        let indices: Vec<u16> = vec![
-            // Front face
-            0, 1, 2, 2, 3, 0,
-            // Back face
-            4, 6, 5, 6, 4, 7,
-            // Left face
-            4, 0, 3, 3, 7, 4,
-            // Right face
-            1, 5, 6, 6, 2, 1,
-            // Top face
-            3, 2, 6, 6, 7, 3,
-            // Bottom face
-            4, 5, 1, 1, 0, 4,
-        ];
-        
+impl Mesh {
+    /// Uploads the given vertices and indices into a new mesh.
+    pub fn new<V: bytemuck::Pod>(device: &wgpu::Device, label: &str, vertices: &[V], indices: &[u32]) -> Self {
         let vertex_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some("Cube Vertex Buffer"),
-                contents: bytemuck::cast_slice(&vertices),
+                label: Some(&format!("{} Vertex Buffer", label)),
+                contents: bytemuck::cast_slice(vertices),
                 usage: wgpu::BufferUsages::VERTEX,
             }
         );
 
         let index_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
-                label: Some("Cube Index Buffer"),
-                contents: bytemuck::cast_slice(&indices),
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(indices),
                 usage: wgpu::BufferUsages::INDEX,
             }
         );
 
-        let num_indices = indices.len() as u32;
-        
         Self {
             vertex_buffer,
             index_buffer,
-            num_indices,
+            num_indices: indices.len() as u32,
         }
     }
-    
-    /// Renders the cube.
-    pub fn render<'a>(
-        &'a self,
-        render_pass: &mut wgpu::RenderPass<'a>, 
-        pipeline: &'a wgpu::RenderPipeline, 
-        bind_group: &'a wgpu::BindGroup
-    ) {
-        render_pass.set_pipeline(pipeline);
-        render_pass.set_bind_group(0, bind_group, &[]);
+
+    /// Draws the mesh with the pipeline and bind groups currently set on the render pass.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_range(render_pass, 0..self.num_indices);
+    }
+
+    /// Draws a range of the mesh's indices.
+    pub fn draw_range<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, indices: Range<u32>) {
+        if indices.is_empty() {
+            return;
+        }
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(indices, 0, 0..1);
+    }
+}
+
+/// The GPU-side geometry of a chunk. Opaque indices are stored first, followed by
+/// the translucent ones, and both are ordered by section.
+///
+/// Sections are selected with a mask that has bit `y` set for section `y`.
+pub struct ChunkGeometry {
+    mesh: Mesh,
+    opaque_indices: u32,
+    /// The end of each section's opaque indices.
+    section_ends: [u32; SECTION_COUNT],
+    /// The end of each section's translucent indices, relative to `opaque_indices`.
+    translucent_section_ends: [u32; SECTION_COUNT],
+    /// The box enclosing every vertex of the chunk, in world coordinates.
+    bounds: Aabb,
+}
+
+impl ChunkGeometry {
+    /// Uploads the mesh of the chunk at `pos`.
+    pub fn new(device: &wgpu::Device, pos: ChunkPos, mesh: &ChunkMesh) -> Self {
+        let indices = [mesh.indices.as_slice(), mesh.translucent_indices.as_slice()].concat();
+        let label = format!("Chunk ({}, {})", pos.x, pos.z);
+        let bounds = Aabb::enclosing(mesh.vertices.iter().map(|vertex| vertex.position)).unwrap_or_else(|| {
+            let (x, z) = pos.origin();
+            let min = Vector3::new(x as f32, 0.0, z as f32);
+            Aabb::new(min, min + Vector3::new(CHUNK_SIZE as f32, CHUNK_HEIGHT as f32, CHUNK_SIZE as f32))
+        });
+        Self {
+            mesh: Mesh::new(device, &label, &mesh.vertices, &indices),
+            opaque_indices: mesh.indices.len() as u32,
+            section_ends: mesh.section_ends,
+            translucent_section_ends: mesh.translucent_section_ends,
+            bounds,
+        }
     }
-}
\ No newline at end of file
+
+    /// Returns the box enclosing the chunk's geometry.
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    /// Returns the box enclosing the geometry of a section.
+    pub fn section_bounds(&self, y: usize) -> Aabb {
+        let (bottom, top) = ((y * SECTION_SIZE) as f32, ((y + 1) * SECTION_SIZE) as f32);
+        let mut bounds = self.bounds;
+        bounds.min.y = bounds.min.y.clamp(bottom, top);
+        bounds.max.y = bounds.max.y.clamp(bottom, top);
+        bounds
+    }
+
+    /// Returns `true` if a section has any faces.
+    pub fn has_section(&self, y: usize) -> bool {
+        !section_range(&self.section_ends, y..y + 1).is_empty()
+            || !section_range(&self.translucent_section_ends, y..y + 1).is_empty()
+    }
+
+    /// Returns the number of triangles in the given sections.
+    pub fn triangle_count(&self, sections: u8) -> u32 {
+        runs(sections)
+            .map(|run| section_range(&self.section_ends, run.clone()).len() + section_range(&self.translucent_section_ends, run).len())
+            .sum::<usize>() as u32
+            / 3
+    }
+
+    /// Returns `true` if the chunk has translucent faces.
+    pub fn has_translucent(&self) -> bool {
+        self.mesh.num_indices > self.opaque_indices
+    }
+
+    /// Draws the opaque faces of the given sections.
+    pub fn draw_opaque<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, sections: u8) {
+        for run in runs(sections) {
+            self.mesh.draw_range(render_pass, section_range(&self.section_ends, run));
+        }
+    }
+
+    /// Draws the translucent faces of the given sections.
+    pub fn draw_translucent<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, sections: u8) {
+        for run in runs(sections) {
+            let range = section_range(&self.translucent_section_ends, run);
+            self.mesh.draw_range(render_pass, self.opaque_indices + range.start..self.opaque_indices + range.end);
+        }
+    }
+}
+
+/// Returns the runs of consecutive sections in a mask, so neighbouring sections
+/// are drawn with a single call.
+fn runs(sections: u8) -> impl Iterator<Item = Range<usize>> {
+    let mut y = 0;
+    std::iter::from_fn(move || {
+        while y < SECTION_COUNT && sections & (1 << y) == 0 {
+            y += 1;
+        }
+        let start = y;
+        while y < SECTION_COUNT && sections & (1 << y) != 0 {
+            y += 1;
+        }
+        (start < y).then_some(start..y)
+    })
+}
+
+/// Returns the indices of a run of sections, given the end of each section.
+fn section_range(ends: &[u32; SECTION_COUNT], run: Range<usize>) -> Range<u32> {
+    let start = if run.start == 0 { 0 } else { ends[run.start - 1] };
+    start..ends[run.end - 1]
+}
+
+/// Builds line-list geometry outlining the box of a targeted block. `progress` is
+/// how far the block has been broken, from 0 to 1, and brightens the outline.
+pub fn block_outline_lines(bounds: &Aabb, progress: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let t = progress.clamp(0.0, 1.0);
+    let color = [0, 1, 2].map(|i| OUTLINE_COLOR[i] + (BREAKING_OUTLINE_COLOR[i] - OUTLINE_COLOR[i]) * t);
+    let offset = Vector3::new(OUTLINE_OFFSET, OUTLINE_OFFSET, OUTLINE_OFFSET);
+    let (min, max) = (bounds.min - offset, bounds.max + offset);
+
+    // The corners, with bit 0 selecting the maximum X, bit 1 Y and bit 2 Z.
+    let vertices = (0..8)
+        .map(|i| {
+            let pick = |bit: usize, min: f32, max: f32| if i & (1 << bit) != 0 { max } else { min };
+            Vertex::new([pick(0, min.x, max.x), pick(1, min.y, max.y), pick(2, min.z, max.z)], color)
+        })
+        .collect();
+    // Every edge joins two corners that differ in a single bit.
+    let mut indices = Vec::new();
+    for corner in 0..8u32 {
+        for bit in [1, 2, 4] {
+            if corner & bit == 0 {
+                indices.extend_from_slice(&[corner, corner | bit]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// Builds line-list geometry outlining the given chunks: a vertical line at each
+/// corner and a horizontal outline at every section boundary.
+pub fn chunk_border_lines(chunks: impl Iterator<Item = ChunkPos>) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut line = |from: [f32; 3], to: [f32; 3]| {
+        let base = vertices.len() as u32;
+        vertices.push(Vertex::new(from, CHUNK_BORDER_COLOR));
+        vertices.push(Vertex::new(to, CHUNK_BORDER_COLOR));
+        indices.extend_from_slice(&[base, base + 1]);
+    };
+
+    let (size, height) = (CHUNK_SIZE as f32, CHUNK_HEIGHT as f32);
+    for pos in chunks {
+        let (x, z) = pos.origin();
+        let (x, z) = (x as f32, z as f32);
+        let corners = [[x, z], [x + size, z], [x + size, z + size], [x, z + size]];
+
+        for [cx, cz] in corners {
+            line([cx, 0.0, cz], [cx, height, cz]);
+        }
+        for y in (0..=CHUNK_HEIGHT).step_by(SECTION_SIZE) {
+            let y = y as f32;
+            for i in 0..4 {
+                let ([ax, az], [bx, bz]) = (corners[i], corners[(i + 1) % 4]);
+                line([ax, y, az], [bx, y, bz]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Builds triangle-list geometry for the sky dome: a cube around the origin whose
+/// corners are the directions they are seen in. The sky shader fills it with the
+/// sky gradient around the camera.
+pub fn sky_dome() -> (Vec<Vertex>, Vec<u32>) {
+    // The corners, with bit 0 selecting +X, bit 1 +Y and bit 2 +Z.
+    let vertices = (0..8)
+        .map(|i| {
+            let pick = |bit: usize| if i & (1 << bit) != 0 { 1.0 } else { -1.0 };
+            Vertex::new([pick(0), pick(1), pick(2)], [0.0; 3])
+        })
+        .collect();
+    // Two triangles for each side, with the corners of the side where `bit` is `set`.
+    let mut indices = Vec::new();
+    for bit in [1u32, 2, 4] {
+        let (a, b) = match bit {
+            1 => (2, 4),
+            2 => (1, 4),
+            _ => (1, 2),
+        };
+        for set in [0, bit] {
+            indices.extend_from_slice(&[set, set | a, set | a | b, set | a | b, set | b, set]);
+        }
+    }
+    (vertices, indices)
+}
+
+/// Builds triangle-list geometry for the sun and moon quads. The sky shader places
+/// them around the camera; the position of each vertex holds its corner across the
+/// quad in X and Y, and its body in Z: 0 for the sun and 1 for the moon.
+pub fn celestial_quads() -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (body, color) in [(0.0, SUN_COLOR), (1.0, MOON_COLOR)] {
+        let base = vertices.len() as u32;
+        for [u, v] in [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]] {
+            vertices.push(Vertex::new([u, v, body], color));
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_sections_are_drawn_together() {
+        let runs = |sections: u8| runs(sections).collect::<Vec<_>>();
+        assert!(runs(0).is_empty());
+        assert_eq!(runs(0b1111_1111).first(), Some(&(0..8)));
+        assert_eq!(runs(0b1111_1111).len(), 1);
+        assert_eq!(runs(0b0110_1001), [0..1, 3..4, 5..7]);
+    }
+
+    #[test]
+    fn section_ranges_follow_the_previous_section() {
+        let ends = [0, 12, 12, 30, 42, 42, 42, 48];
+        assert_eq!(section_range(&ends, 0..1), 0..0);
+        assert_eq!(section_range(&ends, 1..2), 0..12);
+        assert_eq!(section_range(&ends, 2..3), 12..12);
+        assert_eq!(section_range(&ends, 1..5), 0..42);
+        assert_eq!(section_range(&ends, 7..8), 42..48);
+    }
+
+    #[test]
+    fn block_outlines_cover_the_twelve_edges() {
+        let bounds = Aabb::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(2.0, 2.5, 4.0));
+        let (vertices, indices) = block_outline_lines(&bounds, 0.0);
+        assert_eq!((vertices.len(), indices.len()), (8, 24));
+
+        for edge in indices.chunks(2) {
+            let (a, b) = (vertices[edge[0] as usize].position, vertices[edge[1] as usize].position);
+            assert_eq!((0..3).filter(|&axis| a[axis] != b[axis]).count(), 1);
+        }
+        let top = vertices.iter().map(|vertex| vertex.position[1]).fold(f32::MIN, f32::max);
+        assert!(top > 2.5 && top < 2.51);
+
+        let (broken, _) = block_outline_lines(&bounds, 1.0);
+        assert!((0..3).all(|i| (broken[0].color[i] - BREAKING_OUTLINE_COLOR[i]).abs() < 1e-6));
+    }
+
+    #[test]
+    fn the_sky_dome_encloses_the_camera() {
+        let (vertices, indices) = sky_dome();
+        assert_eq!(indices.len(), 36);
+
+        // Each pair of triangles covers one side of the cube, and every side is covered.
+        let mut sides = Vec::new();
+        for quad in indices.chunks(6) {
+            let corners: Vec<[f32; 3]> = quad.iter().map(|&i| vertices[i as usize].position).collect();
+            let axis = (0..3).find(|&axis| corners.iter().all(|c| c[axis] == corners[0][axis])).unwrap();
+            sides.push((axis, corners[0][axis] as i32));
+        }
+        sides.sort();
+        assert_eq!(sides, [(0, -1), (0, 1), (1, -1), (1, 1), (2, -1), (2, 1)]);
+    }
+}
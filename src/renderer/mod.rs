@@ -1,58 +1,124 @@
 //! # Renderer Module
 //! 
 //! This module is the core of the rendering engine. It handles `wgpu` initialization,
-//! manages the render surface, creates the render pipelines, and draws the scene
-//! every frame.
+//! manages the render target (a window surface, or an offscreen texture when
+//! running headless), creates the render pipelines, and draws the sky and the
+//! scene every frame.
 
 mod vertex;
 mod uniforms;
 mod geometry;
 mod camera;
-
-pub use vertex::Vertex;
+mod depth;
+mod atlas;
+mod shaders;
+mod pipeline;
+mod frustum;
+mod offscreen;
+mod screenshot;
+mod sky;
+
+pub use vertex::{TexturedVertex, Vertex};
+pub use atlas::{AtlasBuilder, AtlasRegion, AtlasTexture, TextureAtlas};
+pub use pipeline::{PipelineDescriptor, PipelineFactory, PipelineKind, Pipelines};
 pub use uniforms::Uniforms;
-pub use geometry::Cube;
+pub use geometry::{ChunkGeometry, Mesh};
 pub use camera::Camera;
-
+pub use depth::DepthTexture;
+pub use frustum::{Aabb, CullingStats, Frustum};
+#[cfg(test)]
+pub use offscreen::OffscreenTarget;
+pub use screenshot::SCREENSHOT_DIR;
+pub use sky::Sky;
+
+use std::collections::HashMap;
 use std::sync::Arc;
+#[cfg(test)]
+use image::RgbaImage;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 use log::info;
 
 use crate::debug::gui::GuiManager;
 use crate::debug::overlay::DebugOverlay;
+use crate::meshing::ChunkMesh;
 use crate::monitoring::SystemMonitor;
+use crate::occlusion::{self, ChunkVisibility, SectionPos};
+use crate::renderer::screenshot::Screenshots;
+use crate::world::{ChunkPos, CHUNK_SIZE, SECTION_COUNT, SECTION_SIZE};
 
 /// Manages all rendering-related aspects.
 pub struct Renderer {
-    surface: wgpu::Surface<'static>,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    render_pipeline: wgpu::RenderPipeline,
+    depth_texture: DepthTexture,
+
+    // Pipelines
+    pipelines: Pipelines,
+    textured_shader: wgpu::ShaderModule,
+    basic_shader: wgpu::ShaderModule,
+    sky_shader: wgpu::ShaderModule,
+    supports_wireframe: bool,
     
     // Adapter information
     pub gpu_name: String,
 
     // Separate components
     camera: Camera,
-    cube: Cube,
+    chunk_meshes: HashMap<ChunkPos, ChunkGeometry>,
+    /// The visibility graphs of every chunk that was meshed, including empty ones.
+    chunk_visibility: HashMap<ChunkPos, ChunkVisibility>,
+    chunk_borders: Option<Mesh>,
+    /// The box and break progress of the targeted block, and the outline drawn around it.
+    block_outline: Option<(Aabb, f32, Mesh)>,
+    /// The number of triangles drawn in the latest frame.
+    drawn_triangles: u32,
+    /// The sky at the current time of day, and the geometry it is drawn with.
+    sky: Sky,
+    sky_dome: Mesh,
+    celestial_quads: Mesh,
     
     // Buffers and resources
     uniform_buffer: wgpu::Buffer,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
     uniform_bind_group: wgpu::BindGroup,
+    atlas_texture: AtlasTexture,
     
-    // GUI
-    pub gui_manager: GuiManager,
+    // GUI, drawn only into windows
+    gui_manager: Option<GuiManager>,
+
+    /// Requested screenshots, and the ones being read back.
+    screenshots: Screenshots,
+}
 
-    // State
-    rotation: f32,
+/// Where the renderer draws frames.
+enum RenderTarget {
+    /// The surface of a window, presented on screen.
+    Surface {
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    /// A texture that frames are read back from, by tests.
+    #[cfg(test)]
+    Offscreen(OffscreenTarget),
+}
+
+impl RenderTarget {
+    /// Returns the format of the textures drawn into.
+    fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            RenderTarget::Surface { config, .. } => config.format,
+            #[cfg(test)]
+            RenderTarget::Offscreen(_) => OffscreenTarget::FORMAT,
+        }
+    }
 }
 
 impl Renderer {
-    /// Creates a new `Renderer`.
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    /// Creates a new `Renderer` that draws into a window.
+    pub async fn new(window: Arc<Window>, atlas: &TextureAtlas) -> anyhow::Result<Self> {
         let size = window.inner_size();
         
         // Initialize wgpu
@@ -70,19 +136,7 @@ impl Renderer {
                 force_fallback_adapter: false,
             },
         ).await.ok_or_else(|| anyhow::anyhow!("Failed to get adapter"))?;
-
-        let gpu_name = adapter.get_info().name;
-        info!("GPU: {:?}", gpu_name);
-        info!("Backend: {:?}", adapter.get_info().backend);
-
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                label: None,
-            },
-            None,
-        ).await?;
+        let (device, queue, supports_wireframe) = request_device(&adapter).await?;
 
         // Configure the surface
         let surface_caps = surface.get_capabilities(&adapter);
@@ -91,8 +145,9 @@ impl Renderer {
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
             
+        // Frames are copied out of the surface for screenshots, where supported.
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -107,26 +162,70 @@ impl Renderer {
         // Create the GUI
         let gui_manager = GuiManager::new(&window, &device, config.format);
 
-        // Create the shader
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+        let target = RenderTarget::Surface { surface, config };
+        Ok(Self::with_target(device, queue, &adapter, supports_wireframe, target, size, Some(gui_manager), atlas))
+    }
+
+    /// Creates a new `Renderer` that draws into an offscreen texture of the given
+    /// size on the fallback adapter, a software rasterizer such as lavapipe or
+    /// llvmpipe, so it needs neither a GPU nor a display. Frames are read back with
+    /// `render_to_image`.
+    #[cfg(test)]
+    pub async fn headless(width: u32, height: u32, atlas: &TextureAtlas) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
         });
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::None,
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            },
+        ).await.ok_or_else(|| anyhow::anyhow!("No fallback adapter is available"))?;
+        let (device, queue, supports_wireframe) = request_device(&adapter).await?;
+
+        let size = winit::dpi::PhysicalSize::new(width.max(1), height.max(1));
+        let target = RenderTarget::Offscreen(OffscreenTarget::new(&device, size.width, size.height));
+        Ok(Self::with_target(device, queue, &adapter, supports_wireframe, target, size, None, atlas))
+    }
+
+    /// Creates the resources shared by every render target.
+    #[allow(clippy::too_many_arguments)]
+    fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        adapter: &wgpu::Adapter,
+        supports_wireframe: bool,
+        target: RenderTarget,
+        size: winit::dpi::PhysicalSize<u32>,
+        gui_manager: Option<GuiManager>,
+        atlas: &TextureAtlas,
+    ) -> Self {
+        let gpu_name = adapter.get_info().name;
+        info!("GPU: {:?}", gpu_name);
+        info!("Backend: {:?}", adapter.get_info().backend);
+
+        // Create the shaders and upload the block texture atlas
+        let textured_shader = shaders::create_textured_shader(&device);
+        let basic_shader = shaders::create_basic_shader(&device);
+        let sky_shader = shaders::create_sky_shader(&device);
+        let atlas_texture = atlas.upload(&device, &queue);
 
         // Create the camera
-        let camera = Camera::new(
-            cgmath::Point3::new(0.0, 0.0, 3.0),
-            cgmath::Point3::new(0.0, 0.0, 0.0),
+        let mut camera = Camera::new(
+            cgmath::Point3::new(-72.0, 96.0, -72.0),
+            cgmath::Point3::new(0.0, 48.0, 0.0),
             cgmath::Vector3::unit_y(),
             45.0,
             size.width as f32 / size.height as f32,
             0.1,
-            100.0,
+            500.0,
         );
 
         // Create the uniforms
         let mut uniforms = Uniforms::new();
-        uniforms.update_from_camera(&camera, 0.0);
+        uniforms.update_from_camera(&camera);
 
         let uniform_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -140,7 +239,7 @@ impl Renderer {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -163,94 +262,292 @@ impl Renderer {
             label: Some("uniform_bind_group"),
         });
 
-        // Create the render pipeline
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&uniform_bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
+        // Create the sky geometry
+        let (vertices, indices) = geometry::sky_dome();
+        let sky_dome = Mesh::new(&device, "Sky Dome", &vertices, &indices);
+        let (vertices, indices) = geometry::celestial_quads();
+        let celestial_quads = Mesh::new(&device, "Celestial Quads", &vertices, &indices);
 
-        // Create the geometry
-        let cube = Cube::new(&device);
+        // Create the depth buffer
+        let depth_texture = DepthTexture::new(&device, size.width, size.height, true);
+        camera.reverse_z = depth_texture.reverse_z();
 
-        Ok(Self {
-            surface,
+        let mut renderer = Self {
+            target,
             device,
             queue,
-            config,
             size,
-            render_pipeline,
+            depth_texture,
+            pipelines: Pipelines::default(),
+            textured_shader,
+            basic_shader,
+            sky_shader,
+            supports_wireframe,
             gpu_name,
             camera,
-            cube,
+            chunk_meshes: HashMap::new(),
+            chunk_visibility: HashMap::new(),
+            chunk_borders: None,
+            block_outline: None,
+            drawn_triangles: 0,
+            sky: Sky::default(),
+            sky_dome,
+            celestial_quads,
             uniform_buffer,
+            uniform_bind_group_layout,
             uniform_bind_group,
+            atlas_texture,
             gui_manager,
-            rotation: 0.0,
-        })
+            screenshots: Screenshots::new(SCREENSHOT_DIR),
+        };
+
+        // Create the render pipelines
+        renderer.pipelines = renderer.create_pipelines();
+
+        renderer
+    }
+
+    /// Creates every render pipeline for the current surface format and depth buffer.
+    fn create_pipelines(&self) -> Pipelines {
+        let factory = PipelineFactory::new(&self.device, self.target.format(), &self.depth_texture);
+        let block_layouts = [&self.uniform_bind_group_layout, self.atlas_texture.bind_group_layout()];
+        let line_layouts = [&self.uniform_bind_group_layout];
+        let block = || PipelineDescriptor::new("Opaque Pipeline", &self.textured_shader, TexturedVertex::desc(), &block_layouts);
+
+        let mut pipelines = Pipelines::default();
+        pipelines.insert(PipelineKind::Opaque, factory.create(&block()));
+        pipelines.insert(PipelineKind::Transparent, factory.create(&PipelineDescriptor {
+            label: "Transparent Pipeline",
+            fragment_entry_point: "fs_transparent",
+            cull_mode: None,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            depth_write: false,
+            ..block()
+        }));
+        if self.supports_wireframe {
+            pipelines.insert(PipelineKind::Wireframe, factory.create(&PipelineDescriptor {
+                label: "Wireframe Pipeline",
+                polygon_mode: wgpu::PolygonMode::Line,
+                cull_mode: None,
+                ..block()
+            }));
+        }
+        pipelines.insert(PipelineKind::Line, factory.create(&PipelineDescriptor {
+            topology: wgpu::PrimitiveTopology::LineList,
+            cull_mode: None,
+            ..PipelineDescriptor::new("Line Pipeline", &self.basic_shader, Vertex::desc(), &line_layouts)
+        }));
+
+        // The sky is drawn first, around the camera, and never hides anything.
+        let sky = || PipelineDescriptor {
+            cull_mode: None,
+            depth_write: false,
+            depth_test: false,
+            ..PipelineDescriptor::new("Sky Pipeline", &self.sky_shader, Vertex::desc(), &line_layouts)
+        };
+        pipelines.insert(PipelineKind::Sky, factory.create(&PipelineDescriptor {
+            vertex_entry_point: "vs_sky",
+            fragment_entry_point: "fs_sky",
+            ..sky()
+        }));
+        pipelines.insert(PipelineKind::Celestial, factory.create(&PipelineDescriptor {
+            label: "Celestial Pipeline",
+            vertex_entry_point: "vs_celestial",
+            fragment_entry_point: "fs_celestial",
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            ..sky()
+        }));
+
+        pipelines
     }
 
-    /// Resizes the render surface when the window size changes.
+    /// Resizes the render target, e.g. when the window size changes.
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                RenderTarget::Surface { surface, config } => {
+                    config.width = new_size.width;
+                    config.height = new_size.height;
+                    surface.configure(&self.device, config);
+                }
+                #[cfg(test)]
+                RenderTarget::Offscreen(target) => {
+                    *target = OffscreenTarget::new(&self.device, new_size.width, new_size.height);
+                }
+            }
+            self.depth_texture = DepthTexture::new(&self.device, new_size.width, new_size.height, self.depth_texture.reverse_z());
             
             // Update the camera's aspect ratio.
             self.camera.set_aspect_ratio(new_size.width as f32 / new_size.height as f32);
         }
     }
 
-    /// Returns the current size of the render surface.
+    /// Returns the current size of the render target.
     pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
         self.size
     }
 
+    /// Returns `true` if depth is reversed (1 at the near plane, 0 at the far plane).
+    pub fn reverse_z(&self) -> bool {
+        self.depth_texture.reverse_z()
+    }
+
+    /// Switches between standard and reverse-Z depth, rebuilding the depth buffer
+    /// and the pipelines that depend on it.
+    pub fn set_reverse_z(&mut self, reverse_z: bool) {
+        self.depth_texture = DepthTexture::new(&self.device, self.size.width, self.size.height, reverse_z);
+        self.pipelines = self.create_pipelines();
+        self.camera.reverse_z = reverse_z;
+    }
+
+    /// Passes a window event to the GUI. Returns `true` if the GUI consumed it, e.g.
+    /// because a widget was clicked.
+    pub fn handle_gui_event(&mut self, window: &Window, event: &winit::event::WindowEvent) -> bool {
+        self.gui_manager.as_mut().is_some_and(|gui_manager| gui_manager.handle_event(window, event))
+    }
+
+    /// Returns the camera the scene is drawn from.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Returns the camera the scene is drawn from, for moving it.
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Sets the time of day the sky is drawn at, from 0 at sunrise to 1 at the next one.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.sky = Sky::new(time_of_day);
+    }
+
+    /// Returns `true` if the device supports drawing block geometry as wireframe.
+    pub fn supports_wireframe(&self) -> bool {
+        self.supports_wireframe
+    }
+
+    /// Uploads the mesh of a chunk, replacing any mesh previously uploaded for it.
+    pub fn upload_chunk_mesh(&mut self, pos: ChunkPos, mesh: &ChunkMesh) {
+        self.chunk_borders = None;
+        self.chunk_visibility.insert(pos, mesh.visibility);
+        if mesh.is_empty() {
+            self.chunk_meshes.remove(&pos);
+            return;
+        }
+
+        self.chunk_meshes.insert(pos, ChunkGeometry::new(&self.device, pos, mesh));
+    }
+
+    /// Removes the mesh of a chunk, if one was uploaded.
+    pub fn remove_chunk_mesh(&mut self, pos: ChunkPos) {
+        self.chunk_visibility.remove(&pos);
+        if self.chunk_meshes.remove(&pos).is_some() {
+            self.chunk_borders = None;
+        }
+    }
+
+    /// Outlines the box of the targeted block, brightening the outline as the block
+    /// breaks by `progress`, from 0 to 1. `None` removes the outline.
+    pub fn set_block_outline(&mut self, outline: Option<(Aabb, f32)>) {
+        let Some((bounds, progress)) = outline else {
+            self.block_outline = None;
+            return;
+        };
+        // The outline changes in ten steps as the block breaks, so it is not rebuilt
+        // every frame.
+        let progress = (progress.clamp(0.0, 1.0) * 10.0).floor() / 10.0;
+        if self.block_outline.as_ref().is_some_and(|(b, p, _)| (*b, *p) == (bounds, progress)) {
+            return;
+        }
+        let (vertices, indices) = geometry::block_outline_lines(&bounds, progress);
+        self.block_outline = Some((bounds, progress, Mesh::new(&self.device, "Block Outline", &vertices, &indices)));
+    }
+
+    /// Returns the number of triangles drawn in the latest frame.
+    pub fn triangle_count(&self) -> u32 {
+        self.drawn_triangles
+    }
+
     /// Renders a single frame.
-    pub fn render(&mut self, window: &Window, debug_overlay: &DebugOverlay, system_monitor: &SystemMonitor) -> Result<(), wgpu::SurfaceError> {
-        // Update the cube's rotation.
-        self.rotation += 0.01;
+    pub fn render(&mut self, window: &Window, debug_overlay: &mut DebugOverlay, system_monitor: &SystemMonitor) -> Result<(), wgpu::SurfaceError> {
+        let (output, view) = match &self.target {
+            RenderTarget::Surface { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), view)
+            }
+            #[cfg(test)]
+            RenderTarget::Offscreen(target) => (None, target.create_view()),
+        };
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+        self.draw_scene(&mut encoder, &view, debug_overlay);
+
+        // Capture the frame before or after the GUI is drawn over it, if requested.
+        let screenshot = self.screenshots.take_request();
+        if screenshot == Some(false) {
+            self.screenshots.capture(&self.device, &mut encoder, frame_texture(&self.target, output.as_ref()));
+        }
+
+        // Render the GUI.
+        if let Some(gui_manager) = &mut self.gui_manager {
+            gui_manager.render(window, &self.device, &self.queue, &mut encoder, &view, system_monitor, debug_overlay, &self.gpu_name);
+        }
+
+        if screenshot == Some(true) {
+            self.screenshots.capture(&self.device, &mut encoder, frame_texture(&self.target, output.as_ref()));
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(output) = output {
+            output.present();
+        }
+        self.screenshots.update(&self.device);
+
+        Ok(())
+    }
+
+    /// Saves the next frame as a PNG file in the screenshot directory, with or
+    /// without the GUI. The file is written in the background.
+    pub fn request_screenshot(&mut self, include_gui: bool) {
+        self.screenshots.request(include_gui);
+    }
+
+    /// Renders a single frame of the scene, without the GUI, and reads it back.
+    /// Only renderers created with `headless` can render to an image.
+    #[cfg(test)]
+    pub fn render_to_image(&mut self, debug_overlay: &mut DebugOverlay) -> anyhow::Result<RgbaImage> {
+        let no_target = || anyhow::anyhow!("The renderer has no offscreen target");
+        let view = self.offscreen_target().ok_or_else(no_target)?.create_view();
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        });
+        self.draw_scene(&mut encoder, &view, debug_overlay);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        self.offscreen_target().ok_or_else(no_target)?.read_rgba(&self.device, &self.queue)
+    }
+
+    /// Returns the offscreen texture frames are drawn into, if the renderer is headless.
+    #[cfg(test)]
+    fn offscreen_target(&self) -> Option<&OffscreenTarget> {
+        match &self.target {
+            RenderTarget::Offscreen(target) => Some(target),
+            RenderTarget::Surface { .. } => None,
+        }
+    }
 
+    /// Records the drawing of the scene into `view`.
+    fn draw_scene(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, debug_overlay: &mut DebugOverlay) {
         // Update the uniforms.
         let mut uniforms = Uniforms::new();
-        uniforms.update_from_camera(&self.camera, self.rotation);
+        uniforms.update_from_camera(&self.camera);
+        uniforms.update_from_sky(&self.sky);
+        uniforms.ambient_occlusion = if debug_overlay.ambient_occlusion { 1.0 } else { 0.0 };
 
         self.queue.write_buffer(
             &self.uniform_buffer,
@@ -258,44 +555,410 @@ impl Renderer {
             bytemuck::cast_slice(&[uniforms]),
         );
 
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
-        
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        });
+        // Rebuild the chunk border lines if the set of chunks changed.
+        if debug_overlay.chunk_borders && self.chunk_borders.is_none() {
+            let (vertices, indices) = geometry::chunk_border_lines(self.chunk_meshes.keys().copied());
+            self.chunk_borders = Some(Mesh::new(&self.device, "Chunk Borders", &vertices, &indices));
+        }
+
+        // Skip the sections outside of the camera's view or hidden behind terrain.
+        let frustum = debug_overlay.frustum_culling.then(|| Frustum::from_camera(&self.camera));
+        let in_view = |bounds: &Aabb| frustum.is_none_or(|frustum| frustum.intersects(bounds));
+        let eye = self.camera.position;
+        let reachable = debug_overlay.cave_culling
+            .then(|| occlusion::visible_sections([eye.x, eye.y, eye.z], &self.chunk_visibility, |section| in_view(&section_bounds(section))))
+            .flatten();
+
+        let mut culling = CullingStats::default();
+        let mut visible = Vec::new();
+        for (pos, mesh) in &self.chunk_meshes {
+            let mut sections = 0u8;
+            if !in_view(mesh.bounds()) {
+                culling.culled += (0..SECTION_COUNT).filter(|&y| mesh.has_section(y)).count();
+                continue;
+            }
+            for y in (0..SECTION_COUNT).filter(|&y| mesh.has_section(y)) {
+                if !in_view(&mesh.section_bounds(y)) {
+                    culling.culled += 1;
+                } else if reachable.as_ref().is_some_and(|reachable| !reachable.contains(SectionPos::new(pos.x, y as i32, pos.z))) {
+                    culling.occluded += 1;
+                } else {
+                    culling.visible += 1;
+                    sections |= 1 << y;
+                }
+            }
+            if sections != 0 {
+                visible.push((pos, mesh, sections));
+            }
+        }
+        debug_overlay.culling = culling;
+        self.drawn_triangles = visible.iter().map(|(_, mesh, sections)| mesh.triangle_count(*sections)).sum();
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.sky.clear_color()),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(self.depth_texture.attachment()),
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
 
-            // Render the cube.
-            self.cube.render(&mut render_pass, &self.render_pipeline, &self.uniform_bind_group);
+            // Render the sky behind everything, then the sun and moon over it.
+            for (kind, mesh) in [(PipelineKind::Sky, &self.sky_dome), (PipelineKind::Celestial, &self.celestial_quads)] {
+                if let Some(pipeline) = self.pipelines.get(kind) {
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                    mesh.draw(&mut render_pass);
+                }
+            }
+
+            // Render the chunks.
+            let kind = if debug_overlay.wireframe && self.supports_wireframe {
+                PipelineKind::Wireframe
+            } else {
+                PipelineKind::Opaque
+            };
+            if let Some(pipeline) = self.pipelines.get(kind) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                render_pass.set_bind_group(1, self.atlas_texture.bind_group(), &[]);
+                for &(_, mesh, sections) in &visible {
+                    mesh.draw_opaque(&mut render_pass, sections);
+                }
+            }
+
+            // Render the translucent faces back to front, after all opaque geometry.
+            if kind == PipelineKind::Opaque {
+                if let Some(pipeline) = self.pipelines.get(PipelineKind::Transparent) {
+                    let eye = self.camera.position;
+                    let mut translucent: Vec<_> = visible.iter().filter(|(_, mesh, _)| mesh.has_translucent()).collect();
+                    translucent.sort_by(|(a, _, _), (b, _, _)| b.center_distance_sq(eye.x, eye.z).total_cmp(&a.center_distance_sq(eye.x, eye.z)));
+
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                    render_pass.set_bind_group(1, self.atlas_texture.bind_group(), &[]);
+                    for &(_, mesh, sections) in translucent {
+                        mesh.draw_translucent(&mut render_pass, sections);
+                    }
+                }
+            }
+
+            // Render the debug lines.
+            if let (true, Some(lines), Some(pipeline)) = (debug_overlay.chunk_borders, &self.chunk_borders, self.pipelines.get(PipelineKind::Line)) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                lines.draw(&mut render_pass);
+            }
+
+            // Outline the targeted block.
+            if let (Some((_, _, outline)), Some(pipeline)) = (&self.block_outline, self.pipelines.get(PipelineKind::Line)) {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+                outline.draw(&mut render_pass);
+            }
         }
+    }
+}
 
-        // Render the GUI.
-        self.gui_manager.render(window, &self.device, &self.queue, &mut encoder, &view, system_monitor, debug_overlay, &self.gpu_name);
+/// Returns the box enclosing a whole section.
+fn section_bounds(section: SectionPos) -> Aabb {
+    let [x, y, z] = section.origin().map(|v| v as f32);
+    let min = cgmath::Vector3::new(x, y, z);
+    Aabb::new(min, min + cgmath::Vector3::new(CHUNK_SIZE as f32, SECTION_SIZE as f32, CHUNK_SIZE as f32))
+}
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+/// Returns the texture of the frame being drawn: the surface texture of a window,
+/// or the offscreen texture.
+fn frame_texture<'a>(target: &'a RenderTarget, output: Option<&'a wgpu::SurfaceTexture>) -> &'a wgpu::Texture {
+    match target {
+        RenderTarget::Surface { .. } => &output.expect("Surface frames have an output texture").texture,
+        #[cfg(test)]
+        RenderTarget::Offscreen(target) => target.texture(),
+    }
+}
 
-        Ok(())
+/// Requests a device from the adapter, with wireframe rendering when it is
+/// supported. Returns the device, its queue, and whether wireframe is supported.
+async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue, bool)> {
+    // Wireframe rendering is optional, so only request it when available.
+    let supports_wireframe = adapter.features().contains(wgpu::Features::POLYGON_MODE_LINE);
+    let required_features = if supports_wireframe {
+        wgpu::Features::POLYGON_MODE_LINE
+    } else {
+        wgpu::Features::empty()
+    };
+
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features,
+            required_limits: wgpu::Limits::default(),
+            label: None,
+        },
+        None,
+    ).await?;
+    Ok((device, queue, supports_wireframe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use crate::lighting;
+    use crate::meshing::{self, BlockTextureMap, MeshMode};
+    use crate::world::{BlockId, BlockRegistry, World, AIR};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const DIRT: BlockId = 2;
+    const GRASS: BlockId = 3;
+    const WATER: BlockId = 5;
+    const LEAVES: BlockId = 7;
+    const LOG: BlockId = 12;
+
+    /// The size of the rendered test frames.
+    const WIDTH: u32 = 160;
+    const HEIGHT: u32 = 120;
+
+    /// How much a channel of a pixel may differ from the golden image, since
+    /// rasterizers differ slightly in rounding.
+    const CHANNEL_TOLERANCE: u8 = 8;
+
+    /// The share of pixels that may differ by more than the tolerance.
+    const MAX_MISMATCHED: f64 = 0.005;
+
+    fn atlas() -> TextureAtlas {
+        crate::renderer::AtlasBuilder::new(4).add_dir("assets/textures/blocks").unwrap().build().unwrap()
+    }
+
+    /// Creates a headless renderer, or returns `None` if `SKIP_RENDER_TESTS` is set,
+    /// in which case rendering tests are skipped. Panics if this machine has no
+    /// fallback adapter.
+    fn renderer(atlas: &TextureAtlas) -> Option<Renderer> {
+        if std::env::var_os("SKIP_RENDER_TESTS").is_some() {
+            eprintln!("Skipping rendering test: SKIP_RENDER_TESTS is set");
+            return None;
+        }
+        match futures::executor::block_on(Renderer::headless(WIDTH, HEIGHT, atlas)) {
+            Ok(renderer) => Some(renderer),
+            Err(e) => panic!("{}; install a software adapter such as lavapipe, or set SKIP_RENDER_TESTS=1 to skip rendering tests", e),
+        }
+    }
+
+    /// Builds a small lit scene: a grass-topped island with a tree and a pond.
+    fn scene(registry: &BlockRegistry) -> World {
+        let mut world = World::new();
+        for z in 0..16 {
+            for x in 0..16 {
+                world.set_block(x, 0, z, STONE);
+                world.set_block(x, 1, z, DIRT);
+                world.set_block(x, 2, z, GRASS);
+            }
+        }
+        for z in 9..13 {
+            for x in 3..7 {
+                world.set_block(x, 2, z, WATER);
+            }
+        }
+        for y in 3..7 {
+            world.set_block(10, y, 6, LOG);
+        }
+        for y in 6..9 {
+            for z in 4..9 {
+                for x in 8..13 {
+                    if world.get_block(x, y, z) == AIR {
+                        world.set_block(x, y, z, LEAVES);
+                    }
+                }
+            }
+        }
+
+        let mut lit = World::new();
+        for chunk in world.chunks() {
+            let mut chunk = chunk.clone();
+            lighting::light_chunk(&mut chunk, registry);
+            lit.insert_chunk(chunk);
+        }
+        lit
+    }
+
+    /// Returns the path of a golden image.
+    fn golden_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+    }
+
+    /// Compares a frame with the golden image of the same name. The golden image is
+    /// written instead if `UPDATE_GOLDEN` is set.
+    fn assert_matches_golden(name: &str, frame: &RgbaImage) {
+        let path = golden_path(name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            frame.save(&path).unwrap();
+            eprintln!("Wrote golden image {}", path.display());
+            return;
+        }
+
+        assert!(path.exists(), "The golden image {} is missing; run the tests with UPDATE_GOLDEN=1 to create it", path.display());
+        let golden = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(golden.dimensions(), frame.dimensions(), "{} has the wrong size", name);
+        let mismatched = golden
+            .pixels()
+            .zip(frame.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0).any(|(&a, b)| a.abs_diff(b) > CHANNEL_TOLERANCE))
+            .count();
+        let share = mismatched as f64 / (WIDTH * HEIGHT) as f64;
+        if share > MAX_MISMATCHED {
+            let actual = path.with_extension("actual.png");
+            frame.save(&actual).unwrap();
+            panic!("{:.2}% of the pixels differ from {}; the frame was saved to {}", share * 100.0, path.display(), actual.display());
+        }
+    }
+
+    /// Points the camera of the renderer from `eye` towards `target`.
+    fn look_at(renderer: &mut Renderer, eye: [f32; 3], target: [f32; 3], up: cgmath::Vector3<f32>) {
+        let reverse_z = renderer.reverse_z();
+        *renderer.camera_mut() = Camera::new(eye.into(), target.into(), up, 45.0, WIDTH as f32 / HEIGHT as f32, 0.1, 100.0);
+        renderer.camera_mut().reverse_z = reverse_z;
+    }
+
+    /// Returns the average brightness of a frame, from 0 to 255.
+    fn mean_brightness(frame: &RgbaImage) -> f64 {
+        let sum: u64 = frame.pixels().map(|pixel| pixel.0[..3].iter().map(|&c| c as u64).sum::<u64>()).sum();
+        sum as f64 / (frame.pixels().len() * 3) as f64
+    }
+
+    #[test]
+    fn empty_scenes_show_the_sky_gradient() {
+        let atlas = atlas();
+        let Some(mut renderer) = renderer(&atlas) else {
+            return;
+        };
+        look_at(&mut renderer, [0.0, 64.0, 0.0], [0.0, 64.0, 10.0], cgmath::Vector3::unit_y());
+        let frame = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        assert_eq!(frame.dimensions(), (WIDTH, HEIGHT));
+
+        // The sky is a deeper blue above the horizon than at it.
+        let (top, horizon) = (frame.get_pixel(WIDTH / 2, 0), frame.get_pixel(WIDTH / 2, HEIGHT / 2));
+        let blueness = |pixel: &image::Rgba<u8>| pixel[2] as i32 - pixel[0] as i32;
+        assert!(blueness(top) > blueness(horizon), "{:?} {:?}", top, horizon);
+        assert!(top[2] > top[1] && top[1] > top[0] && top[3] == 255, "{:?}", top);
+
+        // It glows at sunset, and is dark at night.
+        renderer.set_time_of_day(0.5);
+        let sunset = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        let glow = sunset.get_pixel(WIDTH / 2, HEIGHT / 2);
+        assert!(glow[0] > glow[2], "{:?}", glow);
+        renderer.set_time_of_day(0.75);
+        let night = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        assert!(mean_brightness(&night) < 0.25 * mean_brightness(&frame));
+    }
+
+    #[test]
+    fn the_sun_and_moon_cross_the_sky() {
+        let atlas = atlas();
+        let Some(mut renderer) = renderer(&atlas) else {
+            return;
+        };
+        // Look straight up, where the sun is at noon and the moon at midnight.
+        look_at(&mut renderer, [0.0, 64.0, 0.0], [0.0, 74.0, 0.0], cgmath::Vector3::unit_z());
+        let noon = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        let sun = noon.get_pixel(WIDTH / 2, HEIGHT / 2);
+        assert!(sun[0] > 240 && sun[1] > 220 && sun[0] > sun[2], "{:?}", sun);
+
+        renderer.set_time_of_day(0.75);
+        let midnight = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        let (moon, sky) = (midnight.get_pixel(WIDTH / 2, HEIGHT / 2), midnight.get_pixel(0, 0));
+        assert!(moon[2] > moon[0] && moon[2] > 200, "{:?}", moon);
+        assert!(sky[2] < 60, "{:?}", sky);
+
+        // In the morning both are out of view.
+        renderer.set_time_of_day(0.1);
+        let morning = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        let center = morning.get_pixel(WIDTH / 2, HEIGHT / 2);
+        assert!(center[2] > center[0] && center[0] < 150, "{:?}", center);
+    }
+
+    #[test]
+    fn screenshots_are_saved_in_the_background() {
+        let atlas = atlas();
+        let Some(mut renderer) = renderer(&atlas) else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("screenshots-{}", std::process::id()));
+        renderer.screenshots = Screenshots::new(&dir);
+
+        // Capture a frame the way `render` does, which needs a window.
+        renderer.request_screenshot(false);
+        let view = renderer.offscreen_target().unwrap().create_view();
+        let mut encoder = renderer.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.draw_scene(&mut encoder, &view, &mut DebugOverlay::new());
+        assert_eq!(renderer.screenshots.take_request(), Some(false));
+        renderer.screenshots.capture(&renderer.device, &mut encoder, frame_texture(&renderer.target, None));
+        renderer.queue.submit(std::iter::once(encoder.finish()));
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let path = loop {
+            renderer.screenshots.update(&renderer.device);
+            let saved = std::fs::read_dir(&dir).ok().and_then(|mut entries| entries.next()).and_then(Result::ok);
+            if let Some(entry) = saved {
+                break entry.path();
+            }
+            assert!(std::time::Instant::now() < deadline, "the screenshot was not saved");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        // The file may still be being written.
+        let screenshot = loop {
+            if let Ok(image) = image::open(&path) {
+                break image.to_rgba8();
+            }
+            assert!(std::time::Instant::now() < deadline, "the screenshot is not a valid image");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(screenshot, renderer.render_to_image(&mut DebugOverlay::new()).unwrap());
+    }
+
+    #[test]
+    fn scenes_match_the_golden_image() {
+        let atlas = atlas();
+        let Some(mut renderer) = renderer(&atlas) else {
+            return;
+        };
+        let registry = BlockRegistry::builtin();
+        let textures = BlockTextureMap::new(&registry, &atlas);
+        let world = scene(&registry);
+        for chunk in world.chunks() {
+            let pos = chunk.pos();
+            let mesh = meshing::mesh_chunk(&world.neighborhood(pos).unwrap(), &registry, &textures, MeshMode::Greedy);
+            renderer.upload_chunk_mesh(pos, &mesh);
+        }
+        *renderer.camera_mut() = Camera::new(
+            cgmath::Point3::new(-6.0, 14.0, -8.0),
+            cgmath::Point3::new(8.0, 3.0, 8.0),
+            cgmath::Vector3::unit_y(),
+            45.0,
+            WIDTH as f32 / HEIGHT as f32,
+            0.1,
+            100.0,
+        );
+        renderer.camera_mut().reverse_z = renderer.reverse_z();
+
+        let frame = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        assert_matches_golden("island", &frame);
+
+        // Sky light dims at night, but the island is still visible.
+        renderer.set_time_of_day(0.75);
+        let night = renderer.render_to_image(&mut DebugOverlay::new()).unwrap();
+        let (day, night) = (mean_brightness(&frame), mean_brightness(&night));
+        assert!(night < 0.5 * day && night > 0.0, "{} {}", day, night);
     }
 }
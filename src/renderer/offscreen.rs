@@ -0,0 +1,202 @@
+//! # Offscreen Target
+//!
+//! This module defines a texture the renderer can draw into instead of a window
+//! surface, and reads finished frames back to the CPU as RGBA images. Together
+//! with a software adapter, it lets tests run the renderer without a GPU or a
+//! display. Screenshots use the same read-back.
+
+use std::sync::mpsc;
+
+#[cfg(test)]
+use image::RgbaImage;
+
+/// A texture that frames are drawn into and read back from.
+#[cfg(test)]
+pub struct OffscreenTarget {
+    texture: wgpu::Texture,
+    width: u32,
+    height: u32,
+}
+
+#[cfg(test)]
+impl OffscreenTarget {
+    /// The format of the texture. It is sRGB, like the window surface, so frames
+    /// look the same on screen and when read back.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Creates a new offscreen target of the given size.
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Self { texture, width, height }
+    }
+
+    /// Returns the texture frames are drawn into.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// Creates a view of the texture to draw into.
+    pub fn create_view(&self) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// Copies the texture back to the CPU, waiting for the GPU to finish drawing it.
+    pub fn read_rgba(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<RgbaImage> {
+        let data = read_texture(device, queue, &self.texture, self.width, self.height)?;
+        RgbaImage::from_raw(self.width, self.height, data)
+            .ok_or_else(|| anyhow::anyhow!("The offscreen frame has the wrong size"))
+    }
+}
+
+/// Copies a texture with 4 bytes per pixel back to the CPU, waiting for the GPU
+/// to finish writing it. Returns the rows without padding.
+#[cfg(test)]
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, width: u32, height: u32) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Read-Back Encoder"),
+    });
+    let mut readback = TextureReadback::new(device, &mut encoder, texture, width, height);
+    queue.submit(std::iter::once(encoder.finish()));
+    readback.map();
+    readback.wait(device)
+}
+
+/// A copy of a texture with 4 bytes per pixel on its way back to the CPU. The copy
+/// is recorded into an encoder, and once that is submitted, the buffer it is
+/// copied into is mapped without blocking.
+pub struct TextureReadback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    /// Receives the result of mapping the buffer, once `map` was called.
+    mapped: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl TextureReadback {
+    /// Records a copy of `texture` into `encoder`. `map` must be called once the
+    /// encoder has been submitted.
+    pub fn new(device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture, width: u32, height: u32) -> Self {
+        let bytes_per_row = padded_bytes_per_row(width);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Read-Back Buffer"),
+            size: bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self { buffer, width, height, bytes_per_row, mapped: None }
+    }
+
+    /// Starts mapping the buffer. Does nothing if it is already being mapped.
+    pub fn map(&mut self) {
+        if self.mapped.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.mapped = Some(receiver);
+    }
+
+    /// Returns the rows of the texture without padding, or `None` if the buffer is
+    /// not mapped yet. The device has to be polled for mapping to finish.
+    pub fn try_read(&mut self) -> Option<anyhow::Result<Vec<u8>>> {
+        let result = match self.mapped.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if let Err(e) = result {
+            return Some(Err(e.into()));
+        }
+
+        let data = unpad_rows(&self.buffer.slice(..).get_mapped_range(), self.width, self.height, self.bytes_per_row);
+        self.buffer.unmap();
+        Some(Ok(data))
+    }
+
+    /// Waits for the buffer to be mapped and returns the rows of the texture
+    /// without padding.
+    #[cfg(test)]
+    pub fn wait(mut self, device: &wgpu::Device) -> anyhow::Result<Vec<u8>> {
+        device.poll(wgpu::Maintain::Wait);
+        self.try_read().unwrap_or_else(|| Err(anyhow::anyhow!("The read-back buffer was not mapped")))
+    }
+}
+
+/// Returns the number of bytes in a row of a texture copy, rounded up to the
+/// alignment required by wgpu.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(align) * align
+}
+
+/// Removes the padding at the end of each row of a texture copy.
+fn unpad_rows(data: &[u8], width: u32, height: u32, bytes_per_row: u32) -> Vec<u8> {
+    let row = width as usize * 4;
+    data.chunks(bytes_per_row as usize)
+        .take(height as usize)
+        .flat_map(|padded| &padded[..row])
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_padded_to_the_copy_alignment() {
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(1280), 5120);
+    }
+
+    #[test]
+    fn padding_is_removed_from_every_row() {
+        // Two rows of three pixels, each padded to 256 bytes.
+        let mut data = vec![0xAA; 512];
+        for (y, row) in data.chunks_mut(256).enumerate() {
+            for (i, byte) in row[..12].iter_mut().enumerate() {
+                *byte = (y * 12 + i) as u8;
+            }
+        }
+        let unpadded = unpad_rows(&data, 3, 2, 256);
+        assert_eq!(unpadded, (0..24).collect::<Vec<u8>>());
+    }
+}
@@ -1,69 +1,153 @@
 //! # Render Pipeline
 //! 
-//! This module defines the `wgpu` render pipeline.
+//! This module defines a factory that builds `wgpu` render pipelines from a
+//! `PipelineDescriptor`, and the `Pipelines` collection the renderer draws with.
 
-use crate::renderer::{vertex::Vertex, shaders};
+use std::collections::HashMap;
 
-/// Contains the `wgpu` render pipeline.
-pub struct RenderPipeline {
-    pipeline: wgpu::RenderPipeline,
+use crate::renderer::depth::DepthTexture;
+
+/// Identifies one of the render pipelines used by the renderer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PipelineKind {
+    /// Opaque block geometry.
+    Opaque,
+    /// Alpha-blended block geometry, drawn after opaque geometry without writing depth.
+    Transparent,
+    /// Block geometry drawn as lines, for debugging.
+    Wireframe,
+    /// Coloured debug lines.
+    Line,
+    /// The sky dome, drawn behind everything else.
+    Sky,
+    /// The sun and moon, drawn over the sky dome.
+    Celestial,
+}
+
+/// Describes a render pipeline independently of the surface and depth buffer.
+pub struct PipelineDescriptor<'a> {
+    pub label: &'a str,
+    pub shader: &'a wgpu::ShaderModule,
+    pub vertex_entry_point: &'a str,
+    pub fragment_entry_point: &'a str,
+    pub vertex_layout: wgpu::VertexBufferLayout<'a>,
+    pub bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+    pub topology: wgpu::PrimitiveTopology,
+    pub polygon_mode: wgpu::PolygonMode,
+    pub cull_mode: Option<wgpu::Face>,
+    pub blend: Option<wgpu::BlendState>,
+    pub depth_write: bool,
+    /// Whether fragments behind the depth buffer are discarded.
+    pub depth_test: bool,
 }
 
-impl RenderPipeline {
-    /// Creates a new render pipeline.
+impl<'a> PipelineDescriptor<'a> {
+    /// Creates a descriptor for filled, back-face culled, opaque triangles using the
+    /// `vs_main` and `fs_main` entry points of `shader`.
     pub fn new(
-        device: &wgpu::Device, 
-        config: &wgpu::SurfaceConfiguration,
-        uniform_bind_group_layout: &wgpu::BindGroupLayout
-    ) -> anyhow::Result<Self> {
-        let shader = shaders::create_basic_shader(device);
-        
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[uniform_bind_group_layout],
+        label: &'a str,
+        shader: &'a wgpu::ShaderModule,
+        vertex_layout: wgpu::VertexBufferLayout<'a>,
+        bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+    ) -> Self {
+        Self {
+            label,
+            shader,
+            vertex_entry_point: "vs_main",
+            fragment_entry_point: "fs_main",
+            vertex_layout,
+            bind_group_layouts,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: Some(wgpu::BlendState::REPLACE),
+            depth_write: true,
+            depth_test: true,
+        }
+    }
+}
+
+/// Builds render pipelines that target the surface format and the depth buffer.
+pub struct PipelineFactory<'a> {
+    device: &'a wgpu::Device,
+    color_format: wgpu::TextureFormat,
+    depth_texture: &'a DepthTexture,
+}
+
+impl<'a> PipelineFactory<'a> {
+    /// Creates a new `PipelineFactory`.
+    pub fn new(device: &'a wgpu::Device, color_format: wgpu::TextureFormat, depth_texture: &'a DepthTexture) -> Self {
+        Self {
+            device,
+            color_format,
+            depth_texture,
+        }
+    }
+
+    /// Creates a render pipeline from a descriptor.
+    pub fn create(&self, desc: &PipelineDescriptor) -> wgpu::RenderPipeline {
+        let layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Layout", desc.label)),
+            bind_group_layouts: desc.bind_group_layouts,
             push_constant_ranges: &[],
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+        let mut depth_stencil = self.depth_texture.depth_stencil_state(desc.depth_write);
+        if !desc.depth_test {
+            depth_stencil.depth_compare = wgpu::CompareFunction::Always;
+        }
+
+        self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(desc.label),
+            layout: Some(&layout),
             vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
+                module: desc.shader,
+                entry_point: desc.vertex_entry_point,
+                buffers: std::slice::from_ref(&desc.vertex_layout),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
+                module: desc.shader,
+                entry_point: desc.fragment_entry_point,
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    format: self.color_format,
+                    blend: desc.blend,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: desc.topology,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
+                cull_mode: desc.cull_mode,
+                polygon_mode: desc.polygon_mode,
                 unclipped_depth: false,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: Some(depth_stencil),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-        });
-        
-        Ok(Self { pipeline })
+        })
     }
-    
-    /// Returns a reference to the render pipeline.
-    pub fn get_pipeline(&self) -> &wgpu::RenderPipeline {
-        &self.pipeline
+}
+
+/// The render pipelines built by the renderer, keyed by kind.
+#[derive(Default)]
+pub struct Pipelines {
+    pipelines: HashMap<PipelineKind, wgpu::RenderPipeline>,
+}
+
+impl Pipelines {
+    /// Adds a pipeline, replacing any pipeline of the same kind.
+    pub fn insert(&mut self, kind: PipelineKind, pipeline: wgpu::RenderPipeline) {
+        self.pipelines.insert(kind, pipeline);
+    }
+
+    /// Returns the pipeline of the given kind, if it was built.
+    pub fn get(&self, kind: PipelineKind) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(&kind)
     }
-}
\ No newline at end of file
+}
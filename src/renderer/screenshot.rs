@@ -0,0 +1,201 @@
+//! # Screenshots
+//! 
+//! This module saves frames as PNG files. A requested screenshot is copied out of
+//! the frame on the GPU, read back once the copy is done, and converted and
+//! written to disk on a separate thread, so taking one does not stall the frame
+//! loop.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+use log::{error, info, warn};
+
+use crate::renderer::offscreen::TextureReadback;
+
+/// The directory screenshots are saved in, relative to the working directory.
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+/// A screenshot waiting for its frame to be read back.
+struct PendingScreenshot {
+    readback: TextureReadback,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    path: PathBuf,
+}
+
+/// Captures requested screenshots and saves them in the background.
+pub struct Screenshots {
+    dir: PathBuf,
+    /// Whether the next frame is captured, and if so, whether with the GUI.
+    requested: Option<bool>,
+    pending: Vec<PendingScreenshot>,
+}
+
+impl Screenshots {
+    /// Creates a new `Screenshots` that saves into `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), requested: None, pending: Vec::new() }
+    }
+
+    /// Captures the next frame, with or without the GUI drawn over it.
+    pub fn request(&mut self, include_gui: bool) {
+        self.requested = Some(include_gui);
+    }
+
+    /// Returns whether the frame being drawn is captured, and if so, whether with
+    /// the GUI, clearing the request.
+    pub fn take_request(&mut self) -> Option<bool> {
+        self.requested.take()
+    }
+
+    /// Records a copy of the frame in `texture` into `encoder`. The copy is read
+    /// back by `update` once the encoder has been submitted.
+    pub fn capture(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            warn!("Screenshots are not supported, because the frame cannot be copied");
+            return;
+        }
+        let (width, height) = (texture.width(), texture.height());
+        self.pending.push(PendingScreenshot {
+            readback: TextureReadback::new(device, encoder, texture, width, height),
+            format: texture.format(),
+            width,
+            height,
+            path: self.dir.join(format!("{}.png", timestamp(SystemTime::now()))),
+        });
+    }
+
+    /// Starts reading back the frames captured since the last call, and saves the
+    /// ones that finished on a separate thread. Called after every submission.
+    pub fn update(&mut self, device: &wgpu::Device) {
+        if self.pending.is_empty() {
+            return;
+        }
+        for screenshot in &mut self.pending {
+            screenshot.readback.map();
+        }
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut i = 0;
+        while i < self.pending.len() {
+            let Some(result) = self.pending[i].readback.try_read() else {
+                i += 1;
+                continue;
+            };
+            let screenshot = self.pending.swap_remove(i);
+            match result {
+                Ok(data) => save_in_background(screenshot, data),
+                Err(e) => error!("Failed to read back the screenshot: {}", e),
+            }
+        }
+    }
+}
+
+/// Converts the frame to RGBA and writes it as a PNG file on a new thread.
+fn save_in_background(screenshot: PendingScreenshot, mut data: Vec<u8>) {
+    let result = std::thread::Builder::new().name("screenshot".into()).spawn(move || {
+        let saved = to_rgba(&mut data, screenshot.format).and_then(|()| {
+            let image = RgbaImage::from_raw(screenshot.width, screenshot.height, data)
+                .ok_or_else(|| anyhow::anyhow!("The frame has the wrong size"))?;
+            save(&image, &screenshot.path)
+        });
+        match saved {
+            Ok(()) => info!("Saved screenshot to {}", screenshot.path.display()),
+            Err(e) => error!("Failed to save screenshot {}: {}", screenshot.path.display(), e),
+        }
+    });
+    if let Err(e) = result {
+        error!("Failed to start saving the screenshot: {}", e);
+    }
+}
+
+/// Writes an image as a PNG file, creating its directory if needed.
+fn save(image: &RgbaImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    image.save(path)?;
+    Ok(())
+}
+
+/// Converts the pixels of a frame in `format` to opaque RGBA in place.
+///
+/// The bytes of sRGB formats are already sRGB encoded, and those of linear ones are
+/// shown on screen as they are, so the colours are kept as they are either way and
+/// only the channels are reordered.
+fn to_rgba(data: &mut [u8], format: wgpu::TextureFormat) -> anyhow::Result<()> {
+    let bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => anyhow::bail!("Screenshots of {:?} frames are not supported", format),
+    };
+    for pixel in data.chunks_exact_mut(4) {
+        if bgra {
+            pixel.swap(0, 2);
+        }
+        // The window is opaque, whatever alpha the frame has.
+        pixel[3] = 255;
+    }
+    Ok(())
+}
+
+/// Formats a time as a UTC timestamp for file names, e.g. `2023-11-14_22.13.20.000`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Converts days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}.{:02}.{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps_are_utc_dates() {
+        let at = |secs: u64, millis: u64| timestamp(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis));
+        assert_eq!(at(0, 0), "1970-01-01_00.00.00.000");
+        assert_eq!(at(1_700_000_000, 42), "2023-11-14_22.13.20.042");
+        // A leap day.
+        assert_eq!(at(951_782_400 + 86_399, 999), "2000-02-29_23.59.59.999");
+        assert_eq!(at(1_709_251_199, 0), "2024-02-29_23.59.59.000");
+    }
+
+    #[test]
+    fn frames_are_converted_to_opaque_rgba() {
+        let mut bgra = vec![10, 20, 30, 0, 40, 50, 60, 128];
+        to_rgba(&mut bgra, wgpu::TextureFormat::Bgra8UnormSrgb).unwrap();
+        assert_eq!(bgra, [30, 20, 10, 255, 60, 50, 40, 255]);
+
+        let mut rgba = vec![10, 20, 30, 0];
+        to_rgba(&mut rgba, wgpu::TextureFormat::Rgba8Unorm).unwrap();
+        assert_eq!(rgba, [10, 20, 30, 255]);
+
+        assert!(to_rgba(&mut [0; 8], wgpu::TextureFormat::Rgba16Float).is_err());
+    }
+}
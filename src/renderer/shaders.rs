@@ -11,6 +11,14 @@ pub fn create_basic_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
     })
 }
 
+/// Creates the sky shader module, which draws the sky dome, the sun and the moon.
+pub fn create_sky_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Sky Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sky.wgsl").into()),
+    })
+}
+
 /// Creates the textured shader module.
 pub fn create_textured_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
     device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -0,0 +1,87 @@
+struct Uniforms {
+    view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    sky_light: f32,
+    sun_direction: vec3<f32>,
+    ambient_occlusion: f32,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+// The distance of the sky from the camera. Sky geometry is drawn without depth
+// testing, so it only has to lie between the near and far planes.
+const SKY_DISTANCE: f32 = 10.0;
+
+// Half the size of the sun and moon quads, relative to their distance.
+const CELESTIAL_SIZE: f32 = 0.12;
+
+struct SkyOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+}
+
+// Draws the dome, a cube around the camera whose positions are view directions.
+@vertex
+fn vs_sky(model: VertexInput) -> SkyOutput {
+    var out: SkyOutput;
+    out.direction = model.position;
+    out.clip_position = uniforms.view_proj * vec4<f32>(uniforms.camera_position + model.position * SKY_DISTANCE, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_sky(in: SkyOutput) -> @location(0) vec4<f32> {
+    let height = normalize(in.direction).y;
+    // Blend from the horizon to the zenith, and darken the sky below the horizon a little.
+    var color = mix(uniforms.horizon_color.rgb, uniforms.zenith_color.rgb, sqrt(max(height, 0.0)));
+    color *= mix(1.0, 0.7, clamp(-height * 4.0, 0.0, 1.0));
+    return vec4<f32>(color, 1.0);
+}
+
+struct CelestialOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) @interpolate(flat) body: f32,
+}
+
+// Draws the sun and moon quads. The position of a vertex holds its corner across
+// the quad, from -1 to 1, and the body it belongs to: 0 for the sun, 1 for the moon.
+@vertex
+fn vs_celestial(model: VertexInput) -> CelestialOutput {
+    let body = model.position.z;
+    let direction = select(uniforms.sun_direction, -uniforms.sun_direction, body > 0.5);
+    // The sun moves in the XY plane, so Z is always across its path.
+    let side = vec3<f32>(0.0, 0.0, 1.0);
+    let up = normalize(cross(side, direction));
+    let offset = (side * model.position.x + up * model.position.y) * CELESTIAL_SIZE;
+
+    var out: CelestialOutput;
+    out.corner = model.position.xy;
+    out.color = model.color;
+    out.body = body;
+    out.clip_position = uniforms.view_proj * vec4<f32>(uniforms.camera_position + (direction + offset) * SKY_DISTANCE, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_celestial(in: CelestialOutput) -> @location(0) vec4<f32> {
+    let radius = length(in.corner);
+    if in.body < 0.5 {
+        // A bright disc with a soft glow around it.
+        let disc = 1.0 - smoothstep(0.45, 0.5, radius);
+        let glow = 0.5 * pow(max(1.0 - radius, 0.0), 2.0);
+        return vec4<f32>(in.color, clamp(disc + glow, 0.0, 1.0));
+    }
+    // A pale disc with a sharper edge.
+    let disc = 1.0 - smoothstep(0.33, 0.36, radius);
+    return vec4<f32>(in.color, disc);
+}
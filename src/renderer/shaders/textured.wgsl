@@ -0,0 +1,85 @@
+struct Uniforms {
+    view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    // Scales sky light, from dim at night to 1 by day.
+    sky_light: f32,
+    sun_direction: vec3<f32>,
+    // 1 to darken occluded vertices, 0 to ignore ambient occlusion.
+    ambient_occlusion: f32,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) atlas_rect: vec4<f32>,
+    @location(3) color: vec3<f32>,
+    @location(4) light: vec2<f32>,
+    @location(5) ao: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) atlas_rect: vec4<f32>,
+    @location(2) color: vec3<f32>,
+}
+
+// The brightness of blocks without any light, so caves are not pitch black.
+const MIN_BRIGHTNESS: f32 = 0.04;
+
+// Converts a light level from 0 to 1 into a brightness. Like in Minecraft, each
+// level is noticeably darker than the one above it.
+fn brightness(level: f32) -> f32 {
+    return mix(MIN_BRIGHTNESS, 1.0, level / (4.0 - 3.0 * level));
+}
+
+// The brightness of a vertex with all three neighbours around it occluded.
+const MIN_AO_BRIGHTNESS: f32 = 0.45;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.atlas_rect = model.atlas_rect;
+    let ao = mix(1.0, mix(MIN_AO_BRIGHTNESS, 1.0, model.ao), uniforms.ambient_occlusion);
+    let light = max(model.light.x * uniforms.sky_light, model.light.y);
+    out.color = model.color * brightness(light) * ao;
+    out.clip_position = uniforms.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+fn sample_atlas(in: VertexOutput) -> vec4<f32> {
+    // Wrap the coordinates inside the texture's region of the atlas. The gradients
+    // are taken from the unwrapped coordinates so the seams between repeats do not
+    // select a tiny mip level.
+    let size = in.atlas_rect.zw;
+    let uv = in.atlas_rect.xy + fract(in.tex_coords) * size;
+    return textureSampleGrad(atlas_texture, atlas_sampler, uv, dpdx(in.tex_coords) * size, dpdy(in.tex_coords) * size);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_atlas(in);
+    if color.a < 0.5 {
+        discard;
+    }
+    return vec4<f32>(color.rgb * in.color, 1.0);
+}
+
+@fragment
+fn fs_transparent(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_atlas(in);
+    return vec4<f32>(color.rgb * in.color, color.a);
+}
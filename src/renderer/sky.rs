@@ -0,0 +1,137 @@
+//! # Sky
+//! 
+//! This module derives the look of the sky from the time of day: where the sun and
+//! moon are, the colours of the sky dome, and how bright sky light is. The sun
+//! rises in the east (+X), passes overhead at noon and sets in the west; the moon
+//! is always opposite it.
+
+use cgmath::{InnerSpace, Vector3};
+
+/// The brightness of sky light at night, relative to the day.
+const MIN_DAYLIGHT: f32 = 0.3;
+
+/// The colours at the top of the sky and at the horizon, by day and by night. They
+/// are linear, like the clear colour.
+const DAY_ZENITH: [f32; 3] = [0.16, 0.38, 0.85];
+const DAY_HORIZON: [f32; 3] = [0.55, 0.72, 0.92];
+const NIGHT_ZENITH: [f32; 3] = [0.002, 0.003, 0.012];
+const NIGHT_HORIZON: [f32; 3] = [0.012, 0.016, 0.035];
+
+/// The colour the horizon glows at sunrise and sunset.
+const SUNSET_GLOW: [f32; 3] = [0.95, 0.42, 0.15];
+
+/// The state of the sky at a time of day.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sky {
+    /// How far the day is, from 0 at sunrise to 1 at the next one.
+    time_of_day: f32,
+}
+
+impl Default for Sky {
+    /// Returns the sky at noon.
+    fn default() -> Self {
+        Self::new(0.25)
+    }
+}
+
+impl Sky {
+    /// Creates the sky at a time of day, from 0 at sunrise to 1 at the next one.
+    pub fn new(time_of_day: f32) -> Self {
+        Self { time_of_day: time_of_day.rem_euclid(1.0) }
+    }
+
+    /// Returns the unit direction towards the sun.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = self.time_of_day * std::f32::consts::TAU;
+        Vector3::new(angle.cos(), angle.sin(), 0.0).normalize()
+    }
+
+    /// Returns how much of the day it is, from 0 at night to 1 by day, easing
+    /// between the two while the sun is near the horizon.
+    fn day_amount(&self) -> f32 {
+        smoothstep(-0.2, 0.2, self.sun_direction().y)
+    }
+
+    /// Returns the multiplier applied to sky light, from `MIN_DAYLIGHT` at night to 1.
+    pub fn daylight(&self) -> f32 {
+        lerp(MIN_DAYLIGHT, 1.0, self.day_amount())
+    }
+
+    /// Returns the colour at the top of the sky.
+    pub fn zenith_color(&self) -> [f32; 3] {
+        lerp3(NIGHT_ZENITH, DAY_ZENITH, self.day_amount())
+    }
+
+    /// Returns the colour of the sky at the horizon, which glows while the sun rises
+    /// and sets.
+    pub fn horizon_color(&self) -> [f32; 3] {
+        let sun_height = self.sun_direction().y / 0.15;
+        let glow = 0.6 * (-sun_height * sun_height).exp();
+        lerp3(lerp3(NIGHT_HORIZON, DAY_HORIZON, self.day_amount()), SUNSET_GLOW, glow)
+    }
+
+    /// Returns the colour the frame is cleared to, which matches the horizon.
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b] = self.horizon_color().map(f64::from);
+        wgpu::Color { r, g, b, a: 1.0 }
+    }
+}
+
+/// Eases from 0 at `edge0` to 1 at `edge1`, like the WGSL function of the same name.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp3(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| lerp(a[i], b[i], t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luminance([r, g, b]: [f32; 3]) -> f32 {
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    #[test]
+    fn the_sun_crosses_the_sky_once_a_day() {
+        let at = |time: f32| Sky::new(time).sun_direction();
+        assert!((at(0.0) - Vector3::unit_x()).magnitude() < 1e-5);
+        assert!((at(0.25) - Vector3::unit_y()).magnitude() < 1e-5);
+        assert!((at(0.5) + Vector3::unit_x()).magnitude() < 1e-5);
+        assert!((at(0.75) + Vector3::unit_y()).magnitude() < 1e-5);
+        assert!((at(1.25) - at(0.25)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn sky_light_dims_at_night() {
+        assert_eq!(Sky::new(0.25).daylight(), 1.0);
+        assert_eq!(Sky::new(0.75).daylight(), MIN_DAYLIGHT);
+        // Dusk is between the two, and gets darker as the sun sets.
+        let dusk: Vec<f32> = [0.48, 0.5, 0.52].iter().map(|&time| Sky::new(time).daylight()).collect();
+        assert!(dusk.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", dusk);
+        assert!(dusk.iter().all(|&daylight| MIN_DAYLIGHT < daylight && daylight < 1.0));
+    }
+
+    #[test]
+    fn sky_colours_follow_the_sun() {
+        let (noon, sunset, midnight) = (Sky::new(0.25), Sky::new(0.5), Sky::new(0.75));
+        assert!(luminance(midnight.zenith_color()) < 0.1 * luminance(noon.zenith_color()));
+        assert!(luminance(midnight.horizon_color()) < 0.1 * luminance(noon.horizon_color()));
+
+        // The sky is bluer overhead, and glows red at sunset.
+        let [r, _, b] = noon.zenith_color();
+        assert!(b > r);
+        let [r, _, b] = sunset.horizon_color();
+        assert!(r > b);
+
+        let clear = midnight.clear_color();
+        assert_eq!([clear.r, clear.g, clear.b].map(|c| c as f32), midnight.horizon_color());
+    }
+}
@@ -1,34 +1,62 @@
 //! # Uniforms
 //! 
 //! This module defines the `Uniforms` struct, which contains data that is passed
-//! to shaders, such as the view-projection matrix.
+//! to shaders, such as the view-projection matrix and the state of the sky.
 
 use crate::renderer::camera::Camera;
+use crate::renderer::sky::Sky;
 
 /// Contains the uniform data that is passed to shaders.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Uniforms {
     pub view_proj: [[f32; 4]; 4],
+    pub camera_position: [f32; 3],
+    /// The multiplier applied to sky light, from dim at night to 1 by day.
+    pub sky_light: f32,
+    /// The unit direction towards the sun.
+    pub sun_direction: [f32; 3],
+    /// 1 if block vertices are darkened by ambient occlusion, 0 if not.
+    pub ambient_occlusion: f32,
+    /// The colours at the top of the sky and at the horizon. Alpha is unused.
+    pub zenith_color: [f32; 4],
+    pub horizon_color: [f32; 4],
 }
 
 impl Uniforms {
-    /// Creates a new `Uniforms` with an identity matrix.
+    /// Creates a new `Uniforms` with an identity matrix, the sky at noon and ambient
+    /// occlusion enabled.
     pub fn new() -> Self {
         use cgmath::{Matrix4, SquareMatrix};
-        Self {
+        let mut uniforms = Self {
             view_proj: Matrix4::identity().into(),
-        }
+            camera_position: [0.0; 3],
+            sky_light: 1.0,
+            sun_direction: [0.0, 1.0, 0.0],
+            ambient_occlusion: 1.0,
+            zenith_color: [0.0; 4],
+            horizon_color: [0.0; 4],
+        };
+        uniforms.update_from_sky(&Sky::default());
+        uniforms
     }
 
-    /// Updates the view-projection matrix from the camera and a rotation.
-    pub fn update_from_camera(&mut self, camera: &Camera, rotation: f32) {
-        use cgmath::{Matrix4, Rad};
-        
+    /// Updates the view-projection matrix and the camera position from the camera.
+    pub fn update_from_camera(&mut self, camera: &Camera) {
         let view = camera.get_view_matrix();
         let proj = camera.get_projection_matrix();
-        let rotation_matrix = Matrix4::from_angle_y(Rad(rotation));
         
-        self.view_proj = (proj * view * rotation_matrix).into();
+        self.view_proj = (proj * view).into();
+        self.camera_position = camera.position.into();
+    }
+
+    /// Updates the sky light, the sun and the sky colours from the sky.
+    pub fn update_from_sky(&mut self, sky: &Sky) {
+        let [zr, zg, zb] = sky.zenith_color();
+        let [hr, hg, hb] = sky.horizon_color();
+        self.sky_light = sky.daylight();
+        self.sun_direction = sky.sun_direction().into();
+        self.zenith_color = [zr, zg, zb, 1.0];
+        self.horizon_color = [hr, hg, hb, 1.0];
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,7 @@
 //! # Vertex
 //! 
-//! This module defines the `Vertex` struct, which represents a vertex in 3D space.
+//! This module defines the vertex types uploaded to the GPU: `Vertex`, a plain
+//! coloured vertex, and `TexturedVertex`, used by block faces.
 
 /// Represents a vertex in 3D space with a position and color.
 #[repr(C)]
@@ -35,4 +36,42 @@ impl Vertex {
             ]
         }
     }
-}
\ No newline at end of file
+}
+
+/// Represents a textured vertex of a block face.
+///
+/// `tex_coords` are measured in blocks across the face and wrap every whole unit,
+/// so a single quad can repeat its texture when several faces are merged into it.
+/// `atlas_rect` holds the texture's region in the atlas as `[min.x, min.y, size.x, size.y]`.
+/// `light` holds the sky and block light at the vertex, from 0 to 1, and `ao` its
+/// ambient occlusion, from 0 (fully occluded) to 1.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexturedVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub atlas_rect: [f32; 4],
+    pub color: [f32; 3],
+    pub light: [f32; 2],
+    pub ao: f32,
+}
+
+impl TexturedVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x3,
+        4 => Float32x2,
+        5 => Float32,
+    ];
+
+    /// Returns the vertex buffer layout for this vertex type.
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TexturedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
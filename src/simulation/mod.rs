@@ -0,0 +1,91 @@
+//! # Simulation Module
+//! 
+//! This module runs the game state at a fixed tick rate, independent of the frame
+//! rate. Frame times are collected in an accumulator, and every whole tick in it is
+//! simulated; the fraction of a tick left over is used to interpolate between the
+//! last two ticks when rendering, so movement stays smooth at any frame rate.
+
+mod time;
+
+pub use time::{WorldTime, TICKS_PER_DAY, TIME_PRESETS};
+
+use std::time::Duration;
+
+/// The number of game ticks per second, as in Minecraft.
+pub const TICKS_PER_SECOND: u32 = 20;
+
+/// The duration of a game tick.
+pub const TICK: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+
+/// The most ticks run in a single frame. After a long stall the simulation falls
+/// behind instead of running a burst of ticks that would stall the next frame too.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+/// Splits frame times into fixed ticks.
+#[derive(Debug)]
+pub struct FixedTimestep {
+    tick: Duration,
+    /// Time that has passed but not been simulated yet.
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    /// Creates a new `FixedTimestep` with ticks of the given duration.
+    pub fn new(tick: Duration) -> Self {
+        Self { tick, accumulator: Duration::ZERO }
+    }
+
+    /// Adds the duration of a frame and returns the number of ticks to run for it.
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_FRAME {
+            ticks = MAX_TICKS_PER_FRAME;
+            self.accumulator = Duration::ZERO;
+        }
+        ticks
+    }
+
+    /// Returns how far the simulation is into the next tick, from 0 to 1, for
+    /// interpolating between the last two ticks.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_do_not_depend_on_the_frame_rate() {
+        let mut slow = FixedTimestep::new(TICK);
+        let mut fast = FixedTimestep::new(TICK);
+        let slow_ticks: u32 = (0..10).map(|_| slow.advance(Duration::from_millis(100))).sum();
+        let fast_ticks: u32 = (0..125).map(|_| fast.advance(Duration::from_millis(8))).sum();
+        assert_eq!(slow_ticks, TICKS_PER_SECOND);
+        assert_eq!(fast_ticks, TICKS_PER_SECOND);
+        assert_eq!(slow.alpha(), 0.0);
+    }
+
+    #[test]
+    fn the_remainder_of_a_tick_is_the_alpha() {
+        let mut timestep = FixedTimestep::new(TICK);
+        assert_eq!(timestep.advance(Duration::from_millis(20)), 0);
+        assert!((timestep.alpha() - 0.4).abs() < 1e-6);
+        assert_eq!(timestep.advance(Duration::from_millis(45)), 1);
+        assert!((timestep.alpha() - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn long_stalls_are_not_caught_up() {
+        let mut timestep = FixedTimestep::new(TICK);
+        assert_eq!(timestep.advance(Duration::from_secs(10)), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(TICK), 1);
+    }
+}
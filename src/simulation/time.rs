@@ -0,0 +1,89 @@
+//! # World Time
+//! 
+//! This module keeps the time of day, which advances by one with every game tick.
+//! As in Minecraft, a day lasts 24000 ticks, or 20 minutes, and starts at sunrise:
+//! noon is at tick 6000, sunset at 12000 and midnight at 18000.
+
+/// The number of ticks in a day.
+pub const TICKS_PER_DAY: u64 = 24_000;
+
+/// Named times of day, like the arguments of Minecraft's `/time set` command.
+pub const TIME_PRESETS: [(&str, u64); 4] = [("Day", 1000), ("Noon", 6000), ("Night", 13_000), ("Midnight", 18_000)];
+
+/// The time of the world, in ticks since the first sunrise.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorldTime {
+    ticks: u64,
+}
+
+impl WorldTime {
+    /// Creates a new `WorldTime` at the given tick.
+    pub fn new(ticks: u64) -> Self {
+        Self { ticks }
+    }
+
+    /// Advances the time by one tick.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// Returns the number of days that have passed.
+    pub fn day(&self) -> u64 {
+        self.ticks / TICKS_PER_DAY
+    }
+
+    /// Returns the ticks since the latest sunrise, from 0 to `TICKS_PER_DAY - 1`.
+    pub fn time_of_day(&self) -> u64 {
+        self.ticks % TICKS_PER_DAY
+    }
+
+    /// Sets the time of day, staying on the current day.
+    pub fn set_time_of_day(&mut self, time: u64) {
+        self.ticks = self.day() * TICKS_PER_DAY + time % TICKS_PER_DAY;
+    }
+
+    /// Returns how far the current day is, from 0 at sunrise to 1 at the next one,
+    /// `alpha` ticks after the latest tick.
+    pub fn fraction(&self, alpha: f32) -> f32 {
+        ((self.time_of_day() as f32 + alpha) / TICKS_PER_DAY as f32).fract()
+    }
+
+    /// Returns the time of day on a 24-hour clock, as hours and minutes.
+    pub fn clock(&self) -> (u64, u64) {
+        let time = self.time_of_day();
+        ((time / 1000 + 6) % 24, time % 1000 * 60 / 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_start_at_sunrise() {
+        let mut time = WorldTime::new(TICKS_PER_DAY - 1);
+        assert_eq!((time.day(), time.time_of_day(), time.clock()), (0, 23_999, (5, 59)));
+        time.tick();
+        assert_eq!((time.day(), time.time_of_day(), time.clock()), (1, 0, (6, 0)));
+        assert_eq!(WorldTime::new(6000).clock(), (12, 0));
+        assert_eq!(WorldTime::new(18_500).clock(), (0, 30));
+    }
+
+    #[test]
+    fn setting_the_time_keeps_the_day() {
+        let mut time = WorldTime::new(3 * TICKS_PER_DAY + 20_000);
+        time.set_time_of_day(6000);
+        assert_eq!((time.day(), time.time_of_day()), (3, 6000));
+        time.set_time_of_day(TICKS_PER_DAY + 13_000);
+        assert_eq!((time.day(), time.time_of_day()), (3, 13_000));
+    }
+
+    #[test]
+    fn fractions_interpolate_between_ticks() {
+        let time = WorldTime::new(TICKS_PER_DAY + 6000);
+        assert_eq!(time.fraction(0.0), 0.25);
+        assert!((time.fraction(0.5) - 6000.5 / 24_000.0).abs() < 1e-6);
+        assert!(WorldTime::new(TICKS_PER_DAY - 1).fraction(0.99) < 1.0);
+        assert_eq!(WorldTime::new(TICKS_PER_DAY - 1).fraction(1.0), 0.0);
+    }
+}
@@ -0,0 +1,282 @@
+//! # Chunk Manager
+//! 
+//! This module defines the `ChunkManager`, which keeps the chunks around the camera
+//! loaded. Chunks within the render distance are requested from the scheduler, and
+//! chunks are only unloaded once they are a few chunks further away, so moving back
+//! and forth across a chunk border does not load and unload the same chunks.
+
+use std::collections::HashSet;
+
+use crate::lighting;
+use crate::meshing::MeshMode;
+use crate::renderer::Renderer;
+use crate::streaming::ChunkScheduler;
+use crate::world::{BlockId, ChunkPos, World};
+
+/// The maximum size of the chunk meshes uploaded to the GPU in a single frame, in bytes.
+const UPLOAD_BUDGET: usize = 4 * 1024 * 1024;
+
+/// The counts of chunks in each stage of streaming, shown in the debug overlay.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// Chunks whose blocks are in the world.
+    pub loaded: usize,
+    /// Chunks waiting to be generated, meshed or uploaded.
+    pub pending: usize,
+    /// Chunks whose mesh has been uploaded.
+    pub meshed: usize,
+}
+
+/// Loads and unloads chunks in a radius around the camera.
+pub struct ChunkManager {
+    scheduler: ChunkScheduler,
+    /// The radius within which chunks are loaded, in chunks.
+    render_distance: i32,
+    /// How much further than the render distance chunks stay loaded, in chunks.
+    unload_margin: i32,
+    /// The chunk the camera was in when chunks were last requested.
+    center: Option<ChunkPos>,
+    /// Chunks whose mesh has been uploaded.
+    meshed: HashSet<ChunkPos>,
+}
+
+impl ChunkManager {
+    /// Creates a new `ChunkManager`.
+    pub fn new(scheduler: ChunkScheduler, render_distance: i32, unload_margin: i32) -> Self {
+        Self {
+            scheduler,
+            render_distance: render_distance.max(0),
+            unload_margin: unload_margin.max(0),
+            center: None,
+            meshed: HashSet::new(),
+        }
+    }
+
+    /// Returns the radius within which chunks are loaded, in chunks.
+    pub fn render_distance(&self) -> i32 {
+        self.render_distance
+    }
+
+    /// Changes the radius within which chunks are loaded.
+    pub fn set_render_distance(&mut self, render_distance: i32) {
+        self.render_distance = render_distance.max(0);
+        self.center = None;
+    }
+
+    /// Remeshes every loaded chunk with a new meshing mode.
+    pub fn set_mesh_mode(&mut self, world: &World, mode: MeshMode) {
+        self.scheduler.set_mode(world, mode);
+    }
+
+    /// Returns `true` if every chunk in range is loaded and meshed.
+    pub fn is_idle(&self) -> bool {
+        self.scheduler.is_idle()
+    }
+
+    /// Returns the current chunk counts.
+    pub fn stats(&self, world: &World) -> ChunkStats {
+        ChunkStats {
+            loaded: world.chunk_count(),
+            pending: self.scheduler.pending_count(),
+            meshed: self.meshed.len(),
+        }
+    }
+
+    /// Changes a block in a loaded chunk and updates the light around it. Its chunk is
+    /// remeshed, along with the neighbouring chunks whose meshes border it or whose
+    /// light changed. Returns the previous block, or `None` if the chunk is not loaded
+    /// or `y` is outside of the world.
+    pub fn set_block(&mut self, world: &mut World, x: i32, y: i32, z: i32, block: BlockId) -> Option<BlockId> {
+        if !world.is_loaded(ChunkPos::from_block(x, z)) {
+            return None;
+        }
+        let previous = world.set_block(x, y, z, block)?;
+        let mut remesh = touching_chunks(x, z);
+        for pos in lighting::update_block(world, self.scheduler.registry(), x, y, z) {
+            if !remesh.contains(&pos) {
+                remesh.push(pos);
+            }
+        }
+        for pos in remesh {
+            self.scheduler.remesh(world, pos);
+        }
+        Some(previous)
+    }
+
+    /// Streams chunks around the camera at `(x, z)` and uploads a bounded share of
+    /// the finished meshes. Called once per frame.
+    pub fn update(&mut self, x: f32, z: f32, world: &mut World, renderer: &mut Renderer) {
+        self.scheduler.set_focus(x, z);
+        if let Some(unloaded) = self.stream(x, z, world) {
+            for pos in unloaded {
+                renderer.remove_chunk_mesh(pos);
+                self.meshed.remove(&pos);
+            }
+        }
+        self.scheduler.process(world);
+        self.meshed.extend(self.scheduler.upload(renderer, UPLOAD_BUDGET));
+    }
+
+    /// Requests the chunks in range and unloads the chunks out of range if the camera
+    /// entered another chunk. Returns the unloaded chunks, or `None` if the camera
+    /// stayed in the same chunk.
+    fn stream(&mut self, x: f32, z: f32, world: &mut World) -> Option<Vec<ChunkPos>> {
+        let center = ChunkPos::from_block(x.floor() as i32, z.floor() as i32);
+        if self.center == Some(center) {
+            return None;
+        }
+        self.center = Some(center);
+
+        let unload = self.render_distance + self.unload_margin;
+        let outside = |pos: ChunkPos| !within(center, pos, unload);
+        self.scheduler.cancel_where(outside);
+        let unloaded: Vec<ChunkPos> = world.chunks().map(|chunk| chunk.pos()).filter(|&pos| outside(pos)).collect();
+        for &pos in &unloaded {
+            world.remove_chunk(pos);
+        }
+
+        let radius = self.render_distance;
+        for dz in -radius..=radius {
+            for dx in -radius..=radius {
+                let pos = ChunkPos::new(center.x + dx, center.z + dz);
+                if within(center, pos, radius) {
+                    self.scheduler.request(world, pos);
+                }
+            }
+        }
+        Some(unloaded)
+    }
+}
+
+/// Returns the chunks containing the column at `(x, z)` or a column next to it,
+/// i.e. the chunks whose meshes change when a block in the column does.
+fn touching_chunks(x: i32, z: i32) -> Vec<ChunkPos> {
+    let mut chunks = Vec::new();
+    for dz in -1..=1 {
+        for dx in -1..=1 {
+            let pos = ChunkPos::from_block(x + dx, z + dz);
+            if !chunks.contains(&pos) {
+                chunks.push(pos);
+            }
+        }
+    }
+    chunks
+}
+
+/// Returns `true` if `pos` is within `radius` chunks of `center`.
+fn within(center: ChunkPos, pos: ChunkPos, radius: i32) -> bool {
+    let (dx, dz) = (pos.x - center.x, pos.z - center.z);
+    dx * dx + dz * dz <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::meshing::BlockTextureMap;
+    use crate::world::{BlockRegistry, Chunk, PendingBlocks, AIR};
+    use crate::worldgen::{GeneratedChunk, WorldGenerator};
+
+    /// A generator that produces empty chunks.
+    struct Empty;
+
+    impl WorldGenerator for Empty {
+        fn generate(&self, pos: ChunkPos) -> GeneratedChunk {
+            GeneratedChunk {
+                chunk: Chunk::new(pos),
+                pending: PendingBlocks::new(),
+            }
+        }
+    }
+
+    fn manager(render_distance: i32, unload_margin: i32) -> ChunkManager {
+        let registry = Arc::new(BlockRegistry::builtin());
+        let textures = Arc::new(BlockTextureMap::untextured(&registry));
+        let scheduler = ChunkScheduler::new(Arc::new(Empty), registry, textures, MeshMode::Greedy, 2);
+        ChunkManager::new(scheduler, render_distance, unload_margin)
+    }
+
+    /// Streams around a block position until every requested chunk is loaded.
+    fn stream_to(manager: &mut ChunkManager, world: &mut World, x: f32, z: f32) {
+        manager.stream(x, z, world);
+        for _ in 0..5000 {
+            manager.scheduler.process(world);
+            if manager.is_idle() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("streaming did not finish");
+    }
+
+    fn loaded(world: &World) -> HashSet<ChunkPos> {
+        world.chunks().map(|chunk| chunk.pos()).collect()
+    }
+
+    #[test]
+    fn loads_a_disc_around_the_camera() {
+        let (mut manager, mut world) = (manager(3, 1), World::new());
+        stream_to(&mut manager, &mut world, 40.0, -8.0);
+
+        let center = ChunkPos::new(2, -1);
+        let expected: HashSet<ChunkPos> = (-3..=3)
+            .flat_map(|dz| (-3..=3).map(move |dx| ChunkPos::new(center.x + dx, center.z + dz)))
+            .filter(|&pos| within(center, pos, 3))
+            .collect();
+        assert_eq!(loaded(&world), expected);
+        assert_eq!(manager.stats(&world).loaded, expected.len());
+    }
+
+    #[test]
+    fn chunks_near_the_border_are_kept() {
+        let (mut manager, mut world) = (manager(2, 2), World::new());
+        stream_to(&mut manager, &mut world, 8.0, 8.0);
+        let before = loaded(&world);
+
+        // Crossing into the next chunk and back loads new chunks but unloads none.
+        stream_to(&mut manager, &mut world, 24.0, 8.0);
+        stream_to(&mut manager, &mut world, 8.0, 8.0);
+        let after = loaded(&world);
+        assert!(after.is_superset(&before));
+        assert!(after.len() > before.len());
+
+        // Moving far away unloads everything that is out of range.
+        stream_to(&mut manager, &mut world, 8.0 + 16.0 * 10.0, 8.0);
+        let center = ChunkPos::new(10, 0);
+        assert!(loaded(&world).iter().all(|&pos| within(center, pos, 4)));
+        assert!(loaded(&world).contains(&center));
+    }
+
+    #[test]
+    fn staying_in_a_chunk_does_not_stream_again() {
+        let (mut manager, mut world) = (manager(1, 1), World::new());
+        stream_to(&mut manager, &mut world, 1.0, 1.0);
+        assert!(manager.stream(15.5, 0.5, &mut world).is_none());
+        assert!(manager.stream(16.5, 0.5, &mut world).is_some());
+
+        manager.set_render_distance(2);
+        assert!(manager.stream(16.5, 0.5, &mut world).is_some());
+    }
+
+    #[test]
+    fn editing_a_block_remeshes_the_chunks_it_touches() {
+        let (mut manager, mut world) = (manager(2, 1), World::new());
+        stream_to(&mut manager, &mut world, 8.0, 8.0);
+        let mut remeshed = |x: i32, z: i32| {
+            assert_eq!(manager.set_block(&mut world, x, 5, z, 1), Some(AIR));
+            let mut chunks: Vec<(i32, i32)> = manager.scheduler.meshing.keys().map(|pos| (pos.x, pos.z)).collect();
+            chunks.sort();
+            stream_to(&mut manager, &mut world, 8.0, 8.0);
+            chunks
+        };
+        assert_eq!(remeshed(8, 8), [(0, 0)]);
+        assert_eq!(remeshed(0, 8), [(-1, 0), (0, 0)]);
+        assert_eq!(remeshed(15, 15), [(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+        // Chunks that are not loaded are not edited.
+        assert_eq!(manager.set_block(&mut world, 1000, 5, 0, 1), None);
+        assert!(!world.is_loaded(ChunkPos::from_block(1000, 0)));
+    }
+}
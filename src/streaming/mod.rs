@@ -0,0 +1,354 @@
+//! # Streaming Module
+//! 
+//! This module moves chunk generation, lighting and meshing off the event loop
+//! thread. Jobs run on a `WorkerPool`, closest to the camera first; their results
+//! are merged into the `World` on the main thread, where light is spread across
+//! chunk borders, and finished meshes are handed to the
+//! renderer a few at a time so a frame never waits on a large upload. The
+//! `ChunkManager` decides which chunks to load around the camera.
+
+mod manager;
+mod pool;
+
+pub use manager::{ChunkManager, ChunkStats};
+pub use pool::{Job, JobId, WorkerPool};
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use crate::lighting;
+use crate::meshing::{self, BlockTextureMap, ChunkMesh, MeshMode};
+use crate::renderer::Renderer;
+use crate::world::{BlockRegistry, ChunkPos, NeighborhoodSnapshot, World};
+use crate::worldgen::{GeneratedChunk, WorldGenerator};
+
+/// The resources shared by meshing jobs.
+struct MeshContext {
+    registry: Arc<BlockRegistry>,
+    textures: Arc<BlockTextureMap>,
+}
+
+/// A job run by the chunk workers.
+enum ChunkJob {
+    Generate {
+        pos: ChunkPos,
+        generator: Arc<dyn WorldGenerator>,
+        registry: Arc<BlockRegistry>,
+    },
+    Mesh {
+        snapshot: NeighborhoodSnapshot,
+        context: Arc<MeshContext>,
+        mode: MeshMode,
+    },
+}
+
+/// The result of a `ChunkJob`.
+enum ChunkJobOutput {
+    Generated(GeneratedChunk),
+    Meshed(ChunkPos, ChunkMesh),
+}
+
+impl Job for ChunkJob {
+    type Output = ChunkJobOutput;
+
+    fn pos(&self) -> ChunkPos {
+        match self {
+            ChunkJob::Generate { pos, .. } => *pos,
+            ChunkJob::Mesh { snapshot, .. } => snapshot.pos(),
+        }
+    }
+
+    fn run(self) -> ChunkJobOutput {
+        match self {
+            ChunkJob::Generate { pos, generator, registry } => {
+                let mut generated = generator.generate(pos);
+                lighting::light_chunk(&mut generated.chunk, &registry);
+                ChunkJobOutput::Generated(generated)
+            }
+            ChunkJob::Mesh { snapshot, context, mode } => {
+                let mesh = meshing::mesh_chunk(&snapshot.neighborhood(), &context.registry, &context.textures, mode);
+                ChunkJobOutput::Meshed(snapshot.pos(), mesh)
+            }
+        }
+    }
+}
+
+/// Schedules the generation and meshing of chunks on a worker pool.
+///
+/// A chunk is meshed once none of its neighbours is still being generated, and
+/// meshed again whenever a neighbour is added, so faces on chunk borders are
+/// always culled against the latest blocks.
+pub struct ChunkScheduler {
+    pool: WorkerPool<ChunkJob>,
+    generator: Arc<dyn WorldGenerator>,
+    context: Arc<MeshContext>,
+    mode: MeshMode,
+    /// The current generation job of each chunk being generated.
+    generating: HashMap<ChunkPos, JobId>,
+    /// The current meshing job of each chunk being meshed.
+    meshing: HashMap<ChunkPos, JobId>,
+    /// Meshes waiting to be uploaded.
+    ready: HashMap<ChunkPos, ChunkMesh>,
+    focus: (f32, f32),
+}
+
+impl ChunkScheduler {
+    /// Creates a new `ChunkScheduler` running `threads` worker threads.
+    pub fn new(
+        generator: Arc<dyn WorldGenerator>,
+        registry: Arc<BlockRegistry>,
+        textures: Arc<BlockTextureMap>,
+        mode: MeshMode,
+        threads: usize,
+    ) -> Self {
+        Self {
+            pool: WorkerPool::new(threads),
+            generator,
+            context: Arc::new(MeshContext { registry, textures }),
+            mode,
+            generating: HashMap::new(),
+            meshing: HashMap::new(),
+            ready: HashMap::new(),
+            focus: (0.0, 0.0),
+        }
+    }
+
+    /// Sets the world position that jobs and uploads are prioritised around.
+    pub fn set_focus(&mut self, x: f32, z: f32) {
+        self.focus = (x, z);
+        self.pool.set_focus(x, z);
+    }
+
+    /// Queues the generation of a chunk unless it is loaded or already queued.
+    pub fn request(&mut self, world: &World, pos: ChunkPos) {
+        if world.is_loaded(pos) || self.generating.contains_key(&pos) {
+            return;
+        }
+        let generator = Arc::clone(&self.generator);
+        let registry = Arc::clone(&self.context.registry);
+        self.generating.insert(pos, self.pool.submit(ChunkJob::Generate { pos, generator, registry }));
+    }
+
+    /// Remeshes every loaded chunk with a new meshing mode.
+    pub fn set_mode(&mut self, world: &World, mode: MeshMode) {
+        self.mode = mode;
+        for pos in world.chunks().map(|chunk| chunk.pos()).collect::<Vec<_>>() {
+            self.schedule_mesh(world, pos);
+        }
+    }
+
+    /// Returns the block definitions used for meshing and lighting.
+    pub fn registry(&self) -> &BlockRegistry {
+        &self.context.registry
+    }
+
+    /// Queues the meshing of a loaded chunk again, e.g. after one of its blocks changed.
+    pub fn remesh(&mut self, world: &World, pos: ChunkPos) {
+        self.schedule_mesh(world, pos);
+    }
+
+    /// Cancels the jobs and drops the pending uploads of the chunks matching `predicate`.
+    pub fn cancel_where(&mut self, predicate: impl Fn(ChunkPos) -> bool) {
+        self.pool.cancel_where(&predicate);
+        self.generating.retain(|&pos, _| !predicate(pos));
+        self.meshing.retain(|&pos, _| !predicate(pos));
+        self.ready.retain(|&pos, _| !predicate(pos));
+    }
+
+    /// Returns `true` if no chunk is being generated or meshed.
+    pub fn is_idle(&self) -> bool {
+        self.generating.is_empty() && self.meshing.is_empty()
+    }
+
+    /// Returns the number of chunks waiting to be generated, meshed or uploaded.
+    pub fn pending_count(&self) -> usize {
+        self.generating.len() + self.meshing.len() + self.ready.len()
+    }
+
+    /// Adds the results of finished jobs to the world and schedules the meshes
+    /// they make possible.
+    pub fn process(&mut self, world: &mut World) {
+        while let Some((id, output)) = self.pool.try_recv() {
+            match output {
+                ChunkJobOutput::Generated(GeneratedChunk { chunk, pending }) => {
+                    let pos = chunk.pos();
+                    // Results of cancelled or superseded jobs are dropped.
+                    if self.generating.get(&pos) != Some(&id) {
+                        continue;
+                    }
+                    self.generating.remove(&pos);
+                    let placed = world.insert_generated(chunk, pending);
+
+                    // Spread light across the new borders, and around the blocks
+                    // that features placed after the chunk was lit.
+                    let registry = Arc::clone(&self.context.registry);
+                    let mut changed: BTreeSet<ChunkPos> = lighting::stitch_chunk(world, &registry, pos).into_iter().collect();
+                    for block in placed {
+                        changed.extend(lighting::update_block(world, &registry, block.x, block.y, block.z));
+                    }
+
+                    for dz in -1..=1 {
+                        for dx in -1..=1 {
+                            changed.insert(ChunkPos::new(pos.x + dx, pos.z + dz));
+                        }
+                    }
+                    for neighbor in changed {
+                        if world.is_loaded(neighbor) && !self.has_generating_neighbor(neighbor) {
+                            self.schedule_mesh(world, neighbor);
+                        }
+                    }
+                }
+                ChunkJobOutput::Meshed(pos, mesh) => {
+                    if self.meshing.get(&pos) == Some(&id) {
+                        self.meshing.remove(&pos);
+                        self.ready.insert(pos, mesh);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Uploads the finished meshes closest to the focus, stopping once `budget`
+    /// bytes have been uploaded. Returns the positions of the uploaded meshes.
+    pub fn upload(&mut self, renderer: &mut Renderer, budget: usize) -> Vec<ChunkPos> {
+        let uploads = self.take_uploads(budget);
+        for (pos, mesh) in &uploads {
+            renderer.upload_chunk_mesh(*pos, mesh);
+        }
+        uploads.into_iter().map(|(pos, _)| pos).collect()
+    }
+
+    /// Removes and returns the finished meshes closest to the focus whose total size
+    /// fits in `budget` bytes. At least one mesh is returned if any is ready, so large
+    /// meshes are never starved.
+    fn take_uploads(&mut self, budget: usize) -> Vec<(ChunkPos, ChunkMesh)> {
+        let (x, z) = self.focus;
+        let mut positions: Vec<ChunkPos> = self.ready.keys().copied().collect();
+        positions.sort_by(|a, b| a.center_distance_sq(x, z).total_cmp(&b.center_distance_sq(x, z)));
+
+        let mut uploads = Vec::new();
+        let mut used = 0;
+        for pos in positions {
+            let size = self.ready[&pos].byte_size();
+            if !uploads.is_empty() && used + size > budget {
+                break;
+            }
+            used += size;
+            uploads.push((pos, self.ready.remove(&pos).unwrap()));
+        }
+        uploads
+    }
+
+    fn has_generating_neighbor(&self, pos: ChunkPos) -> bool {
+        (-1..=1).any(|dz| (-1..=1).any(|dx| self.generating.contains_key(&ChunkPos::new(pos.x + dx, pos.z + dz))))
+    }
+
+    /// Queues the meshing of a loaded chunk, replacing any queued or outdated mesh.
+    fn schedule_mesh(&mut self, world: &World, pos: ChunkPos) {
+        let Some(snapshot) = world.snapshot(pos) else {
+            return;
+        };
+        if let Some(previous) = self.meshing.remove(&pos) {
+            self.pool.cancel(previous);
+        }
+        self.ready.remove(&pos);
+
+        let job = ChunkJob::Mesh {
+            snapshot,
+            context: Arc::clone(&self.context),
+            mode: self.mode,
+        };
+        self.meshing.insert(pos, self.pool.submit(job));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::worldgen::{GeneratorPipeline, OreVein};
+
+    const SEED: u64 = 0x5EED_1234_ABCD;
+
+    fn scheduler() -> ChunkScheduler {
+        let registry = Arc::new(BlockRegistry::builtin());
+        let generator = GeneratorPipeline::standard(SEED, &registry, &OreVein::builtin()).unwrap();
+        let textures = Arc::new(BlockTextureMap::untextured(&registry));
+        ChunkScheduler::new(Arc::new(generator), registry, textures, MeshMode::Greedy, 3)
+    }
+
+    fn square(radius: i32) -> Vec<ChunkPos> {
+        (-radius..=radius).flat_map(|z| (-radius..=radius).map(move |x| ChunkPos::new(x, z))).collect()
+    }
+
+    fn wait_until_idle(scheduler: &mut ChunkScheduler, world: &mut World) {
+        for _ in 0..5000 {
+            scheduler.process(world);
+            if scheduler.is_idle() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("the scheduler did not finish its jobs");
+    }
+
+    #[test]
+    fn requested_chunks_are_generated_and_meshed() {
+        let (mut scheduler, mut world) = (scheduler(), World::new());
+        for pos in square(1) {
+            scheduler.request(&world, pos);
+        }
+        wait_until_idle(&mut scheduler, &mut world);
+
+        assert_eq!(world.chunk_count(), 9);
+        assert_eq!(scheduler.ready.len(), 9);
+
+        // Meshes are built after every neighbour was loaded, like a synchronous mesh.
+        let context = &scheduler.context;
+        for pos in square(1) {
+            let expected = meshing::mesh_chunk(&world.neighborhood(pos).unwrap(), &context.registry, &context.textures, MeshMode::Greedy);
+            let mesh = &scheduler.ready[&pos];
+            assert_eq!((mesh.vertices.len(), mesh.indices.len()), (expected.vertices.len(), expected.indices.len()), "{:?}", pos);
+        }
+    }
+
+    #[test]
+    fn uploads_respect_the_budget_nearest_first() {
+        let (mut scheduler, mut world) = (scheduler(), World::new());
+        for pos in square(1) {
+            scheduler.request(&world, pos);
+        }
+        wait_until_idle(&mut scheduler, &mut world);
+        scheduler.set_focus(40.0, 8.0);
+
+        let first = scheduler.take_uploads(0);
+        assert_eq!(first.iter().map(|(pos, _)| *pos).collect::<Vec<_>>(), [ChunkPos::new(1, 0)]);
+
+        let sizes: Vec<usize> = scheduler.ready.values().map(ChunkMesh::byte_size).collect();
+        let budget = sizes.iter().sum::<usize>() - 1;
+        let second = scheduler.take_uploads(budget);
+        assert!(second.len() < 8 && !second.is_empty());
+        assert!(second.iter().map(|(_, mesh)| mesh.byte_size()).sum::<usize>() <= budget);
+        let distances: Vec<f32> = second.iter().map(|(pos, _)| pos.center_distance_sq(40.0, 8.0)).collect();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert_eq!(scheduler.take_uploads(usize::MAX).len(), 8 - second.len());
+        assert!(scheduler.take_uploads(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn cancelled_chunks_are_not_loaded() {
+        let (mut scheduler, mut world) = (scheduler(), World::new());
+        for pos in square(3) {
+            scheduler.request(&world, pos);
+        }
+        scheduler.cancel_where(|pos| pos.x < 1 || pos.z < 1);
+        wait_until_idle(&mut scheduler, &mut world);
+
+        let loaded: Vec<ChunkPos> = world.chunks().map(|chunk| chunk.pos()).collect();
+        assert_eq!(loaded.len(), 9);
+        assert!(loaded.iter().all(|pos| pos.x >= 1 && pos.z >= 1));
+        assert!(scheduler.ready.keys().all(|pos| pos.x >= 1 && pos.z >= 1));
+    }
+}
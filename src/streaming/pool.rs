@@ -0,0 +1,311 @@
+//! # Worker Pool
+//! 
+//! This module defines a pool of worker threads that run chunk jobs. Queued jobs
+//! are picked closest to a focus point first, so the chunks around the camera are
+//! ready before distant ones, and jobs can be cancelled while they are queued or
+//! running.
+
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use crate::world::ChunkPos;
+
+/// Identifies a job submitted to a `WorkerPool`.
+pub type JobId = u64;
+
+/// A unit of work about a single chunk.
+pub trait Job: Send + 'static {
+    /// The result of the job.
+    type Output: Send + 'static;
+
+    /// Returns the chunk the job works on, which decides its priority.
+    fn pos(&self) -> ChunkPos;
+
+    /// Runs the job.
+    fn run(self) -> Self::Output;
+}
+
+/// A job waiting for a worker.
+struct Queued<J> {
+    id: JobId,
+    pos: ChunkPos,
+    job: J,
+}
+
+/// The state shared between the pool and its workers.
+struct State<J> {
+    queue: Vec<Queued<J>>,
+    /// The jobs being run, and the chunks they work on.
+    running: Vec<(JobId, ChunkPos)>,
+    /// Running jobs whose results must be discarded.
+    cancelled: HashSet<JobId>,
+    /// The world position on the X/Z plane that jobs are prioritised around.
+    focus: (f32, f32),
+    next_id: JobId,
+    shutdown: bool,
+}
+
+impl<J> State<J> {
+    /// Removes and returns the queued job closest to the focus.
+    fn pop_nearest(&mut self) -> Option<Queued<J>> {
+        let (x, z) = self.focus;
+        let index = (0..self.queue.len())
+            .min_by(|&a, &b| self.queue[a].pos.center_distance_sq(x, z).total_cmp(&self.queue[b].pos.center_distance_sq(x, z)))?;
+        Some(self.queue.swap_remove(index))
+    }
+}
+
+struct Shared<J> {
+    state: Mutex<State<J>>,
+    available: Condvar,
+}
+
+/// Runs `Job`s on a fixed number of threads.
+pub struct WorkerPool<J: Job> {
+    shared: Arc<Shared<J>>,
+    results: Receiver<(JobId, J::Output)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<J: Job> WorkerPool<J> {
+    /// Creates a new `WorkerPool` with `threads` worker threads.
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: Vec::new(),
+                running: Vec::new(),
+                cancelled: HashSet::new(),
+                focus: (0.0, 0.0),
+                next_id: 0,
+                shutdown: false,
+            }),
+            available: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                let sender = sender.clone();
+                std::thread::Builder::new()
+                    .name(format!("chunk-worker-{}", i))
+                    .spawn(move || work(&shared, &sender))
+                    .expect("failed to spawn a chunk worker thread")
+            })
+            .collect();
+
+        Self {
+            shared,
+            results,
+            workers,
+        }
+    }
+
+    /// Queues a job and returns its ID.
+    pub fn submit(&self, job: J) -> JobId {
+        let mut state = self.shared.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.queue.push(Queued { id, pos: job.pos(), job });
+        self.shared.available.notify_one();
+        id
+    }
+
+    /// Sets the world position that queued jobs are prioritised around.
+    pub fn set_focus(&self, x: f32, z: f32) {
+        self.shared.state.lock().unwrap().focus = (x, z);
+    }
+
+    /// Cancels a job. A queued job is dropped; a running job finishes, but its
+    /// result is discarded.
+    pub fn cancel(&self, id: JobId) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.queue.retain(|queued| queued.id != id);
+        if state.running.iter().any(|&(running, _)| running == id) {
+            state.cancelled.insert(id);
+        }
+    }
+
+    /// Cancels every queued or running job whose chunk matches `predicate`, and
+    /// returns the IDs of the cancelled jobs.
+    pub fn cancel_where(&self, predicate: impl Fn(ChunkPos) -> bool) -> Vec<JobId> {
+        let mut state = self.shared.state.lock().unwrap();
+        let mut cancelled = Vec::new();
+        state.queue.retain(|queued| {
+            let cancel = predicate(queued.pos);
+            if cancel {
+                cancelled.push(queued.id);
+            }
+            !cancel
+        });
+        let running: Vec<JobId> = state.running.iter().filter(|&&(_, pos)| predicate(pos)).map(|&(id, _)| id).collect();
+        state.cancelled.extend(running.iter().copied());
+        cancelled.extend(running);
+        cancelled
+    }
+
+    /// Returns the result of a finished job, if any, without blocking.
+    pub fn try_recv(&self) -> Option<(JobId, J::Output)> {
+        self.results.try_recv().ok()
+    }
+
+    /// Returns the number of jobs that are queued or running.
+    #[cfg(test)]
+    pub fn pending(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.queue.len() + state.running.len()
+    }
+}
+
+impl<J: Job> Drop for WorkerPool<J> {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.shutdown = true;
+            state.queue.clear();
+        }
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The loop run by each worker thread.
+fn work<J: Job>(shared: &Shared<J>, results: &Sender<(JobId, J::Output)>) {
+    loop {
+        let queued = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.shutdown {
+                    return;
+                }
+                if let Some(queued) = state.pop_nearest() {
+                    state.running.push((queued.id, queued.pos));
+                    break queued;
+                }
+                state = shared.available.wait(state).unwrap();
+            }
+        };
+
+        let output = queued.job.run();
+
+        let mut state = shared.state.lock().unwrap();
+        state.running.retain(|&(id, _)| id != queued.id);
+        // Send while holding the lock, so `pending` never misses a finished job.
+        if !state.cancelled.remove(&queued.id) && results.send((queued.id, output)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A job that returns its chunk position, optionally after waiting for a signal.
+    struct TestJob {
+        pos: ChunkPos,
+        gate: Option<Receiver<()>>,
+    }
+
+    impl TestJob {
+        fn new(x: i32, z: i32) -> Self {
+            Self { pos: ChunkPos::new(x, z), gate: None }
+        }
+
+        /// Returns a job that blocks its worker until the returned sender is used or dropped.
+        fn gated(x: i32, z: i32) -> (Self, Sender<()>) {
+            let (open, gate) = mpsc::channel();
+            (Self { pos: ChunkPos::new(x, z), gate: Some(gate) }, open)
+        }
+    }
+
+    impl Job for TestJob {
+        type Output = ChunkPos;
+
+        fn pos(&self) -> ChunkPos {
+            self.pos
+        }
+
+        fn run(self) -> ChunkPos {
+            if let Some(gate) = self.gate {
+                let _ = gate.recv();
+            }
+            self.pos
+        }
+    }
+
+    /// Waits until every job has finished and returns the results in completion order.
+    fn collect(pool: &WorkerPool<TestJob>) -> Vec<(JobId, ChunkPos)> {
+        let mut results = Vec::new();
+        for _ in 0..1000 {
+            while let Some(result) = pool.try_recv() {
+                results.push(result);
+            }
+            if pool.pending() == 0 {
+                results.extend(std::iter::from_fn(|| pool.try_recv()));
+                return results;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("the pool did not finish its jobs");
+    }
+
+    /// Waits until the pool has started running a job.
+    fn wait_until_running(pool: &WorkerPool<TestJob>) {
+        for _ in 0..1000 {
+            if !pool.shared.state.lock().unwrap().running.is_empty() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("the pool did not start a job");
+    }
+
+    #[test]
+    fn runs_every_job() {
+        let pool = WorkerPool::new(4);
+        let ids: HashSet<JobId> = (0..50).map(|i| pool.submit(TestJob::new(i, -i))).collect();
+        let results = collect(&pool);
+        assert_eq!(results.iter().map(|&(id, _)| id).collect::<HashSet<_>>(), ids);
+    }
+
+    #[test]
+    fn nearest_jobs_run_first() {
+        let pool = WorkerPool::new(1);
+        let (blocker, open) = TestJob::gated(0, 0);
+        pool.submit(blocker);
+        wait_until_running(&pool);
+
+        for x in [9, -2, 5, 1, -7] {
+            pool.submit(TestJob::new(x, 0));
+        }
+        // The focus is read when a job is picked, so it may change after submitting.
+        pool.set_focus(16.0 * 3.0, 8.0);
+        drop(open);
+
+        let order: Vec<i32> = collect(&pool).iter().map(|(_, pos)| pos.x).collect();
+        assert_eq!(order, [0, 1, 5, -2, 9, -7]);
+    }
+
+    #[test]
+    fn cancelled_jobs_produce_no_results() {
+        let pool = WorkerPool::new(1);
+        let (blocker, open) = TestJob::gated(0, 0);
+        let running = pool.submit(blocker);
+        wait_until_running(&pool);
+
+        let queued = pool.submit(TestJob::new(1, 0));
+        let kept = pool.submit(TestJob::new(2, 0));
+        let far = pool.submit(TestJob::new(20, 0));
+        pool.cancel(queued);
+        let cancelled = pool.cancel_where(|pos| pos.x == 0 || pos.x > 10);
+        assert_eq!(cancelled.iter().copied().collect::<HashSet<_>>(), HashSet::from([running, far]));
+        drop(open);
+
+        assert_eq!(collect(&pool), [(kept, ChunkPos::new(2, 0))]);
+    }
+}
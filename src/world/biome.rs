@@ -0,0 +1,106 @@
+//! # Biome
+//! 
+//! This module defines the biomes of the world and the per-column data chunks keep
+//! about them: the biome itself and the blended grass and foliage colours.
+
+use crate::world::registry::BlockTint;
+
+/// A climate region of the world.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Desert,
+    Jungle,
+    #[default]
+    Plains,
+    Forest,
+    Tundra,
+    Taiga,
+}
+
+impl Biome {
+    /// All biomes, in a fixed order.
+    pub const ALL: [Biome; 6] = [Biome::Desert, Biome::Jungle, Biome::Plains, Biome::Forest, Biome::Tundra, Biome::Taiga];
+
+    /// Returns a human-readable name for the biome.
+    pub fn name(self) -> &'static str {
+        match self {
+            Biome::Desert => "Desert",
+            Biome::Jungle => "Jungle",
+            Biome::Plains => "Plains",
+            Biome::Forest => "Forest",
+            Biome::Tundra => "Tundra",
+            Biome::Taiga => "Taiga",
+        }
+    }
+
+    /// Returns the position of the biome in `Biome::ALL`.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the colour of grass in the biome.
+    pub fn grass_color(self) -> [f32; 3] {
+        match self {
+            Biome::Desert => [0.75, 0.72, 0.42],
+            Biome::Jungle => [0.35, 0.80, 0.20],
+            Biome::Plains => [0.55, 0.74, 0.33],
+            Biome::Forest => [0.47, 0.69, 0.31],
+            Biome::Tundra => [0.50, 0.71, 0.60],
+            Biome::Taiga => [0.52, 0.67, 0.47],
+        }
+    }
+
+    /// Returns the colour of foliage in the biome.
+    pub fn foliage_color(self) -> [f32; 3] {
+        match self {
+            Biome::Desert => [0.68, 0.66, 0.35],
+            Biome::Jungle => [0.30, 0.72, 0.12],
+            Biome::Plains => [0.46, 0.66, 0.25],
+            Biome::Forest => [0.38, 0.60, 0.22],
+            Biome::Tundra => [0.38, 0.58, 0.50],
+            Biome::Taiga => [0.40, 0.58, 0.40],
+        }
+    }
+}
+
+/// The biome data of a single block column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColumnInfo {
+    /// The dominant biome of the column.
+    pub biome: Biome,
+    /// The grass colour, blended with the surrounding biomes.
+    pub grass_color: [u8; 3],
+    /// The foliage colour, blended with the surrounding biomes.
+    pub foliage_color: [u8; 3],
+}
+
+impl ColumnInfo {
+    /// Creates the column data of a column lying entirely inside `biome`.
+    pub fn new(biome: Biome) -> Self {
+        Self {
+            biome,
+            grass_color: quantize_color(biome.grass_color()),
+            foliage_color: quantize_color(biome.foliage_color()),
+        }
+    }
+
+    /// Returns the colour multiplied into faces with the given tint.
+    pub fn tint_color(&self, tint: BlockTint) -> [f32; 3] {
+        match tint {
+            BlockTint::None => [1.0; 3],
+            BlockTint::Grass => self.grass_color.map(|c| c as f32 / 255.0),
+            BlockTint::Foliage => self.foliage_color.map(|c| c as f32 / 255.0),
+        }
+    }
+}
+
+impl Default for ColumnInfo {
+    fn default() -> Self {
+        Self::new(Biome::default())
+    }
+}
+
+/// Converts a colour with components in `[0, 1]` to 8 bits per component.
+pub fn quantize_color(color: [f32; 3]) -> [u8; 3] {
+    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
@@ -0,0 +1,9 @@
+//! # Block
+//! 
+//! This module defines the numeric identifiers used to store blocks in chunks.
+
+/// The numeric identifier of a block type.
+pub type BlockId = u16;
+
+/// The identifier of the empty (air) block.
+pub const AIR: BlockId = 0;
@@ -0,0 +1,206 @@
+//! # Chunk
+//! 
+//! This module defines the `Chunk` type, a column of `CHUNK_SIZE` x `CHUNK_HEIGHT`
+//! x `CHUNK_SIZE` blocks split vertically into cubic sections.
+
+use crate::world::biome::ColumnInfo;
+use crate::world::block::{BlockId, AIR};
+use crate::world::light::{Light, LightSection};
+
+/// The width and depth of a chunk, in blocks.
+pub const CHUNK_SIZE: usize = 16;
+
+/// The edge length of a cubic chunk section, in blocks.
+pub const SECTION_SIZE: usize = 16;
+
+/// The number of sections stacked in a chunk.
+pub const SECTION_COUNT: usize = 8;
+
+/// The height of a chunk, in blocks.
+pub const CHUNK_HEIGHT: usize = SECTION_SIZE * SECTION_COUNT;
+
+/// The number of blocks stored in a single section.
+pub(super) const SECTION_VOLUME: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+/// The position of a chunk in chunk coordinates (world block coordinates divided by `CHUNK_SIZE`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    /// Creates a new `ChunkPos`.
+    pub fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Returns the chunk containing the given world block column.
+    pub fn from_block(x: i32, z: i32) -> Self {
+        Self {
+            x: x.div_euclid(CHUNK_SIZE as i32),
+            z: z.div_euclid(CHUNK_SIZE as i32),
+        }
+    }
+
+    /// Returns the world coordinates of the chunk's minimum corner block on the X and Z axes.
+    pub fn origin(&self) -> (i32, i32) {
+        (self.x * CHUNK_SIZE as i32, self.z * CHUNK_SIZE as i32)
+    }
+
+    /// Returns the squared horizontal distance from a world position to the center of the chunk.
+    pub fn center_distance_sq(&self, x: f32, z: f32) -> f32 {
+        let (origin_x, origin_z) = self.origin();
+        let half = CHUNK_SIZE as f32 / 2.0;
+        let (dx, dz) = (origin_x as f32 + half - x, origin_z as f32 + half - z);
+        dx * dx + dz * dz
+    }
+}
+
+/// Converts a world block coordinate on the X or Z axis into a coordinate local to its chunk.
+pub fn local_coord(v: i32) -> usize {
+    v.rem_euclid(CHUNK_SIZE as i32) as usize
+}
+
+/// A cubic `SECTION_SIZE`^3 slice of a chunk.
+///
+/// Sections that only contain air do not allocate block storage.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkSection {
+    blocks: Option<Box<[BlockId]>>,
+    non_air: u16,
+}
+
+impl ChunkSection {
+    /// Returns `true` if the section only contains air.
+    pub fn is_empty(&self) -> bool {
+        self.non_air == 0
+    }
+
+    /// Returns the block at the given section-local coordinates.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        match &self.blocks {
+            Some(blocks) => blocks[Self::index(x, y, z)],
+            None => AIR,
+        }
+    }
+
+    /// Sets the block at the given section-local coordinates and returns the previous block.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) -> BlockId {
+        if self.blocks.is_none() {
+            if block == AIR {
+                return AIR;
+            }
+            self.blocks = Some(vec![AIR; SECTION_VOLUME].into_boxed_slice());
+        }
+
+        let blocks = self.blocks.as_mut().unwrap();
+        let previous = std::mem::replace(&mut blocks[Self::index(x, y, z)], block);
+
+        if previous == AIR && block != AIR {
+            self.non_air += 1;
+        } else if previous != AIR && block == AIR {
+            self.non_air -= 1;
+            if self.non_air == 0 {
+                self.blocks = None;
+            }
+        }
+
+        previous
+    }
+
+    pub(super) fn index(x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < SECTION_SIZE && y < SECTION_SIZE && z < SECTION_SIZE);
+        (y * SECTION_SIZE + z) * SECTION_SIZE + x
+    }
+}
+
+/// A vertical column of blocks.
+#[derive(Clone, Debug)]
+pub struct Chunk {
+    pos: ChunkPos,
+    sections: Vec<ChunkSection>,
+    /// The light of each section, computed by the `lighting` module.
+    light: Vec<LightSection>,
+    columns: Box<[ColumnInfo]>,
+}
+
+impl Chunk {
+    /// Creates a new chunk filled with air.
+    pub fn new(pos: ChunkPos) -> Self {
+        Self {
+            pos,
+            sections: vec![ChunkSection::default(); SECTION_COUNT],
+            light: vec![LightSection::default(); SECTION_COUNT],
+            columns: vec![ColumnInfo::default(); CHUNK_SIZE * CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the position of the chunk.
+    pub fn pos(&self) -> ChunkPos {
+        self.pos
+    }
+
+    /// Returns the sections of the chunk, from bottom to top.
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+
+    /// Returns the biome data of the block column at the given chunk-local coordinates.
+    pub fn column(&self, x: usize, z: usize) -> &ColumnInfo {
+        &self.columns[z * CHUNK_SIZE + x]
+    }
+
+    /// Sets the biome data of the block column at the given chunk-local coordinates.
+    pub fn set_column(&mut self, x: usize, z: usize, column: ColumnInfo) {
+        self.columns[z * CHUNK_SIZE + x] = column;
+    }
+
+    /// Returns the block at the given chunk-local coordinates.
+    ///
+    /// Blocks above or below the chunk are reported as air.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        if y >= CHUNK_HEIGHT {
+            return AIR;
+        }
+        self.sections[y / SECTION_SIZE].get(x, y % SECTION_SIZE, z)
+    }
+
+    /// Sets the block at the given chunk-local coordinates and returns the previous block.
+    ///
+    /// Returns `None` if `y` is outside of the chunk.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) -> Option<BlockId> {
+        if y >= CHUNK_HEIGHT {
+            return None;
+        }
+        Some(self.sections[y / SECTION_SIZE].set(x, y % SECTION_SIZE, z, block))
+    }
+
+    /// Returns the light at the given chunk-local coordinates.
+    ///
+    /// Blocks above the chunk are lit by the open sky.
+    pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+        if y >= CHUNK_HEIGHT {
+            return Light::SKY;
+        }
+        self.light[y / SECTION_SIZE].get(ChunkSection::index(x, y % SECTION_SIZE, z))
+    }
+
+    /// Sets the light at the given chunk-local coordinates. Does nothing if `y` is
+    /// outside of the chunk.
+    pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: Light) {
+        if y < CHUNK_HEIGHT {
+            self.light[y / SECTION_SIZE].set(ChunkSection::index(x, y % SECTION_SIZE, z), light);
+        }
+    }
+
+    /// Sets the light of every block in the chunk.
+    pub fn fill_light(&mut self, light: Light) {
+        self.light.fill(LightSection::filled(light));
+    }
+
+    /// Releases the light storage of sections where every block has the same light.
+    pub fn compact_light(&mut self) {
+        self.light.iter_mut().for_each(LightSection::compact);
+    }
+}
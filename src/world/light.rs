@@ -0,0 +1,160 @@
+//! # Light
+//! 
+//! This module defines how light levels are stored. Every block has a sky light
+//! level, from sunlight, and a block light level, from light-emitting blocks, each
+//! from 0 to 15. The two are packed into one byte, and sections whose blocks all
+//! have the same light store it only once.
+
+use crate::world::chunk::SECTION_VOLUME;
+
+/// The highest light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// One of the two kinds of light.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    /// Light from the sky, which travels straight down through air without fading.
+    Sky,
+    /// Light emitted by blocks.
+    Block,
+}
+
+impl LightKind {
+    /// Both kinds of light.
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+/// The sky and block light levels of a block.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Light(u8);
+
+impl Light {
+    /// No light at all.
+    pub const DARK: Light = Light(0);
+
+    /// Full sky light and no block light, like the open sky.
+    pub const SKY: Light = Light(MAX_LIGHT << 4);
+
+    /// Creates a light from its sky and block levels.
+    pub fn new(sky: u8, block: u8) -> Self {
+        debug_assert!(sky <= MAX_LIGHT && block <= MAX_LIGHT);
+        Self(sky << 4 | block)
+    }
+
+    /// Returns the sky light level.
+    pub fn sky(self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// Returns the block light level.
+    pub fn block(self) -> u8 {
+        self.0 & 0x0F
+    }
+
+    /// Returns the level of one kind of light.
+    pub fn get(self, kind: LightKind) -> u8 {
+        match kind {
+            LightKind::Sky => self.sky(),
+            LightKind::Block => self.block(),
+        }
+    }
+
+    /// Returns the light with the level of one kind replaced.
+    pub fn with(self, kind: LightKind, level: u8) -> Self {
+        match kind {
+            LightKind::Sky => Self::new(level, self.block()),
+            LightKind::Block => Self::new(self.sky(), level),
+        }
+    }
+}
+
+/// The light of a cubic chunk section.
+///
+/// Sections where every block has the same light do not allocate storage. Until
+/// their light is computed, sections are lit like the open sky.
+#[derive(Clone, Debug)]
+pub struct LightSection {
+    values: Option<Box<[Light]>>,
+    /// The light of every block while `values` is not allocated.
+    uniform: Light,
+}
+
+impl Default for LightSection {
+    fn default() -> Self {
+        Self::filled(Light::SKY)
+    }
+}
+
+impl LightSection {
+    /// Creates a section where every block has the same light.
+    pub fn filled(light: Light) -> Self {
+        Self { values: None, uniform: light }
+    }
+
+    /// Returns the light at the given index, as used by `ChunkSection`.
+    pub fn get(&self, index: usize) -> Light {
+        match &self.values {
+            Some(values) => values[index],
+            None => self.uniform,
+        }
+    }
+
+    /// Sets the light at the given index, as used by `ChunkSection`.
+    pub fn set(&mut self, index: usize, light: Light) {
+        if self.values.is_none() {
+            if light == self.uniform {
+                return;
+            }
+            self.values = Some(vec![self.uniform; SECTION_VOLUME].into_boxed_slice());
+        }
+        self.values.as_mut().unwrap()[index] = light;
+    }
+
+    /// Releases the storage of the section if every block has the same light.
+    pub fn compact(&mut self) {
+        if let Some(values) = &self.values {
+            if values.iter().all(|&light| light == values[0]) {
+                self.uniform = values[0];
+                self.values = None;
+            }
+        }
+    }
+
+    /// Returns `true` if the section stores the light of each block.
+    #[cfg(test)]
+    pub fn is_allocated(&self) -> bool {
+        self.values.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_packed_independently() {
+        let light = Light::new(12, 7);
+        assert_eq!((light.sky(), light.block()), (12, 7));
+        assert_eq!(light.with(LightKind::Sky, 3), Light::new(3, 7));
+        assert_eq!(light.with(LightKind::Block, 15).get(LightKind::Block), 15);
+        assert_eq!((Light::SKY.sky(), Light::SKY.block()), (15, 0));
+    }
+
+    #[test]
+    fn uniform_sections_release_their_storage() {
+        let mut section = LightSection::default();
+        section.set(3, Light::SKY);
+        assert!(!section.is_allocated());
+
+        section.set(3, Light::new(4, 2));
+        assert!(section.is_allocated());
+        assert_eq!((section.get(3), section.get(4)), (Light::new(4, 2), Light::SKY));
+
+        section.compact();
+        assert!(section.is_allocated());
+        section.set(3, Light::SKY);
+        section.compact();
+        assert!(!section.is_allocated());
+        assert_eq!(section.get(3), Light::SKY);
+    }
+}
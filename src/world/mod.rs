@@ -0,0 +1,332 @@
+//! # World Module
+//! 
+//! This module contains the voxel representation of the game world: block
+//! identifiers, chunks, biomes, and the `World` container that stores them.
+
+mod biome;
+pub mod block;
+mod chunk;
+mod light;
+mod neighborhood;
+mod pending;
+mod raycast;
+mod registry;
+
+pub use biome::{quantize_color, Biome, ColumnInfo};
+pub use block::{BlockId, AIR};
+pub use chunk::{local_coord, Chunk, ChunkPos, ChunkSection, CHUNK_HEIGHT, CHUNK_SIZE, SECTION_COUNT, SECTION_SIZE};
+pub use light::{Light, LightKind, MAX_LIGHT};
+pub use neighborhood::{ChunkNeighborhood, NeighborhoodSnapshot};
+pub use pending::{PendingBlock, PendingBlocks};
+pub use raycast::{raycast, RaycastHit};
+pub use registry::{BlockRegistry, BlockTint};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Stores the loaded chunks of the world, keyed by chunk position.
+///
+/// Chunks are reference counted so worker threads can hold snapshots of them while
+/// the world keeps changing; editing a chunk that is shared copies it first.
+#[derive(Default)]
+pub struct World {
+    chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    /// Blocks that generated chunks placed into their neighbours, keyed by the chunk
+    /// that generated them. They are kept after being placed, so a neighbour that is
+    /// unloaded and generated again gets them back, and dropped with their source.
+    pending: HashMap<ChunkPos, PendingBlocks>,
+}
+
+impl World {
+    /// Creates a new, empty `World`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the chunk at the given position together with its loaded neighbours.
+    #[cfg(test)]
+    pub fn neighborhood(&self, pos: ChunkPos) -> Option<ChunkNeighborhood<'_>> {
+        let mut neighborhood = ChunkNeighborhood::new(self.chunks.get(&pos)?);
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dz) != (0, 0) {
+                    let neighbor = ChunkPos::new(pos.x + dx, pos.z + dz);
+                    neighborhood.set_neighbor(dx, dz, self.chunks.get(&neighbor).map(Arc::as_ref));
+                }
+            }
+        }
+        Some(neighborhood)
+    }
+
+    /// Returns an owned snapshot of the chunk at the given position and its loaded neighbours.
+    pub fn snapshot(&self, pos: ChunkPos) -> Option<NeighborhoodSnapshot> {
+        self.chunks.get(&pos)?;
+        let chunks = [-1, 0, 1].map(|dz| [-1, 0, 1].map(|dx| self.chunks.get(&ChunkPos::new(pos.x + dx, pos.z + dz)).cloned()));
+        Some(NeighborhoodSnapshot::new(chunks))
+    }
+
+    /// Returns `true` if the chunk at the given position is loaded.
+    pub fn is_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Adds a chunk to the world, replacing any chunk loaded at the same position.
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        self.chunks.insert(chunk.pos(), Arc::new(chunk));
+    }
+
+    /// Adds a generated chunk to the world together with the blocks it placed outside
+    /// of its own bounds.
+    ///
+    /// Blocks queued for this chunk by other chunks are placed into it, and its own
+    /// blocks are placed into loaded neighbours and remembered for the others.
+    /// Returns the blocks that were placed, in this chunk and its neighbours, so
+    /// their light can be updated.
+    pub fn insert_generated(&mut self, mut chunk: Chunk, pending: PendingBlocks) -> Vec<PendingBlock> {
+        let pos = chunk.pos();
+        let mut placed: Vec<PendingBlock> = self.pending.values()
+            .flat_map(|blocks| blocks.blocks(pos))
+            .filter(|block| block.place(&mut chunk))
+            .copied()
+            .collect();
+
+        for neighbor_pos in pending.chunk_positions() {
+            if let Some(neighbor) = self.chunks.get_mut(&neighbor_pos) {
+                let neighbor = Arc::make_mut(neighbor);
+                placed.extend(pending.blocks(neighbor_pos).filter(|block| block.place(neighbor)));
+            }
+        }
+        self.pending.insert(pos, pending);
+        self.insert_chunk(chunk);
+        placed
+    }
+
+    /// Unloads a chunk and forgets the blocks it placed into its neighbours; they are
+    /// queued again if it is generated again. Returns `false` if it was not loaded.
+    pub fn remove_chunk(&mut self, pos: ChunkPos) -> bool {
+        self.pending.remove(&pos);
+        self.chunks.remove(&pos).is_some()
+    }
+
+    /// Returns an iterator over all loaded chunks.
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values().map(Arc::as_ref)
+    }
+
+    /// Returns the number of loaded chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the biome of the given world column, or `None` if its chunk is not loaded.
+    pub fn biome_at(&self, x: i32, z: i32) -> Option<Biome> {
+        let chunk = self.chunks.get(&ChunkPos::from_block(x, z))?;
+        Some(chunk.column(local_coord(x), local_coord(z)).biome)
+    }
+
+    /// Returns the block at the given world coordinates.
+    ///
+    /// Blocks in unloaded chunks or outside of the vertical range are reported as air.
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        if y < 0 {
+            return AIR;
+        }
+        match self.chunks.get(&ChunkPos::from_block(x, z)) {
+            Some(chunk) => chunk.get(local_coord(x), y as usize, local_coord(z)),
+            None => AIR,
+        }
+    }
+
+    /// Returns the light at the given world coordinates, or `None` if its chunk is not
+    /// loaded or `y` is below the world. Blocks above the world are lit by the sky.
+    pub fn light(&self, x: i32, y: i32, z: i32) -> Option<Light> {
+        if y < 0 {
+            return None;
+        }
+        let chunk = self.chunks.get(&ChunkPos::from_block(x, z))?;
+        Some(chunk.light(local_coord(x), y as usize, local_coord(z)))
+    }
+
+    /// Sets the light at the given world coordinates. Does nothing if the chunk is
+    /// not loaded or `y` is outside of the world.
+    pub fn set_light(&mut self, x: i32, y: i32, z: i32, light: Light) {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return;
+        }
+        if let Some(chunk) = self.chunks.get_mut(&ChunkPos::from_block(x, z)) {
+            if chunk.light(local_coord(x), y as usize, local_coord(z)) != light {
+                Arc::make_mut(chunk).set_light(local_coord(x), y as usize, local_coord(z), light);
+            }
+        }
+    }
+
+    /// Sets the block at the given world coordinates and returns the previous block.
+    ///
+    /// The containing chunk is created if it is not loaded. Returns `None` if `y` is
+    /// outside of the vertical range of the world.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: BlockId) -> Option<BlockId> {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return None;
+        }
+        let pos = ChunkPos::from_block(x, z);
+        let chunk = self.chunks.entry(pos).or_insert_with(|| Arc::new(Chunk::new(pos)));
+        Arc::make_mut(chunk).set(local_coord(x), y as usize, local_coord(z), block)
+    }
+}
+
+/// Read access to blocks by world coordinates, so code that queries the world can
+/// be tested without a full `World`.
+pub trait BlockView {
+    /// Returns the block at the given world coordinates.
+    fn block(&self, x: i32, y: i32, z: i32) -> BlockId;
+}
+
+impl BlockView for World {
+    fn block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        self.get_block(x, y, z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::ChunkSection;
+
+    #[test]
+    fn chunk_pos_from_block_at_negative_boundaries() {
+        assert_eq!(ChunkPos::from_block(0, 0), ChunkPos::new(0, 0));
+        assert_eq!(ChunkPos::from_block(15, 15), ChunkPos::new(0, 0));
+        assert_eq!(ChunkPos::from_block(16, -1), ChunkPos::new(1, -1));
+        assert_eq!(ChunkPos::from_block(-1, -16), ChunkPos::new(-1, -1));
+        assert_eq!(ChunkPos::from_block(-17, -32), ChunkPos::new(-2, -2));
+        assert_eq!(ChunkPos::from_block(-33, 31), ChunkPos::new(-3, 1));
+    }
+
+    #[test]
+    fn local_coord_at_negative_boundaries() {
+        assert_eq!(local_coord(0), 0);
+        assert_eq!(local_coord(15), 15);
+        assert_eq!(local_coord(16), 0);
+        assert_eq!(local_coord(-1), 15);
+        assert_eq!(local_coord(-16), 0);
+        assert_eq!(local_coord(-17), 15);
+    }
+
+    #[test]
+    fn chunk_origin_round_trips() {
+        for x in -40..40 {
+            let pos = ChunkPos::from_block(x, -x);
+            let (ox, oz) = pos.origin();
+            assert_eq!(ox + local_coord(x) as i32, x);
+            assert_eq!(oz + local_coord(-x) as i32, -x);
+        }
+    }
+
+    #[test]
+    fn set_and_get_block_across_negative_chunks() {
+        let mut world = World::new();
+        let coords = [(-1, 0, -1), (-16, 5, 0), (-17, 10, -17), (0, 127, 15), (15, 64, -16)];
+
+        for (i, &(x, y, z)) in coords.iter().enumerate() {
+            assert_eq!(world.set_block(x, y, z, i as BlockId + 1), Some(AIR));
+        }
+        for (i, &(x, y, z)) in coords.iter().enumerate() {
+            assert_eq!(world.get_block(x, y, z), i as BlockId + 1);
+        }
+
+        assert_eq!(world.get_block(-2, 0, -1), AIR);
+        assert_eq!(world.chunk_count(), 5);
+    }
+
+    #[test]
+    fn out_of_range_heights_are_air() {
+        let mut world = World::new();
+        assert_eq!(world.set_block(0, -1, 0, 1), None);
+        assert_eq!(world.set_block(0, CHUNK_HEIGHT as i32, 0, 1), None);
+        assert_eq!(world.get_block(0, -1, 0), AIR);
+        assert_eq!(world.get_block(0, CHUNK_HEIGHT as i32, 0), AIR);
+        assert_eq!(world.chunk_count(), 0);
+    }
+
+    #[test]
+    fn pending_blocks_reach_chunks_loaded_before_and_after() {
+        let mut world = World::new();
+        world.insert_chunk(Chunk::new(ChunkPos::new(-1, 0)));
+
+        let mut pending = PendingBlocks::new();
+        pending.push(PendingBlock { x: -1, y: 3, z: 2, block: 4 });
+        pending.push(PendingBlock { x: 20, y: 3, z: 2, block: 5 });
+        let placed = world.insert_generated(Chunk::new(ChunkPos::new(0, 0)), pending);
+        assert_eq!(placed, [PendingBlock { x: -1, y: 3, z: 2, block: 4 }]);
+        assert_eq!(world.get_block(-1, 3, 2), 4);
+        assert_eq!(world.get_block(20, 3, 2), AIR);
+
+        let placed = world.insert_generated(Chunk::new(ChunkPos::new(1, 0)), PendingBlocks::new());
+        assert_eq!(placed, [PendingBlock { x: 20, y: 3, z: 2, block: 5 }]);
+        assert_eq!(world.get_block(20, 3, 2), 5);
+    }
+
+    #[test]
+    fn pending_blocks_survive_unloading_their_chunk() {
+        let mut world = World::new();
+        world.insert_chunk(Chunk::new(ChunkPos::new(1, 0)));
+        let mut pending = PendingBlocks::new();
+        pending.push(PendingBlock { x: 16, y: 3, z: 0, block: 4 });
+        world.insert_generated(Chunk::new(ChunkPos::new(0, 0)), pending);
+        assert_eq!(world.get_block(16, 3, 0), 4);
+
+        assert!(world.remove_chunk(ChunkPos::new(1, 0)));
+        assert!(!world.remove_chunk(ChunkPos::new(1, 0)));
+        assert_eq!(world.get_block(16, 3, 0), AIR);
+
+        world.insert_generated(Chunk::new(ChunkPos::new(1, 0)), PendingBlocks::new());
+        assert_eq!(world.get_block(16, 3, 0), 4);
+    }
+
+    #[test]
+    fn pending_blocks_are_dropped_with_their_source_chunk() {
+        let mut world = World::new();
+        let mut pending = PendingBlocks::new();
+        pending.push(PendingBlock { x: 16, y: 3, z: 0, block: 4 });
+        world.insert_generated(Chunk::new(ChunkPos::new(0, 0)), pending.clone());
+        world.insert_generated(Chunk::new(ChunkPos::new(1, 0)), PendingBlocks::new());
+        assert_eq!(world.get_block(16, 3, 0), 4);
+
+        // Without its source, a regenerated neighbour no longer gets the block.
+        assert!(world.remove_chunk(ChunkPos::new(0, 0)));
+        assert!(world.remove_chunk(ChunkPos::new(1, 0)));
+        assert!(world.pending.is_empty());
+        world.insert_generated(Chunk::new(ChunkPos::new(1, 0)), PendingBlocks::new());
+        assert_eq!(world.get_block(16, 3, 0), AIR);
+
+        // Regenerating the source queues it again.
+        world.insert_generated(Chunk::new(ChunkPos::new(0, 0)), pending);
+        assert_eq!(world.get_block(16, 3, 0), 4);
+    }
+
+    #[test]
+    fn pending_blocks_only_replace_air() {
+        let mut world = World::new();
+        let mut pending = PendingBlocks::new();
+        pending.push(PendingBlock { x: 16, y: 3, z: 0, block: 4 });
+        world.insert_generated(Chunk::new(ChunkPos::new(0, 0)), pending);
+
+        let mut chunk = Chunk::new(ChunkPos::new(1, 0));
+        chunk.set(0, 3, 0, 1);
+        world.insert_generated(chunk, PendingBlocks::new());
+        assert_eq!(world.get_block(16, 3, 0), 1);
+    }
+
+    #[test]
+    fn sections_release_storage_when_cleared() {
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        assert!(chunk.sections().iter().all(ChunkSection::is_empty));
+
+        chunk.set(3, 20, 4, 7);
+        assert!(!chunk.sections()[1].is_empty());
+        assert_eq!(chunk.get(3, 20, 4), 7);
+
+        assert_eq!(chunk.set(3, 20, 4, AIR), Some(7));
+        assert!(chunk.sections()[1].is_empty());
+    }
+}
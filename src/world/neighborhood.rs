@@ -0,0 +1,105 @@
+//! # Chunk Neighborhood
+//! 
+//! This module defines `ChunkNeighborhood`, a read-only view of a chunk and the
+//! eight chunks surrounding it, used by passes that need to look across chunk borders,
+//! and `NeighborhoodSnapshot`, an owned version of it that can be sent to worker threads.
+
+use std::sync::Arc;
+
+use crate::world::block::{BlockId, AIR};
+use crate::world::chunk::{Chunk, ChunkPos, CHUNK_SIZE};
+use crate::world::light::Light;
+
+/// A chunk together with its (possibly unloaded) horizontal neighbours.
+pub struct ChunkNeighborhood<'a> {
+    chunks: [[Option<&'a Chunk>; 3]; 3],
+}
+
+impl<'a> ChunkNeighborhood<'a> {
+    /// Creates a neighborhood with only the center chunk loaded.
+    #[cfg(test)]
+    pub fn new(center: &'a Chunk) -> Self {
+        let mut chunks = [[None; 3]; 3];
+        chunks[1][1] = Some(center);
+        Self { chunks }
+    }
+
+    /// Sets the neighbour at the given chunk offset from the center, each in `-1..=1`.
+    #[cfg(test)]
+    pub fn set_neighbor(&mut self, dx: i32, dz: i32, chunk: Option<&'a Chunk>) {
+        assert!((dx, dz) != (0, 0), "the center chunk cannot be replaced");
+        self.chunks[(dz + 1) as usize][(dx + 1) as usize] = chunk;
+    }
+
+    /// Returns the center chunk.
+    pub fn center(&self) -> &'a Chunk {
+        self.chunks[1][1].unwrap()
+    }
+
+    /// Returns the block at coordinates relative to the center chunk's origin.
+    ///
+    /// `x` and `z` may extend one chunk past either side of the center chunk. Blocks
+    /// in unloaded neighbours are reported as air.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockId {
+        let size = CHUNK_SIZE as i32;
+        let cx = x.div_euclid(size) + 1;
+        let cz = z.div_euclid(size) + 1;
+        if y < 0 || !(0..3).contains(&cx) || !(0..3).contains(&cz) {
+            return AIR;
+        }
+        match self.chunks[cz as usize][cx as usize] {
+            Some(chunk) => chunk.get(x.rem_euclid(size) as usize, y as usize, z.rem_euclid(size) as usize),
+            None => AIR,
+        }
+    }
+
+    /// Returns the light at coordinates relative to the center chunk's origin, with
+    /// the same range as `get`.
+    ///
+    /// Blocks below the world are dark; blocks in unloaded neighbours and above the
+    /// world are lit by the sky.
+    pub fn light(&self, x: i32, y: i32, z: i32) -> Light {
+        let size = CHUNK_SIZE as i32;
+        let cx = x.div_euclid(size) + 1;
+        let cz = z.div_euclid(size) + 1;
+        if y < 0 {
+            return Light::DARK;
+        }
+        if !(0..3).contains(&cx) || !(0..3).contains(&cz) {
+            return Light::SKY;
+        }
+        match self.chunks[cz as usize][cx as usize] {
+            Some(chunk) => chunk.light(x.rem_euclid(size) as usize, y as usize, z.rem_euclid(size) as usize),
+            None => Light::SKY,
+        }
+    }
+}
+
+/// An owned copy of a chunk and its loaded neighbours.
+///
+/// Chunks are shared with the `World`, so taking a snapshot does not copy any blocks;
+/// later edits to the world copy the edited chunk instead of changing the snapshot.
+#[derive(Clone)]
+pub struct NeighborhoodSnapshot {
+    chunks: [[Option<Arc<Chunk>>; 3]; 3],
+}
+
+impl NeighborhoodSnapshot {
+    /// Creates a snapshot from the chunks at each offset, indexed by `[dz + 1][dx + 1]`.
+    pub(super) fn new(chunks: [[Option<Arc<Chunk>>; 3]; 3]) -> Self {
+        assert!(chunks[1][1].is_some(), "the center chunk must be loaded");
+        Self { chunks }
+    }
+
+    /// Returns the position of the center chunk.
+    pub fn pos(&self) -> ChunkPos {
+        self.chunks[1][1].as_ref().unwrap().pos()
+    }
+
+    /// Returns a view of the snapshot.
+    pub fn neighborhood(&self) -> ChunkNeighborhood<'_> {
+        ChunkNeighborhood {
+            chunks: self.chunks.each_ref().map(|row| row.each_ref().map(Option::as_deref)),
+        }
+    }
+}
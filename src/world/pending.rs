@@ -0,0 +1,70 @@
+//! # Pending Blocks
+//! 
+//! This module defines the store of blocks that world generation placed into
+//! chunks other than the one being generated. Features such as trees may reach
+//! into neighbouring chunks that do not exist yet; their blocks wait here until
+//! the chunk is loaded.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::world::block::{BlockId, AIR};
+use crate::world::chunk::{local_coord, Chunk, ChunkPos, CHUNK_HEIGHT};
+
+/// A block waiting to be placed, in world coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PendingBlock {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub block: BlockId,
+}
+
+impl PendingBlock {
+    /// Places the block into `chunk`, which must contain it, if the position holds air.
+    /// Returns `true` if the block was placed.
+    ///
+    /// Generated features never overwrite blocks, so applying queued blocks gives the
+    /// same result no matter in which order chunks are generated.
+    pub fn place(&self, chunk: &mut Chunk) -> bool {
+        debug_assert_eq!(ChunkPos::from_block(self.x, self.z), chunk.pos());
+        if self.y < 0 || self.y >= CHUNK_HEIGHT as i32 {
+            return false;
+        }
+        let (x, y, z) = (local_coord(self.x), self.y as usize, local_coord(self.z));
+        if chunk.get(x, y, z) != AIR {
+            return false;
+        }
+        chunk.set(x, y, z, self.block);
+        true
+    }
+}
+
+/// Pending blocks grouped by the chunk that contains them.
+///
+/// Each block is stored once, so a feature that writes a block twice queues it once.
+#[derive(Clone, Debug, Default)]
+pub struct PendingBlocks {
+    chunks: HashMap<ChunkPos, HashSet<PendingBlock>>,
+}
+
+impl PendingBlocks {
+    /// Creates a new, empty `PendingBlocks`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a block.
+    pub fn push(&mut self, block: PendingBlock) {
+        self.chunks.entry(ChunkPos::from_block(block.x, block.z)).or_default().insert(block);
+    }
+
+    /// Returns the blocks queued for a chunk.
+    pub fn blocks(&self, pos: ChunkPos) -> impl Iterator<Item = &PendingBlock> {
+        self.chunks.get(&pos).into_iter().flatten()
+    }
+
+    /// Returns the positions of the chunks with queued blocks.
+    pub fn chunk_positions(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.chunks.keys().copied()
+    }
+}
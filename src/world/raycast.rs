@@ -0,0 +1,297 @@
+//! # Raycast
+//! 
+//! This module finds the first block along a ray, for targeting blocks from the
+//! camera. It steps from block to block with a DDA (digital differential analyzer)
+//! walk, so every block the ray passes through is visited exactly once, in order.
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::world::{BlockId, BlockView};
+
+/// A block hit by a ray.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaycastHit {
+    /// The world coordinates of the block.
+    pub pos: [i32; 3],
+    /// The outward normal of the face the ray entered through, or zero if the ray
+    /// started inside the block.
+    pub normal: [i32; 3],
+    /// The distance from the origin of the ray to the face, in blocks.
+    pub distance: f32,
+    /// The block that was hit.
+    pub block: BlockId,
+}
+
+impl RaycastHit {
+    /// Returns the position of the block next to the hit face, where a block placed
+    /// against it would go, or `None` if the ray started inside the block.
+    pub fn adjacent(&self) -> Option<[i32; 3]> {
+        if self.normal == [0; 3] {
+            return None;
+        }
+        Some([0, 1, 2].map(|axis| self.pos[axis] + self.normal[axis]))
+    }
+}
+
+/// Casts a ray from `origin` along `direction` and returns the first block within
+/// `reach` blocks that it hits. `hits` returns the height of a block's hit box, which
+/// sits on the bottom of the block, or `None` if rays pass through the block.
+///
+/// A block containing the origin is hit at distance zero. Returns `None` if the
+/// direction is zero, or if the ray or `reach` is not finite, since the walk would
+/// never end.
+pub fn raycast(
+    view: &impl BlockView,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    reach: f32,
+    hits: impl Fn(BlockId) -> Option<f32>,
+) -> Option<RaycastHit> {
+    if direction.magnitude2() == 0.0 || !reach.is_finite() {
+        return None;
+    }
+    let origin: [f32; 3] = origin.into();
+    let direction: [f32; 3] = direction.normalize().into();
+    if !origin.iter().chain(&direction).all(|v| v.is_finite()) {
+        return None;
+    }
+
+    let mut pos = origin.map(|v| v.floor() as i32);
+
+    // For each axis: the direction of a step, the distance along the ray between
+    // two block boundaries, and the distance to the next boundary.
+    let step = direction.map(|d| d.signum() as i32);
+    let delta = direction.map(|d| if d == 0.0 { f32::INFINITY } else { 1.0 / d.abs() });
+    let mut next = [0.0; 3];
+    for axis in 0..3 {
+        next[axis] = if direction[axis] > 0.0 {
+            (pos[axis] as f32 + 1.0 - origin[axis]) * delta[axis]
+        } else if direction[axis] < 0.0 {
+            (origin[axis] - pos[axis] as f32) * delta[axis]
+        } else {
+            f32::INFINITY
+        };
+    }
+
+    // Returns the hit on the block at `pos`, which the ray enters at `entry` through
+    // the face with `normal` and leaves at the nearest of `next`.
+    let hit = |pos: [i32; 3], entry: f32, normal: [i32; 3], next: &[f32; 3]| {
+        let block = view.block(pos[0], pos[1], pos[2]);
+        let height = hits(block)?;
+        let exit = next.iter().copied().fold(f32::INFINITY, f32::min);
+        let (distance, normal) = enter_box(origin, direction, pos, height, entry, normal, exit)?;
+        (distance <= reach).then_some(RaycastHit { pos, normal, distance, block })
+    };
+    if let Some(hit) = hit(pos, 0.0, [0; 3], &next) {
+        return Some(hit);
+    }
+
+    loop {
+        // Cross the nearest boundary.
+        let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+        let distance = next[axis];
+        if distance > reach {
+            return None;
+        }
+        pos[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        let mut normal = [0; 3];
+        normal[axis] = -step[axis];
+        if let Some(hit) = hit(pos, distance, normal, &next) {
+            return Some(hit);
+        }
+    }
+}
+
+/// Returns where a ray that is inside the block at `pos` from `entry` to `exit` first
+/// touches a hit box `height` blocks high on the bottom of the block, and the normal
+/// of the face it touches there. `normal` is the face the ray entered the block through.
+fn enter_box(origin: [f32; 3], direction: [f32; 3], pos: [i32; 3], height: f32, entry: f32, normal: [i32; 3], exit: f32) -> Option<(f32, [i32; 3])> {
+    if height >= 1.0 || origin[1] + direction[1] * entry - pos[1] as f32 <= height {
+        return Some((entry, normal));
+    }
+    // The ray entered above the box, so it can only come down onto its top.
+    if direction[1] >= 0.0 {
+        return None;
+    }
+    let top = (pos[1] as f32 + height - origin[1]) / direction[1];
+    (top <= exit).then_some((top, [0, 1, 0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::world::{World, AIR};
+
+    // IDs of the bundled block definitions.
+    const STONE: BlockId = 1;
+    const WATER: BlockId = 5;
+    const SLAB: BlockId = 15;
+
+    fn cast(world: &World, origin: [f32; 3], direction: [f32; 3], reach: f32) -> Option<RaycastHit> {
+        raycast(world, origin.into(), direction.into(), reach, |block| (block != AIR && block != WATER).then_some(1.0))
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let mut world = World::new();
+        world.set_block(5, 10, 0, STONE);
+        world.set_block(0, 10, -5, STONE);
+        world.set_block(0, 4, 0, STONE);
+        let origin = [0.5, 10.5, 0.5];
+
+        let cases = [
+            ([1.0, 0.0, 0.0], [5, 10, 0], [-1, 0, 0], 4.5),
+            ([0.0, 0.0, -1.0], [0, 10, -5], [0, 0, 1], 4.5),
+            ([0.0, -1.0, 0.0], [0, 4, 0], [0, 1, 0], 5.5),
+        ];
+        for (direction, pos, normal, distance) in cases {
+            let hit = cast(&world, origin, direction, 10.0).unwrap();
+            assert_eq!((hit.pos, hit.normal, hit.block), (pos, normal, STONE));
+            assert!((hit.distance - distance).abs() < 1e-5, "{:?}", hit);
+        }
+        assert_eq!(cast(&world, origin, [-1.0, 0.0, 0.0], 10.0), None);
+        assert_eq!(cast(&world, origin, [0.0, 1.0, 0.0], 10.0), None);
+    }
+
+    #[test]
+    fn rays_stop_at_the_reach() {
+        let mut world = World::new();
+        world.set_block(5, 10, 0, STONE);
+        let origin = [0.5, 10.5, 0.5];
+        assert_eq!(cast(&world, origin, [1.0, 0.0, 0.0], 4.4), None);
+        assert!(cast(&world, origin, [1.0, 0.0, 0.0], 4.5).is_some());
+        // The length of the direction does not matter.
+        assert!(cast(&world, origin, [20.0, 0.0, 0.0], 4.5).is_some());
+        assert_eq!(cast(&world, origin, [0.0, 0.0, 0.0], 10.0), None);
+    }
+
+    #[test]
+    fn rays_with_a_nan_direction_miss() {
+        let mut world = World::new();
+        world.set_block(5, 10, 0, STONE);
+        assert_eq!(cast(&world, [0.5, 10.5, 0.5], [f32::NAN, 0.0, 0.0], 10.0), None);
+        assert_eq!(cast(&world, [0.5, 10.5, 0.5], [1.0, f32::NAN, 0.0], 10.0), None);
+        assert_eq!(cast(&world, [f32::NAN, 10.5, 0.5], [1.0, 0.0, 0.0], 10.0), None);
+    }
+
+    #[test]
+    fn rays_with_an_infinite_reach_miss() {
+        let mut world = World::new();
+        world.set_block(5, 10, 0, STONE);
+        assert_eq!(cast(&world, [0.5, 10.5, 0.5], [1.0, 0.0, 0.0], f32::INFINITY), None);
+        assert_eq!(cast(&world, [0.5, 10.5, 0.5], [0.0, 1.0, 0.0], f32::NAN), None);
+    }
+
+    #[test]
+    fn diagonal_rays_hit_the_blocks_they_reach() {
+        // A ray at 45 degrees in the XZ plane passes exactly through block corners
+        // and still reaches the block on the diagonal.
+        let mut world = World::new();
+        world.set_block(3, 10, 3, STONE);
+        let hit = cast(&world, [0.5, 10.5, 0.5], [1.0, 0.0, 1.0], 10.0).unwrap();
+        assert_eq!(hit.pos, [3, 10, 3]);
+        assert!((hit.distance - 2.5 * 2f32.sqrt()).abs() < 1e-4);
+
+        // A shallow ray along +X that rises one block over four.
+        let mut world = World::new();
+        world.set_block(2, 11, 0, STONE);
+        world.set_block(3, 10, 0, STONE);
+        let hit = cast(&world, [0.0, 10.5, 0.5], [4.0, 1.0, 0.0], 10.0).unwrap();
+        assert_eq!((hit.pos, hit.normal), ([2, 11, 0], [0, -1, 0]));
+    }
+
+    /// A view of an empty world that records the blocks it is asked about.
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<[i32; 3]>>);
+
+    impl BlockView for Recorder {
+        fn block(&self, x: i32, y: i32, z: i32) -> BlockId {
+            self.0.borrow_mut().push([x, y, z]);
+            AIR
+        }
+    }
+
+    #[test]
+    fn rays_visit_every_block_they_cross_in_order() {
+        let reach = 8.0;
+        for i in 0..200 {
+            // Directions spread over the sphere, from origins inside a block.
+            let (a, b) = (i as f32 * 2.399_963, (i as f32 / 199.0) * 2.0 - 1.0);
+            let direction = Vector3::new(a.cos() * (1.0 - b * b).sqrt(), b, a.sin() * (1.0 - b * b).sqrt());
+            let origin = Point3::new(0.37 - i as f32 * 0.11, 10.61, -0.29 + i as f32 * 0.07);
+
+            let recorder = Recorder::default();
+            assert_eq!(raycast(&recorder, origin, direction, reach, |_| None), None);
+            let visited = recorder.0.into_inner();
+
+            // Each block is a face neighbour of the one before it.
+            for pair in visited.windows(2) {
+                let steps: i32 = (0..3).map(|axis| (pair[1][axis] - pair[0][axis]).abs()).sum();
+                assert_eq!(steps, 1, "{:?} after {:?}", pair[1], pair[0]);
+            }
+
+            // Marching along the ray in small steps finds the same blocks, in order.
+            let mut index = 0;
+            for t in 0..(reach * 1000.0) as i32 {
+                let point = origin + direction * (t as f32 / 1000.0);
+                let block = [point.x, point.y, point.z].map(|v| v.floor() as i32);
+                let found = visited[index..].iter().position(|&pos| pos == block);
+                index += found.unwrap_or_else(|| panic!("missed {:?} along {:?}", block, direction));
+            }
+        }
+    }
+
+    #[test]
+    fn half_blocks_are_hit_below_their_height() {
+        let mut world = World::new();
+        world.set_block(2, 10, 0, SLAB);
+        world.set_block(4, 10, 0, STONE);
+        let cast = |origin: [f32; 3], direction: [f32; 3]| {
+            raycast(&world, origin.into(), direction.into(), 10.0, |block| (block != AIR).then_some(if block == SLAB { 0.5 } else { 1.0 }))
+        };
+
+        // Rays over the slab pass it, rays into its side hit it.
+        assert_eq!(cast([0.5, 10.75, 0.5], [1.0, 0.0, 0.0]).unwrap().pos, [4, 10, 0]);
+        let hit = cast([0.5, 10.25, 0.5], [1.0, 0.0, 0.0]).unwrap();
+        assert_eq!((hit.pos, hit.normal), ([2, 10, 0], [-1, 0, 0]));
+
+        // Rays from above hit its top, even after entering its block through the side.
+        let hit = cast([2.5, 12.5, 0.5], [0.0, -1.0, 0.0]).unwrap();
+        assert_eq!((hit.pos, hit.normal), ([2, 10, 0], [0, 1, 0]));
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        let hit = cast([0.5, 11.5, 0.5], [1.0, -0.5, 0.0]).unwrap();
+        assert_eq!((hit.pos, hit.normal, hit.adjacent()), ([2, 10, 0], [0, 1, 0], Some([2, 11, 0])));
+    }
+
+    #[test]
+    fn ignored_blocks_are_passed_through() {
+        let mut world = World::new();
+        world.set_block(2, 10, 0, WATER);
+        world.set_block(3, 10, 0, STONE);
+        let hit = cast(&world, [0.5, 10.5, 0.5], [1.0, 0.0, 0.0], 10.0).unwrap();
+        assert_eq!(hit.pos, [3, 10, 0]);
+    }
+
+    #[test]
+    fn rays_starting_inside_a_block_hit_it() {
+        let mut world = World::new();
+        world.set_block(0, 10, 0, STONE);
+        let hit = cast(&world, [0.5, 10.5, 0.5], [1.0, 0.0, 0.0], 10.0).unwrap();
+        assert_eq!((hit.pos, hit.normal, hit.distance), ([0, 10, 0], [0, 0, 0], 0.0));
+        assert_eq!(hit.adjacent(), None);
+    }
+
+    #[test]
+    fn rays_work_across_negative_coordinates() {
+        let mut world = World::new();
+        world.set_block(-1, 10, -17, STONE);
+        let hit = cast(&world, [-0.5, 10.5, -0.5], [0.0, 0.0, -1.0], 20.0).unwrap();
+        assert_eq!((hit.pos, hit.normal), ([-1, 10, -17], [0, 0, 1]));
+        assert_eq!(hit.adjacent(), Some([-1, 10, -16]));
+        assert!((hit.distance - 15.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,405 @@
+//! # Block Registry
+//! 
+//! This module defines `BlockRegistry`, which maps numeric block IDs to their
+//! definitions. Definitions are data-driven and loaded from a RON file at startup.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::world::block::{BlockId, AIR};
+
+/// The texture names used by the faces of a block.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum BlockTextures {
+    /// The same texture on every face.
+    All(String),
+    /// Separate textures for the top, bottom and the four sides.
+    Sided {
+        top: String,
+        bottom: String,
+        side: String,
+    },
+}
+
+impl BlockTextures {
+    /// Returns the texture of the top face.
+    pub fn top(&self) -> &str {
+        match self {
+            BlockTextures::All(name) => name,
+            BlockTextures::Sided { top, .. } => top,
+        }
+    }
+
+    /// Returns the texture of the bottom face.
+    pub fn bottom(&self) -> &str {
+        match self {
+            BlockTextures::All(name) => name,
+            BlockTextures::Sided { bottom, .. } => bottom,
+        }
+    }
+
+    /// Returns the texture of the four side faces.
+    pub fn side(&self) -> &str {
+        match self {
+            BlockTextures::All(name) => name,
+            BlockTextures::Sided { side, .. } => side,
+        }
+    }
+
+    /// Returns every texture name referenced by the block.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        [self.top(), self.bottom(), self.side()].into_iter()
+    }
+}
+
+/// A colour multiplied into block faces that depends on the surroundings, such as the biome.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum BlockTint {
+    /// The textures are used as-is.
+    #[default]
+    None,
+    /// The top face is tinted with the grass colour.
+    Grass,
+    /// Every face is tinted with the foliage colour.
+    Foliage,
+}
+
+/// The definition of a block type.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockDef {
+    /// The numeric identifier stored in chunks.
+    pub id: BlockId,
+    /// The unique name of the block.
+    pub name: String,
+    /// Whether entities collide with the block.
+    #[serde(default = "default_true")]
+    pub solid: bool,
+    /// The height of the block, in blocks, e.g. 0.5 for half blocks. It sizes the
+    /// block's faces, collision box and hit box.
+    #[serde(default = "default_height")]
+    pub height: f32,
+    /// Whether the block lets light and sight through, so faces behind it are visible.
+    #[serde(default)]
+    pub transparent: bool,
+    /// Whether the block is drawn with alpha blending after opaque geometry, like water.
+    #[serde(default)]
+    pub translucent: bool,
+    /// The textures of the block faces.
+    pub textures: BlockTextures,
+    /// The tint applied to the block's textures.
+    #[serde(default)]
+    pub tint: BlockTint,
+    /// The block light level emitted by the block, from 0 to 15.
+    #[serde(default)]
+    pub light_emission: u8,
+    /// How long the block takes to break, in seconds.
+    #[serde(default)]
+    pub hardness: f32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_height() -> f32 {
+    1.0
+}
+
+impl BlockDef {
+    /// Returns `true` if the block fully hides the faces of its neighbours. Blocks
+    /// shorter than a full block never do.
+    pub fn is_opaque(&self) -> bool {
+        !self.transparent && self.height >= 1.0
+    }
+
+    /// Returns the definition of air.
+    fn air() -> Self {
+        Self {
+            id: AIR,
+            name: "air".to_string(),
+            solid: false,
+            height: 1.0,
+            transparent: true,
+            translucent: false,
+            textures: BlockTextures::All(String::new()),
+            tint: BlockTint::None,
+            light_emission: 0,
+            hardness: 0.0,
+        }
+    }
+}
+
+/// An error raised while loading or validating block definitions.
+#[derive(Debug)]
+pub enum RegistryError {
+    /// The definitions file could not be read.
+    Io(std::io::Error),
+    /// The definitions file is not valid RON.
+    Parse(ron::error::SpannedError),
+    /// A block uses the ID reserved for air.
+    ReservedId(String),
+    /// Two blocks share the same ID.
+    DuplicateId(BlockId),
+    /// Two blocks share the same name.
+    DuplicateName(String),
+    /// A block emits more light than the maximum light level.
+    InvalidLightEmission { block: String, level: u8 },
+    /// A block's collision box is not between 0 and 1 blocks tall.
+    InvalidHeight { block: String, height: f32 },
+    /// A block references a texture that does not exist.
+    MissingTexture { block: String, texture: String },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Io(err) => write!(f, "failed to read block definitions: {}", err),
+            RegistryError::Parse(err) => write!(f, "failed to parse block definitions: {}", err),
+            RegistryError::ReservedId(name) => write!(f, "block '{}' uses id {}, which is reserved for air", name, AIR),
+            RegistryError::DuplicateId(id) => write!(f, "block id {} is defined more than once", id),
+            RegistryError::DuplicateName(name) => write!(f, "block name '{}' is defined more than once", name),
+            RegistryError::InvalidLightEmission { block, level } => {
+                write!(f, "block '{}' emits light level {}, the maximum is 15", block, level)
+            }
+            RegistryError::InvalidHeight { block, height } => {
+                write!(f, "block '{}' has height {}, which is not between 0 and 1", block, height)
+            }
+            RegistryError::MissingTexture { block, texture } => {
+                write!(f, "block '{}' references missing texture '{}'", block, texture)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Maps block IDs to block definitions.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    blocks: Vec<Option<BlockDef>>,
+    ids: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+    /// Loads block definitions from a RON file and checks that every texture they
+    /// reference exists as a PNG file in `texture_dir`.
+    pub fn load(path: impl AsRef<Path>, texture_dir: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let source = std::fs::read_to_string(path).map_err(RegistryError::Io)?;
+        let registry = Self::parse(&source)?;
+
+        let mut textures = HashSet::new();
+        for entry in std::fs::read_dir(texture_dir).map_err(RegistryError::Io)? {
+            let path = entry.map_err(RegistryError::Io)?.path();
+            if path.extension().is_some_and(|ext| ext == "png") {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    textures.insert(stem.to_string());
+                }
+            }
+        }
+        registry.validate_textures(&textures)?;
+
+        Ok(registry)
+    }
+
+    /// Parses block definitions from a RON string.
+    pub fn parse(source: &str) -> Result<Self, RegistryError> {
+        let defs: Vec<BlockDef> = ron::from_str(source).map_err(RegistryError::Parse)?;
+        Self::from_defs(defs)
+    }
+
+    /// Builds a registry from a list of definitions. Air is registered implicitly.
+    pub fn from_defs(defs: Vec<BlockDef>) -> Result<Self, RegistryError> {
+        let mut registry = Self {
+            blocks: vec![Some(BlockDef::air())],
+            ids: HashMap::from([("air".to_string(), AIR)]),
+        };
+
+        for def in defs {
+            if def.id == AIR {
+                return Err(RegistryError::ReservedId(def.name));
+            }
+            if def.light_emission > 15 {
+                return Err(RegistryError::InvalidLightEmission { block: def.name, level: def.light_emission });
+            }
+            if !(0.0..=1.0).contains(&def.height) {
+                return Err(RegistryError::InvalidHeight { block: def.name, height: def.height });
+            }
+            if registry.ids.contains_key(&def.name) {
+                return Err(RegistryError::DuplicateName(def.name));
+            }
+
+            let index = def.id as usize;
+            if index >= registry.blocks.len() {
+                registry.blocks.resize(index + 1, None);
+            }
+            if registry.blocks[index].is_some() {
+                return Err(RegistryError::DuplicateId(def.id));
+            }
+
+            registry.ids.insert(def.name.clone(), def.id);
+            registry.blocks[index] = Some(def);
+        }
+
+        Ok(registry)
+    }
+
+    /// Checks that every texture referenced by a block is in `available`.
+    pub fn validate_textures(&self, available: &HashSet<String>) -> Result<(), RegistryError> {
+        for def in self.iter().filter(|def| def.id != AIR) {
+            if let Some(texture) = def.textures.names().find(|name| !available.contains(*name)) {
+                return Err(RegistryError::MissingTexture {
+                    block: def.name.clone(),
+                    texture: texture.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the definition of a block.
+    ///
+    /// Unknown IDs resolve to air.
+    pub fn get(&self, id: BlockId) -> &BlockDef {
+        self.blocks
+            .get(id as usize)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| self.blocks[AIR as usize].as_ref().unwrap())
+    }
+
+    /// Returns the ID of the block with the given name.
+    pub fn id_of(&self, name: &str) -> Option<BlockId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Returns `true` if the block fully hides the faces of its neighbours.
+    pub fn is_opaque(&self, id: BlockId) -> bool {
+        self.get(id).is_opaque()
+    }
+
+    /// Returns an iterator over all registered blocks, including air.
+    pub fn iter(&self) -> impl Iterator<Item = &BlockDef> {
+        self.blocks.iter().flatten()
+    }
+
+    /// Returns the registry defined by the bundled `assets/blocks.ron`.
+    #[cfg(test)]
+    pub fn builtin() -> Self {
+        Self::parse(include_str!("../../assets/blocks.ron")).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn textures(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_definitions_with_defaults() {
+        let registry = BlockRegistry::parse(r#"[
+            (id: 1, name: "stone", textures: All("stone"), hardness: 1.5),
+            (id: 4, name: "glass", transparent: true, textures: All("glass")),
+            (id: 6, name: "water", solid: false, transparent: true, translucent: true, textures: All("water")),
+            (id: 5, name: "lamp", textures: Sided(top: "lamp_top", bottom: "lamp_top", side: "lamp"), light_emission: 15),
+            (id: 7, name: "slab", height: 0.5, textures: All("stone")),
+        ]"#).unwrap();
+
+        let stone = registry.get(1);
+        assert_eq!(stone.name, "stone");
+        assert!(stone.solid && stone.is_opaque());
+        assert_eq!(stone.hardness, 1.5);
+        assert_eq!(stone.height, 1.0);
+        assert_eq!(registry.get(7).height, 0.5);
+        assert!(!registry.is_opaque(7));
+
+        assert!(!registry.is_opaque(registry.id_of("glass").unwrap()));
+        assert!(!registry.get(4).translucent);
+
+        let water = registry.get(6);
+        assert!(!water.solid && !water.is_opaque() && water.translucent);
+
+        assert_eq!(registry.get(5).light_emission, 15);
+        assert_eq!(registry.get(5).textures.top(), "lamp_top");
+        assert_eq!(registry.get(5).textures.side(), "lamp");
+    }
+
+    #[test]
+    fn air_is_implicit_and_unknown_ids_resolve_to_air() {
+        let registry = BlockRegistry::parse("[]").unwrap();
+        assert_eq!(registry.id_of("air"), Some(AIR));
+        assert!(!registry.get(AIR).solid);
+        assert!(!registry.is_opaque(AIR));
+        assert_eq!(registry.get(42).id, AIR);
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let result = BlockRegistry::parse(r#"[
+            (id: 1, name: "stone", textures: All("stone")),
+            (id: 2, name: "stone", textures: All("stone")),
+        ]"#);
+        assert!(matches!(result, Err(RegistryError::DuplicateName(name)) if name == "stone"));
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let result = BlockRegistry::parse(r#"[
+            (id: 1, name: "stone", textures: All("stone")),
+            (id: 1, name: "dirt", textures: All("dirt")),
+        ]"#);
+        assert!(matches!(result, Err(RegistryError::DuplicateId(1))));
+    }
+
+    #[test]
+    fn rejects_the_air_id() {
+        let result = BlockRegistry::parse(r#"[(id: 0, name: "void", textures: All("void"))]"#);
+        assert!(matches!(result, Err(RegistryError::ReservedId(_))));
+    }
+
+    #[test]
+    fn rejects_too_much_light() {
+        let result = BlockRegistry::parse(r#"[(id: 1, name: "sun", textures: All("sun"), light_emission: 16)]"#);
+        assert!(matches!(result, Err(RegistryError::InvalidLightEmission { level: 16, .. })));
+    }
+
+    #[test]
+    fn rejects_blocks_taller_than_a_block() {
+        let result = BlockRegistry::parse(r#"[(id: 1, name: "pillar", textures: All("stone"), height: 1.5)]"#);
+        assert!(matches!(result, Err(RegistryError::InvalidHeight { height, .. }) if height == 1.5));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        assert!(matches!(BlockRegistry::parse("[(id: 1,"), Err(RegistryError::Parse(_))));
+    }
+
+    #[test]
+    fn reports_missing_textures() {
+        let registry = BlockRegistry::parse(r#"[
+            (id: 3, name: "grass", textures: Sided(top: "grass_top", bottom: "dirt", side: "grass_side")),
+        ]"#).unwrap();
+
+        assert!(registry.validate_textures(&textures(&["grass_top", "dirt", "grass_side"])).is_ok());
+
+        let result = registry.validate_textures(&textures(&["grass_top", "dirt"]));
+        match result {
+            Err(RegistryError::MissingTexture { block, texture }) => {
+                assert_eq!(block, "grass");
+                assert_eq!(texture, "grass_side");
+            }
+            other => panic!("expected a missing texture error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bundled_definitions_load() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let registry = BlockRegistry::load(root.join("assets/blocks.ron"), root.join("assets/textures/blocks")).unwrap();
+        assert!(registry.id_of("stone").is_some());
+    }
+}
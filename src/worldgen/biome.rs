@@ -0,0 +1,251 @@
+//! # Biome Selection
+//! 
+//! This module picks biomes from temperature and humidity noise maps and blends
+//! the parameters of neighbouring biomes, so terrain height and colours change
+//! smoothly across biome borders.
+
+use crate::world::{quantize_color, Biome, ChunkPos, ColumnInfo, CHUNK_SIZE};
+use crate::worldgen::noise::{mix_seed, FractalNoise, FractalParams};
+
+/// Salt of the temperature noise seed.
+const TEMPERATURE_SALT: u64 = 2;
+
+/// Salt of the humidity noise seed.
+const HUMIDITY_SALT: u64 = 3;
+
+/// The distance between the points at which biomes are sampled for blending, in blocks.
+const BLEND_SPACING: i32 = 4;
+
+/// The radius over which neighbouring biomes are blended, in blocks.
+const BLEND_RADIUS: i32 = 12;
+
+/// How a biome shapes the terrain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiomeParams {
+    /// The name of the topmost block.
+    pub surface: &'static str,
+    /// The name of the blocks between the surface and the stone.
+    pub soil: &'static str,
+    /// The offset of the average terrain height from the world's base height.
+    pub height_offset: f64,
+    /// The maximum distance of the terrain from its average height.
+    pub height_variation: f64,
+    /// The chance that a feature cell grows a tree.
+    pub trees: f64,
+    /// The chance that a feature cell holds a boulder, if it has no tree.
+    pub boulders: f64,
+}
+
+impl BiomeParams {
+    /// Returns the parameters of a biome.
+    pub fn of(biome: Biome) -> Self {
+        let (surface, soil, height_offset, height_variation, trees, boulders) = match biome {
+            Biome::Desert => ("sand", "sand", 2.0, 10.0, 0.0, 0.02),
+            Biome::Jungle => ("grass", "dirt", 6.0, 28.0, 0.9, 0.0),
+            Biome::Plains => ("grass", "dirt", 2.0, 12.0, 0.1, 0.04),
+            Biome::Forest => ("grass", "dirt", 4.0, 24.0, 0.7, 0.03),
+            Biome::Tundra => ("snow", "dirt", 4.0, 16.0, 0.0, 0.08),
+            Biome::Taiga => ("grass", "dirt", 8.0, 36.0, 0.6, 0.1),
+        };
+        Self {
+            surface,
+            soil,
+            height_offset,
+            height_variation,
+            trees,
+            boulders,
+        }
+    }
+}
+
+/// Returns the biome of a climate. Both values are noise samples in `[-1, 1]`.
+pub fn classify(temperature: f64, humidity: f64) -> Biome {
+    let wet = humidity > 0.0;
+    if temperature > 0.15 {
+        if wet { Biome::Jungle } else { Biome::Desert }
+    } else if temperature < -0.15 {
+        if wet { Biome::Taiga } else { Biome::Tundra }
+    } else if wet {
+        Biome::Forest
+    } else {
+        Biome::Plains
+    }
+}
+
+/// The weights of the biomes around a column, summing to one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BiomeBlend {
+    weights: [f64; Biome::ALL.len()],
+}
+
+impl BiomeBlend {
+    /// Returns the biome with the largest weight.
+    pub fn dominant(&self) -> Biome {
+        Biome::ALL
+            .into_iter()
+            .max_by(|a, b| self.weights[a.index()].total_cmp(&self.weights[b.index()]))
+            .unwrap()
+    }
+
+    /// Returns the weighted average of a per-biome value.
+    pub fn mix(&self, value: impl Fn(Biome) -> f64) -> f64 {
+        Biome::ALL.into_iter().map(|biome| self.weights[biome.index()] * value(biome)).sum()
+    }
+
+    /// Returns the column data of the blend: its dominant biome and blended colours.
+    pub fn column_info(&self) -> ColumnInfo {
+        let color = |color: fn(Biome) -> [f32; 3]| {
+            let channel = |i: usize| self.mix(|biome| color(biome)[i] as f64) as f32;
+            quantize_color([channel(0), channel(1), channel(2)])
+        };
+        ColumnInfo {
+            biome: self.dominant(),
+            grass_color: color(Biome::grass_color),
+            foliage_color: color(Biome::foliage_color),
+        }
+    }
+}
+
+/// Chooses the biome of every column from temperature and humidity noise.
+pub struct BiomeSource {
+    temperature: FractalNoise,
+    humidity: FractalNoise,
+}
+
+impl BiomeSource {
+    /// Creates a new `BiomeSource` seeded by `seed`.
+    pub fn new(seed: u64, temperature: FractalParams, humidity: FractalParams) -> Self {
+        Self {
+            temperature: FractalNoise::new(mix_seed(seed, TEMPERATURE_SALT), temperature),
+            humidity: FractalNoise::new(mix_seed(seed, HUMIDITY_SALT), humidity),
+        }
+    }
+
+    /// Returns the unblended biome of a world column.
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let (x, z) = (x as f64, z as f64);
+        classify(self.temperature.sample2(x, z), self.humidity.sample2(x, z))
+    }
+
+    /// Returns the biome blend of every column of a chunk, indexed by `z * CHUNK_SIZE + x`.
+    ///
+    /// Biomes are sampled on a grid aligned to world coordinates and weighted by their
+    /// distance to the column, so a column's blend does not depend on the chunk that
+    /// requested it.
+    pub fn blend_chunk(&self, pos: ChunkPos) -> Vec<BiomeBlend> {
+        let (origin_x, origin_z) = pos.origin();
+        let size = CHUNK_SIZE as i32;
+
+        // Sample every grid point that lies within the blend radius of the chunk.
+        let min = |origin: i32| (origin - BLEND_RADIUS).div_euclid(BLEND_SPACING);
+        let max = |origin: i32| (origin + size - 1 + BLEND_RADIUS).div_euclid(BLEND_SPACING) + 1;
+        let (min_x, min_z) = (min(origin_x), min(origin_z));
+        let width = (max(origin_x) - min_x + 1) as usize;
+        let depth = (max(origin_z) - min_z + 1) as usize;
+        let mut grid = Vec::with_capacity(width * depth);
+        for gz in 0..depth as i32 {
+            for gx in 0..width as i32 {
+                grid.push(self.biome_at((min_x + gx) * BLEND_SPACING, (min_z + gz) * BLEND_SPACING));
+            }
+        }
+
+        let radius_sq = (BLEND_RADIUS * BLEND_RADIUS) as f64;
+        let mut blends = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+        for z in origin_z..origin_z + size {
+            for x in origin_x..origin_x + size {
+                let mut weights = [0.0; Biome::ALL.len()];
+                for (i, biome) in grid.iter().enumerate() {
+                    let gx = (min_x + (i % width) as i32) * BLEND_SPACING;
+                    let gz = (min_z + (i / width) as i32) * BLEND_SPACING;
+                    let distance_sq = ((gx - x).pow(2) + (gz - z).pow(2)) as f64;
+                    if distance_sq < radius_sq {
+                        weights[biome.index()] += (1.0 - distance_sq / radius_sq).powi(2);
+                    }
+                }
+
+                let total: f64 = weights.iter().sum();
+                blends.push(BiomeBlend {
+                    weights: weights.map(|w| w / total),
+                });
+            }
+        }
+        blends
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn source() -> BiomeSource {
+        let params = FractalParams {
+            octaves: 3,
+            frequency: 1.0 / 256.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        };
+        BiomeSource::new(99, params, params)
+    }
+
+    #[test]
+    fn climates_map_to_biomes() {
+        assert_eq!(classify(0.5, -0.5), Biome::Desert);
+        assert_eq!(classify(0.5, 0.5), Biome::Jungle);
+        assert_eq!(classify(0.0, -0.5), Biome::Plains);
+        assert_eq!(classify(0.0, 0.5), Biome::Forest);
+        assert_eq!(classify(-0.5, -0.5), Biome::Tundra);
+        assert_eq!(classify(-0.5, 0.5), Biome::Taiga);
+    }
+
+    #[test]
+    fn blend_weights_sum_to_one() {
+        let source = source();
+        for blend in source.blend_chunk(ChunkPos::new(-3, 7)) {
+            assert!((blend.weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            assert_eq!(blend.mix(|_| 2.5), 2.5);
+        }
+    }
+
+    #[test]
+    fn large_areas_contain_several_biomes() {
+        let source = source();
+        let biomes: HashSet<_> = (-40..40)
+            .flat_map(|z| (-40..40).map(move |x| (x * 32, z * 32)))
+            .map(|(x, z)| source.biome_at(x, z))
+            .collect();
+        assert!(biomes.len() >= 4, "{:?}", biomes);
+    }
+
+    #[test]
+    fn blending_is_smooth_across_chunk_borders() {
+        let source = source();
+        let height_offset = |blend: &BiomeBlend| blend.mix(|biome| BiomeParams::of(biome).height_offset);
+        let assert_smooth = |a: &BiomeBlend, b: &BiomeBlend| {
+            assert!((height_offset(a) - height_offset(b)).abs() < 1.0);
+            let (ca, cb) = (a.column_info().grass_color, b.column_info().grass_color);
+            for i in 0..3 {
+                assert!(ca[i].abs_diff(cb[i]) <= 8, "{:?} -> {:?}", ca, cb);
+            }
+        };
+        let last = CHUNK_SIZE - 1;
+        let mut biomes = HashSet::new();
+
+        // Walk along the border between chunk columns -1 and 0.
+        for cz in -64..64 {
+            let west = source.blend_chunk(ChunkPos::new(-1, cz));
+            let east = source.blend_chunk(ChunkPos::new(0, cz));
+            for z in 0..CHUNK_SIZE {
+                let (a, b) = (&west[z * CHUNK_SIZE + last], &east[z * CHUNK_SIZE]);
+                assert_smooth(a, b);
+                if z < last {
+                    assert_smooth(b, &east[(z + 1) * CHUNK_SIZE]);
+                }
+                biomes.insert(b.dominant());
+            }
+        }
+
+        assert!(biomes.len() > 1, "the walk should cross biome borders");
+    }
+}
@@ -0,0 +1,326 @@
+//! # Cave Carving
+//! 
+//! This module defines the generation stage that carves caves out of the terrain
+//! using 3D noise: large "cheese" caverns where one noise field is high, and long
+//! "spaghetti" tunnels where two noise fields are both close to zero.
+
+use crate::world::{BlockId, BlockRegistry, Chunk, ChunkPos, PendingBlocks, AIR, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::worldgen::noise::{mix_seed, FractalNoise, FractalParams};
+use crate::worldgen::GenerationStage;
+
+/// Salt of the cheese cave noise seed.
+const CHEESE_SALT: u64 = 5;
+
+/// Salts of the two spaghetti tunnel noise seeds.
+const SPAGHETTI_SALTS: [u64; 2] = [6, 7];
+
+/// The distance between the points at which the cave noise is sampled, in blocks.
+/// Values in between are interpolated.
+const CELL_SIZE: usize = 4;
+
+/// The parameters of the cave stage.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CaveSettings {
+    /// The lowest height that is carved.
+    pub min_height: i32,
+    /// The noise of the cheese caverns.
+    pub cheese_noise: FractalParams,
+    /// Cheese caverns are carved where their noise exceeds this value.
+    pub cheese_threshold: f64,
+    /// How far below the surface cheese caverns start, so they rarely break through it.
+    pub cheese_depth: i32,
+    /// The noise of the two fields whose zero crossings form spaghetti tunnels.
+    pub spaghetti_noise: FractalParams,
+    /// The radius of spaghetti tunnels, in noise units.
+    pub spaghetti_radius: f64,
+    /// The factor applied to heights before sampling the noise. Values above one make
+    /// caves wider than they are tall.
+    pub vertical_scale: f64,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            min_height: 4,
+            cheese_noise: FractalParams {
+                octaves: 2,
+                frequency: 1.0 / 64.0,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            cheese_threshold: 0.3,
+            cheese_depth: 10,
+            spaghetti_noise: FractalParams {
+                octaves: 2,
+                frequency: 1.0 / 96.0,
+                lacunarity: 2.0,
+                persistence: 0.4,
+            },
+            spaghetti_radius: 0.04,
+            vertical_scale: 2.0,
+        }
+    }
+}
+
+/// Carves cheese caverns and spaghetti tunnels into the opaque blocks of a chunk.
+pub struct CaveCarver {
+    settings: CaveSettings,
+    cheese: FractalNoise,
+    spaghetti: [FractalNoise; 2],
+    /// Whether each block ID must keep the block beneath it, like water and plants.
+    needs_support: Vec<bool>,
+    opaque: Vec<bool>,
+}
+
+impl CaveCarver {
+    /// Creates a new `CaveCarver` seeded by `seed`.
+    pub fn new(seed: u64, registry: &BlockRegistry, settings: CaveSettings) -> Self {
+        let count = registry.iter().map(|def| def.id as usize + 1).max().unwrap_or(1);
+        let mut needs_support = vec![false; count];
+        let mut opaque = vec![false; count];
+        for def in registry.iter() {
+            needs_support[def.id as usize] = def.id != AIR && !def.is_opaque();
+            opaque[def.id as usize] = def.is_opaque();
+        }
+
+        Self {
+            settings,
+            cheese: FractalNoise::new(mix_seed(seed, CHEESE_SALT), settings.cheese_noise),
+            spaghetti: SPAGHETTI_SALTS.map(|salt| FractalNoise::new(mix_seed(seed, salt), settings.spaghetti_noise)),
+            needs_support,
+            opaque,
+        }
+    }
+
+    fn is_opaque(&self, block: BlockId) -> bool {
+        self.opaque.get(block as usize).copied().unwrap_or(false)
+    }
+
+    fn needs_support(&self, block: BlockId) -> bool {
+        self.needs_support.get(block as usize).copied().unwrap_or(false)
+    }
+
+    /// Returns `true` if the cave noise carves the given position. `depth` is the
+    /// distance below the column's surface.
+    fn is_cave(&self, cheese: &NoiseGrid, tunnels: &[NoiseGrid; 2], x: usize, y: usize, z: usize, depth: i32) -> bool {
+        if depth >= self.settings.cheese_depth && cheese.get(x, y, z) > self.settings.cheese_threshold {
+            return true;
+        }
+        let (a, b) = (tunnels[0].get(x, y, z), tunnels[1].get(x, y, z));
+        a * a + b * b < self.settings.spaghetti_radius * self.settings.spaghetti_radius
+    }
+}
+
+impl GenerationStage for CaveCarver {
+    fn name(&self) -> &'static str {
+        "caves"
+    }
+
+    fn apply(&self, chunk: &mut Chunk, _pending: &mut PendingBlocks) {
+        let pos = chunk.pos();
+        let scale = self.settings.vertical_scale;
+        let cheese = NoiseGrid::new(pos, |x, y, z| self.cheese.sample3(x, y * scale, z));
+        let tunnels = [0, 1].map(|i| NoiseGrid::new(pos, |x, y, z| self.spaghetti[i].sample3(x, y * scale, z)));
+        let min_height = self.settings.min_height.max(0) as usize;
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let Some(surface) = (0..CHUNK_HEIGHT).rev().find(|&y| self.is_opaque(chunk.get(x, y, z))) else {
+                    continue;
+                };
+
+                // Carve from the top down so the support check sees the final block above.
+                for y in (min_height..=surface).rev() {
+                    if !self.is_opaque(chunk.get(x, y, z)) || self.needs_support(chunk.get(x, y + 1, z)) {
+                        continue;
+                    }
+                    if self.is_cave(&cheese, &tunnels, x, y, z, (surface - y) as i32) {
+                        chunk.set(x, y, z, AIR);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A noise field sampled every `CELL_SIZE` blocks over a chunk and trilinearly
+/// interpolated in between, which is much cheaper than sampling every block.
+struct NoiseGrid {
+    values: Vec<f64>,
+}
+
+impl NoiseGrid {
+    const WIDTH: usize = CHUNK_SIZE / CELL_SIZE + 1;
+    const HEIGHT: usize = CHUNK_HEIGHT / CELL_SIZE + 1;
+
+    /// Samples `noise(x, y, z)` at the grid points of a chunk, in world coordinates.
+    fn new(pos: ChunkPos, noise: impl Fn(f64, f64, f64) -> f64) -> Self {
+        let (origin_x, origin_z) = pos.origin();
+        let mut values = Vec::with_capacity(Self::WIDTH * Self::WIDTH * Self::HEIGHT);
+        for gy in 0..Self::HEIGHT {
+            for gz in 0..Self::WIDTH {
+                for gx in 0..Self::WIDTH {
+                    let x = origin_x + (gx * CELL_SIZE) as i32;
+                    let z = origin_z + (gz * CELL_SIZE) as i32;
+                    values.push(noise(x as f64, (gy * CELL_SIZE) as f64, z as f64));
+                }
+            }
+        }
+        Self { values }
+    }
+
+    fn at(&self, gx: usize, gy: usize, gz: usize) -> f64 {
+        self.values[(gy * Self::WIDTH + gz) * Self::WIDTH + gx]
+    }
+
+    /// Returns the interpolated noise at chunk-local block coordinates.
+    fn get(&self, x: usize, y: usize, z: usize) -> f64 {
+        let (gx, gy, gz) = (x / CELL_SIZE, y / CELL_SIZE, z / CELL_SIZE);
+        let t = |v: usize| (v % CELL_SIZE) as f64 / CELL_SIZE as f64;
+        let (tx, ty, tz) = (t(x), t(y), t(z));
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+
+        let plane = |gy: usize| {
+            lerp(
+                tz,
+                lerp(tx, self.at(gx, gy, gz), self.at(gx + 1, gy, gz)),
+                lerp(tx, self.at(gx, gy, gz + 1), self.at(gx + 1, gy, gz + 1)),
+            )
+        };
+        lerp(ty, plane(gy), plane(gy + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 77;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::builtin()
+    }
+
+    /// Returns a chunk filled with stone up to `height`.
+    fn stone_chunk(pos: ChunkPos, height: usize) -> Chunk {
+        let stone = registry().id_of("stone").unwrap();
+        let mut chunk = Chunk::new(pos);
+        for y in 0..=height {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, stone);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn carve(carver: &CaveCarver, mut chunk: Chunk) -> Chunk {
+        carver.apply(&mut chunk, &mut PendingBlocks::new());
+        chunk
+    }
+
+    /// Returns the positions of the air blocks at or below `height`.
+    fn air_blocks(chunk: &Chunk, height: usize) -> Vec<(usize, usize, usize)> {
+        let mut air = Vec::new();
+        for y in 0..=height {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if chunk.get(x, y, z) == AIR {
+                        air.push((x, y, z));
+                    }
+                }
+            }
+        }
+        air
+    }
+
+    /// Carves the chunks around the origin and returns the carved fraction of the stone.
+    fn carved_fraction(carver: &CaveCarver) -> f64 {
+        let mut carved = 0;
+        for cz in -2..2 {
+            for cx in -2..2 {
+                carved += air_blocks(&carve(carver, stone_chunk(ChunkPos::new(cx, cz), 100)), 100).len();
+            }
+        }
+        carved as f64 / (16 * CHUNK_SIZE * CHUNK_SIZE * 101) as f64
+    }
+
+    #[test]
+    fn carving_is_deterministic() {
+        let (a, b) = (CaveCarver::new(SEED, &registry(), CaveSettings::default()), CaveCarver::new(SEED, &registry(), CaveSettings::default()));
+        let c = CaveCarver::new(SEED + 1, &registry(), CaveSettings::default());
+        let pos = ChunkPos::new(-3, 5);
+
+        let carved = air_blocks(&carve(&a, stone_chunk(pos, 100)), 100);
+        assert!(!carved.is_empty());
+        assert_eq!(air_blocks(&carve(&b, stone_chunk(pos, 100)), 100), carved);
+        assert_ne!(air_blocks(&carve(&c, stone_chunk(pos, 100)), 100), carved);
+    }
+
+    #[test]
+    fn caves_take_a_modest_share_of_the_underground() {
+        let fraction = carved_fraction(&CaveCarver::new(SEED, &registry(), CaveSettings::default()));
+        assert!((0.01..0.2).contains(&fraction), "carved {:.3}", fraction);
+    }
+
+    #[test]
+    fn cheese_and_spaghetti_carve_independently() {
+        let tunnels_only = CaveSettings {
+            cheese_threshold: f64::INFINITY,
+            ..CaveSettings::default()
+        };
+        let caverns_only = CaveSettings {
+            spaghetti_radius: 0.0,
+            ..CaveSettings::default()
+        };
+
+        assert!(carved_fraction(&CaveCarver::new(SEED, &registry(), tunnels_only)) > 0.0);
+        assert!(carved_fraction(&CaveCarver::new(SEED, &registry(), caverns_only)) > 0.0);
+    }
+
+    #[test]
+    fn caves_stay_above_the_minimum_height() {
+        let carver = CaveCarver::new(SEED, &registry(), CaveSettings::default());
+        for cx in -4..4 {
+            let chunk = carve(&carver, stone_chunk(ChunkPos::new(cx, 0), 100));
+            assert!(air_blocks(&chunk, 100).iter().all(|&(_, y, _)| y >= 4));
+        }
+    }
+
+    #[test]
+    fn water_is_never_undermined() {
+        let registry = registry();
+        let water = registry.id_of("water").unwrap();
+        let carver = CaveCarver::new(SEED, &registry, CaveSettings::default());
+
+        for cx in -4..4 {
+            let mut chunk = stone_chunk(ChunkPos::new(cx, 1), 60);
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, 61, z, water);
+                }
+            }
+            let chunk = carve(&carver, chunk);
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    assert_ne!(chunk.get(x, 60, z), AIR);
+                    assert_eq!(chunk.get(x, 61, z), water);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn noise_grid_matches_samples_on_grid_points() {
+        let pos = ChunkPos::new(2, -1);
+        let noise = FractalNoise::new(3, CaveSettings::default().cheese_noise);
+        let grid = NoiseGrid::new(pos, |x, y, z| noise.sample3(x, y, z));
+        let (origin_x, origin_z) = pos.origin();
+
+        for (x, y, z) in [(0, 0, 0), (4, 8, 12), (12, 124, 4)] {
+            let expected = noise.sample3((origin_x + x as i32) as f64, y as f64, (origin_z + z as i32) as f64);
+            assert_eq!(grid.get(x, y, z), expected);
+        }
+    }
+}
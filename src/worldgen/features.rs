@@ -0,0 +1,418 @@
+//! # Features
+//! 
+//! This module defines the generation stage that decorates the terrain with trees,
+//! boulders and small prefab structures.
+//! 
+//! The world is divided into square feature cells, each holding at most one feature
+//! whose footprint stays inside the cell. Cells are not aligned to chunks, so most
+//! features reach into neighbouring chunks: those blocks are queued as pending
+//! blocks. Features only ever replace air, and never overlap each other, so the
+//! world looks the same no matter in which order its chunks are generated.
+
+use crate::world::{
+    local_coord, BlockId, BlockRegistry, Chunk, ChunkPos, PendingBlock, PendingBlocks, AIR, CHUNK_HEIGHT, CHUNK_SIZE,
+};
+use crate::worldgen::biome::BiomeParams;
+use crate::worldgen::noise::{mix_seed, position_seed, SplitMix64};
+use crate::worldgen::{GenerationStage, GeneratorError};
+
+/// Salt of the feature placement seed.
+const FEATURE_SALT: u64 = 9;
+
+/// The largest horizontal distance of a feature's blocks from its anchor column.
+const MAX_RADIUS: i32 = 2;
+
+/// A ruined hut, as layers from the bottom up. Each layer is a list of rows along
+/// the Z axis, and each character a block along the X axis: `c` is cobblestone,
+/// `l` is a log and `.` leaves the block untouched.
+const RUIN: [[&str; 5]; 4] = [
+    ["ccccc", "c...c", "c...c", "c...c", "cc.cc"],
+    ["l.c.l", "c...c", ".....", "c...c", "l...l"],
+    ["l...l", ".....", ".....", ".....", "l...l"],
+    ["cc.cc", "c...c", ".....", "....c", "cc.cc"],
+];
+
+/// The parameters of feature placement.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FeatureSettings {
+    /// The edge length of a feature cell, in blocks.
+    pub cell_size: i32,
+    /// The chance that a feature cell without a tree or boulder holds a ruin.
+    pub ruin_chance: f64,
+}
+
+impl Default for FeatureSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 7,
+            ruin_chance: 0.01,
+        }
+    }
+}
+
+/// The kinds of features.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Feature {
+    Tree,
+    Boulder,
+    Ruin,
+}
+
+/// The blocks placed by the feature generator.
+#[derive(Copy, Clone, Debug)]
+struct FeatureBlocks {
+    grass: BlockId,
+    log: BlockId,
+    leaves: BlockId,
+    cobblestone: BlockId,
+}
+
+/// Writes the blocks of a feature into the chunk being generated, or queues them
+/// if they belong to another chunk.
+struct Placer<'a> {
+    chunk: &'a mut Chunk,
+    pending: &'a mut PendingBlocks,
+}
+
+impl Placer<'_> {
+    fn place(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        let block = PendingBlock { x, y, z, block };
+        if ChunkPos::from_block(x, z) == self.chunk.pos() {
+            block.place(self.chunk);
+        } else {
+            self.pending.push(block);
+        }
+    }
+}
+
+/// Places trees, boulders and ruins on the surface of each chunk.
+pub struct FeatureGenerator {
+    settings: FeatureSettings,
+    blocks: FeatureBlocks,
+    seed: u64,
+    opaque: Vec<bool>,
+}
+
+impl FeatureGenerator {
+    /// Creates a new `FeatureGenerator`, resolving the blocks it places from the registry.
+    pub fn new(seed: u64, registry: &BlockRegistry, settings: FeatureSettings) -> Result<Self, GeneratorError> {
+        assert!(settings.cell_size > 2 * MAX_RADIUS, "feature cells must fit a feature");
+        let block = |name: &str| registry.id_of(name).ok_or_else(|| GeneratorError::MissingBlock(name.to_string()));
+        let blocks = FeatureBlocks {
+            grass: block("grass")?,
+            log: block("log")?,
+            leaves: block("leaves")?,
+            cobblestone: block("cobblestone")?,
+        };
+
+        let count = registry.iter().map(|def| def.id as usize + 1).max().unwrap_or(1);
+        let mut opaque = vec![false; count];
+        for def in registry.iter() {
+            opaque[def.id as usize] = def.is_opaque();
+        }
+
+        Ok(Self {
+            settings,
+            blocks,
+            seed: mix_seed(seed, FEATURE_SALT),
+            opaque,
+        })
+    }
+
+    fn is_opaque(&self, block: BlockId) -> bool {
+        self.opaque.get(block as usize).copied().unwrap_or(false)
+    }
+
+    /// Returns the random generator of a feature cell, and the world column the
+    /// cell's feature is anchored to.
+    fn anchor(&self, cell_x: i32, cell_z: i32) -> (SplitMix64, i32, i32) {
+        let size = self.settings.cell_size;
+        let mut rng = SplitMix64::new(position_seed(self.seed, cell_x, cell_z));
+        let x = cell_x * size + rng.next_in(MAX_RADIUS, size - 1 - MAX_RADIUS);
+        let z = cell_z * size + rng.next_in(MAX_RADIUS, size - 1 - MAX_RADIUS);
+        (rng, x, z)
+    }
+
+    /// Returns the height of the topmost block of a column, if that block is opaque.
+    fn surface(&self, chunk: &Chunk, x: usize, z: usize) -> Option<i32> {
+        let y = (0..CHUNK_HEIGHT).rev().find(|&y| chunk.get(x, y, z) != AIR)?;
+        self.is_opaque(chunk.get(x, y, z)).then_some(y as i32)
+    }
+
+    /// Picks the feature of a cell anchored in `chunk`, if any.
+    fn choose(&self, rng: &mut SplitMix64, chunk: &Chunk, x: usize, z: usize, ground: BlockId) -> Option<Feature> {
+        let params = BiomeParams::of(chunk.column(x, z).biome);
+        let roll = rng.next_f64();
+        if roll < params.trees {
+            (ground == self.blocks.grass).then_some(Feature::Tree)
+        } else if roll < params.trees + params.boulders {
+            Some(Feature::Boulder)
+        } else if roll < params.trees + params.boulders + self.settings.ruin_chance {
+            Some(Feature::Ruin)
+        } else {
+            None
+        }
+    }
+
+    /// Grows a tree whose trunk stands on `(x, y, z)`.
+    fn place_tree(&self, rng: &mut SplitMix64, placer: &mut Placer, x: i32, y: i32, z: i32) {
+        let top = y + rng.next_in(4, 6);
+        for trunk in y + 1..=top {
+            placer.place(x, trunk, z, self.blocks.log);
+        }
+
+        for dy in -2..=1 {
+            let radius = if dy < 0 { MAX_RADIUS } else { 1 };
+            for dz in -radius..=radius {
+                for dx in -radius..=radius {
+                    // Round the canopy by dropping some of its corners.
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (dy == 1 || rng.next_below(2) == 0) {
+                        continue;
+                    }
+                    placer.place(x + dx, top + dy, z + dz, self.blocks.leaves);
+                }
+            }
+        }
+    }
+
+    /// Places a boulder half buried around `(x, y, z)`.
+    fn place_boulder(&self, rng: &mut SplitMix64, placer: &mut Placer, x: i32, y: i32, z: i32) {
+        let radius = rng.next_in(1, MAX_RADIUS);
+        for dy in 0..=radius {
+            for dz in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy + dz * dz <= radius * radius + 1 {
+                        placer.place(x + dx, y + dy, z + dz, self.blocks.cobblestone);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds a ruin centred on `(x, z)` on top of height `y`.
+    fn place_ruin(&self, placer: &mut Placer, x: i32, y: i32, z: i32) {
+        for (dy, layer) in RUIN.iter().enumerate() {
+            for (dz, row) in layer.iter().enumerate() {
+                for (dx, symbol) in row.chars().enumerate() {
+                    let block = match symbol {
+                        'c' => self.blocks.cobblestone,
+                        'l' => self.blocks.log,
+                        _ => continue,
+                    };
+                    let (bx, bz) = (x + dx as i32 - MAX_RADIUS, z + dz as i32 - MAX_RADIUS);
+                    placer.place(bx, y + 1 + dy as i32, bz, block);
+                }
+            }
+        }
+    }
+}
+
+impl GenerationStage for FeatureGenerator {
+    fn name(&self) -> &'static str {
+        "features"
+    }
+
+    fn apply(&self, chunk: &mut Chunk, pending: &mut PendingBlocks) {
+        let pos = chunk.pos();
+        let (origin_x, origin_z) = pos.origin();
+        let size = self.settings.cell_size;
+        let last = CHUNK_SIZE as i32 - 1;
+
+        // Features belong to the chunk containing their anchor column, which is the
+        // only column they read.
+        for cell_z in origin_z.div_euclid(size)..=(origin_z + last).div_euclid(size) {
+            for cell_x in origin_x.div_euclid(size)..=(origin_x + last).div_euclid(size) {
+                let (mut rng, x, z) = self.anchor(cell_x, cell_z);
+                if ChunkPos::from_block(x, z) != pos {
+                    continue;
+                }
+                let (local_x, local_z) = (local_coord(x), local_coord(z));
+                let Some(y) = self.surface(chunk, local_x, local_z) else {
+                    continue;
+                };
+                let ground = chunk.get(local_x, y as usize, local_z);
+                let Some(feature) = self.choose(&mut rng, chunk, local_x, local_z, ground) else {
+                    continue;
+                };
+
+                let mut placer = Placer { chunk, pending };
+                match feature {
+                    Feature::Tree => self.place_tree(&mut rng, &mut placer, x, y, z),
+                    Feature::Boulder => self.place_boulder(&mut rng, &mut placer, x, y, z),
+                    Feature::Ruin => self.place_ruin(&mut placer, x, y, z),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Biome, ColumnInfo, World};
+    use crate::worldgen::{GeneratedChunk, GeneratorPipeline, WorldGenerator};
+
+    const SEED: u64 = 0x5EED_1234_ABCD;
+
+    /// The height of the flat test terrain.
+    const GROUND: usize = 50;
+
+    /// A stage that builds flat forest terrain.
+    struct FlatForest {
+        stone: BlockId,
+        grass: BlockId,
+    }
+
+    impl GenerationStage for FlatForest {
+        fn name(&self) -> &'static str {
+            "flat"
+        }
+
+        fn apply(&self, chunk: &mut Chunk, _pending: &mut PendingBlocks) {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set_column(x, z, ColumnInfo { biome: Biome::Forest, ..ColumnInfo::default() });
+                    for y in 0..GROUND {
+                        chunk.set(x, y, z, self.stone);
+                    }
+                    chunk.set(x, GROUND, z, self.grass);
+                }
+            }
+        }
+    }
+
+    fn pipeline() -> GeneratorPipeline {
+        let registry = BlockRegistry::builtin();
+        let flat = FlatForest {
+            stone: registry.id_of("stone").unwrap(),
+            grass: registry.id_of("grass").unwrap(),
+        };
+        GeneratorPipeline::new()
+            .with_stage(flat)
+            .with_stage(FeatureGenerator::new(SEED, &registry, FeatureSettings::default()).unwrap())
+    }
+
+    /// Returns every ordering of `items`.
+    fn permutations(items: &[ChunkPos]) -> Vec<Vec<ChunkPos>> {
+        if items.len() <= 1 {
+            return vec![items.to_vec()];
+        }
+        let mut result = Vec::new();
+        for i in 0..items.len() {
+            let mut rest = items.to_vec();
+            let first = rest.remove(i);
+            for mut tail in permutations(&rest) {
+                tail.insert(0, first);
+                result.push(tail);
+            }
+        }
+        result
+    }
+
+    /// Returns the blocks of the given chunks, in a fixed order.
+    fn blocks(world: &World, positions: &[ChunkPos]) -> Vec<BlockId> {
+        let mut blocks = Vec::new();
+        for pos in positions {
+            let (origin_x, origin_z) = pos.origin();
+            for y in 0..CHUNK_HEIGHT as i32 {
+                for z in 0..CHUNK_SIZE as i32 {
+                    for x in 0..CHUNK_SIZE as i32 {
+                        blocks.push(world.get_block(origin_x + x, y, origin_z + z));
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Returns the chunks around a chunk corner that contain a tree trunk close enough
+    /// to the corner for its canopy to reach all four chunks.
+    fn find_straddling_tree(generator: &GeneratorPipeline, log: BlockId) -> Option<[ChunkPos; 4]> {
+        for corner_z in -4..4 {
+            for corner_x in -4..4 {
+                let corner = [(-1, -1), (0, -1), (-1, 0), (0, 0)].map(|(dx, dz)| ChunkPos::new(corner_x + dx, corner_z + dz));
+                let mut world = World::new();
+                for &pos in &corner {
+                    let GeneratedChunk { chunk, pending } = generator.generate(pos);
+                    world.insert_generated(chunk, pending);
+                }
+
+                let (x, z) = ChunkPos::new(corner_x, corner_z).origin();
+                let trunk_near_corner = (x - MAX_RADIUS..x + MAX_RADIUS)
+                    .any(|tx| (z - MAX_RADIUS..z + MAX_RADIUS).any(|tz| world.get_block(tx, GROUND as i32 + 1, tz) == log));
+                if trunk_near_corner {
+                    return Some(corner);
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn trees_across_four_chunks_are_identical_in_any_generation_order() {
+        let registry = BlockRegistry::builtin();
+        let (log, leaves) = (registry.id_of("log").unwrap(), registry.id_of("leaves").unwrap());
+        let generator = pipeline();
+        let corner = find_straddling_tree(&generator, log).expect("no tree near a chunk corner");
+
+        let mut expected = None;
+        for order in permutations(&corner) {
+            let mut world = World::new();
+            for &pos in &order {
+                let GeneratedChunk { chunk, pending } = generator.generate(pos);
+                world.insert_generated(chunk, pending);
+            }
+
+            // The canopy reaches every one of the four chunks.
+            for pos in corner {
+                let (x, z) = pos.origin();
+                let has_leaves = (GROUND as i32 + 1..GROUND as i32 + 10).any(|y| {
+                    (0..CHUNK_SIZE as i32).any(|dz| (0..CHUNK_SIZE as i32).any(|dx| world.get_block(x + dx, y, z + dz) == leaves))
+                });
+                assert!(has_leaves, "{:?} has no leaves", pos);
+            }
+
+            let blocks = blocks(&world, &corner);
+            match &expected {
+                None => expected = Some(blocks),
+                Some(expected) => assert!(*expected == blocks, "generation order {:?} changed the blocks", order),
+            }
+        }
+    }
+
+    #[test]
+    fn features_only_replace_air() {
+        let registry = BlockRegistry::builtin();
+        let generator = pipeline();
+        for pos in [ChunkPos::new(0, 0), ChunkPos::new(-2, 3)] {
+            let GeneratedChunk { chunk, pending } = generator.generate(pos);
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    assert_eq!(chunk.get(x, GROUND, z), registry.id_of("grass").unwrap());
+                }
+            }
+            assert!(!pending.chunk_positions().any(|queued| queued == pos));
+        }
+    }
+
+    #[test]
+    fn features_stay_inside_their_cell() {
+        let generator = FeatureGenerator::new(SEED, &BlockRegistry::builtin(), FeatureSettings::default()).unwrap();
+        let size = generator.settings.cell_size;
+        for cell_z in -20..20 {
+            for cell_x in -20..20 {
+                let (_, x, z) = generator.anchor(cell_x, cell_z);
+                assert!(x - MAX_RADIUS >= cell_x * size && x + MAX_RADIUS < (cell_x + 1) * size);
+                assert!(z - MAX_RADIUS >= cell_z * size && z + MAX_RADIUS < (cell_z + 1) * size);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_blocks_are_reported() {
+        let registry = BlockRegistry::parse(r#"[(id: 1, name: "grass", textures: All("grass"))]"#).unwrap();
+        let result = FeatureGenerator::new(SEED, &registry, FeatureSettings::default());
+        assert!(matches!(result, Err(GeneratorError::MissingBlock(name)) if name == "log"));
+    }
+}
@@ -0,0 +1,229 @@
+//! # World Generation Module
+//! 
+//! This module fills chunks with procedurally generated terrain, biomes, caves, ores
+//! and features such as trees. Generation runs as a pipeline of stages, each seeded
+//! from the world seed. Generators are deterministic: a chunk depends only on the
+//! world seed and its position, so chunks can be generated in any order and on any
+//! thread. Blocks that a chunk places into its neighbours are returned alongside it
+//! as `PendingBlocks`, which the `World` applies once the neighbours exist.
+
+mod biome;
+mod caves;
+mod features;
+mod noise;
+mod ores;
+mod terrain;
+
+pub use caves::{CaveCarver, CaveSettings};
+pub use features::{FeatureGenerator, FeatureSettings};
+pub use ores::{OreGenerator, OreVein};
+pub use terrain::{TerrainGenerator, TerrainSettings};
+
+use std::fmt;
+
+use crate::world::{BlockRegistry, Chunk, ChunkPos, PendingBlocks};
+
+/// A generated chunk and the blocks it placed outside of its own bounds.
+pub struct GeneratedChunk {
+    pub chunk: Chunk,
+    pub pending: PendingBlocks,
+}
+
+/// Produces the blocks of chunks.
+pub trait WorldGenerator: Send + Sync {
+    /// Generates the chunk at the given position.
+    ///
+    /// Calling this twice with the same position must produce identical chunks.
+    fn generate(&self, pos: ChunkPos) -> GeneratedChunk;
+}
+
+/// One pass of a `GeneratorPipeline`.
+///
+/// A stage may only depend on its own seed, its settings and the chunk it is given,
+/// so it can be tested on hand-built chunks. Blocks outside of the chunk are pushed
+/// to `pending` instead of being read or written directly.
+pub trait GenerationStage: Send + Sync {
+    /// Returns the name of the stage.
+    fn name(&self) -> &'static str;
+
+    /// Applies the stage to a chunk.
+    fn apply(&self, chunk: &mut Chunk, pending: &mut PendingBlocks);
+}
+
+/// A `WorldGenerator` that runs a list of stages, in order, on every chunk.
+#[derive(Default)]
+pub struct GeneratorPipeline {
+    stages: Vec<Box<dyn GenerationStage>>,
+}
+
+impl GeneratorPipeline {
+    /// Creates a new, empty `GeneratorPipeline`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the default pipeline: terrain, then caves, then ore veins, then features.
+    pub fn standard(seed: u64, registry: &BlockRegistry, ore_veins: &[OreVein]) -> Result<Self, GeneratorError> {
+        Ok(Self::new()
+            .with_stage(TerrainGenerator::new(seed, registry, TerrainSettings::default())?)
+            .with_stage(CaveCarver::new(seed, registry, CaveSettings::default()))
+            .with_stage(OreGenerator::new(seed, registry, ore_veins)?)
+            .with_stage(FeatureGenerator::new(seed, registry, FeatureSettings::default())?))
+    }
+
+    /// Appends a stage to the pipeline.
+    pub fn with_stage(mut self, stage: impl GenerationStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Returns the names of the stages, in the order they run.
+    pub fn stage_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages.iter().map(|stage| stage.name())
+    }
+}
+
+impl WorldGenerator for GeneratorPipeline {
+    fn generate(&self, pos: ChunkPos) -> GeneratedChunk {
+        let mut chunk = Chunk::new(pos);
+        let mut pending = PendingBlocks::new();
+        for stage in &self.stages {
+            stage.apply(&mut chunk, &mut pending);
+        }
+        GeneratedChunk { chunk, pending }
+    }
+}
+
+/// An error raised while setting up a generator.
+#[derive(Debug)]
+pub enum GeneratorError {
+    /// A configuration file could not be read.
+    Io(std::io::Error),
+    /// A configuration file is not valid RON.
+    Parse(ron::error::SpannedError),
+    /// A block the generator places is not in the block registry.
+    MissingBlock(String),
+    /// An ore vein's height range is empty or outside of the world.
+    InvalidHeightRange(String),
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::Io(err) => write!(f, "failed to read world generation settings: {}", err),
+            GeneratorError::Parse(err) => write!(f, "failed to parse world generation settings: {}", err),
+            GeneratorError::MissingBlock(name) => write!(f, "world generation needs block '{}', which is not registered", name),
+            GeneratorError::InvalidHeightRange(name) => write!(f, "ore vein '{}' has an invalid height range", name),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::world::{CHUNK_HEIGHT, CHUNK_SIZE};
+
+    const SEED: u64 = 0x5EED_1234_ABCD;
+
+    fn pipeline(seed: u64) -> GeneratorPipeline {
+        GeneratorPipeline::standard(seed, &BlockRegistry::builtin(), &OreVein::builtin()).unwrap()
+    }
+
+    /// Hashes every block of a chunk with FNV-1a, which is stable across runs and platforms.
+    fn block_hash(chunk: &Chunk) -> u64 {
+        let mut hash = 0xCBF2_9CE4_8422_2325u64;
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    for byte in chunk.get(x, y, z).to_le_bytes() {
+                        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+                    }
+                }
+            }
+        }
+        hash
+    }
+
+    fn positions() -> Vec<ChunkPos> {
+        (-3..3).flat_map(|z| (-3..3).map(move |x| ChunkPos::new(x, z))).collect()
+    }
+
+    /// Generates every position on `threads` threads and returns the hash of each chunk.
+    fn hashes_with_threads(generator: &dyn WorldGenerator, threads: usize) -> HashMap<ChunkPos, u64> {
+        let positions = positions();
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|t| {
+                    let positions = &positions;
+                    scope.spawn(move || {
+                        positions
+                            .iter()
+                            .skip(t)
+                            .step_by(threads)
+                            .map(|&pos| (pos, block_hash(&generator.generate(pos).chunk)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        })
+    }
+
+    /// A stage that records the order in which stages run.
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl GenerationStage for Recorder {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn apply(&self, _chunk: &mut Chunk, _pending: &mut PendingBlocks) {
+            self.log.lock().unwrap().push(self.name);
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic_across_runs() {
+        let (a, b) = (pipeline(SEED), pipeline(SEED));
+        for pos in positions() {
+            assert_eq!(block_hash(&a.generate(pos).chunk), block_hash(&b.generate(pos).chunk), "{:?}", pos);
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic_across_thread_counts() {
+        let generator = pipeline(SEED);
+        let expected = hashes_with_threads(&generator, 1);
+        assert_eq!(expected.len(), positions().len());
+
+        for threads in [2, 3, 8] {
+            assert_eq!(hashes_with_threads(&generator, threads), expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_worlds() {
+        let (a, b) = (pipeline(1), pipeline(2));
+        assert!(positions().iter().any(|&pos| block_hash(&a.generate(pos).chunk) != block_hash(&b.generate(pos).chunk)));
+    }
+
+    #[test]
+    fn stages_run_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stage = |name| Recorder { name, log: Arc::clone(&log) };
+        let recorded = GeneratorPipeline::new().with_stage(stage("first")).with_stage(stage("second"));
+
+        recorded.generate(ChunkPos::new(0, 0));
+        assert_eq!(*log.lock().unwrap(), ["first", "second"]);
+        assert_eq!(recorded.stage_names().collect::<Vec<_>>(), ["first", "second"]);
+        assert_eq!(pipeline(SEED).stage_names().collect::<Vec<_>>(), ["terrain", "caves", "ores", "features"]);
+    }
+}
@@ -0,0 +1,307 @@
+//! # Noise
+//! 
+//! This module implements seeded gradient noise and the fractal sums built from it.
+//! Everything here is a pure function of the seed and the sample position, so the
+//! terrain built on top of it is reproducible.
+
+/// A small, fast pseudo-random number generator (SplitMix64).
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a new generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in `0..bound`. `bound` must not be zero.
+    pub fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Returns a random number in `min..=max`.
+    pub fn next_in(&mut self, min: i32, max: i32) -> i32 {
+        min + self.next_below((max - min) as u64 + 1) as i32
+    }
+
+    /// Returns a random number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Derives an independent seed from a seed and a salt, so that different noise
+/// layers of the same world do not correlate.
+pub fn mix_seed(seed: u64, salt: u64) -> u64 {
+    SplitMix64::new(seed ^ salt.wrapping_mul(0xD1B5_4A32_D192_ED03)).next_u64()
+}
+
+/// Derives a seed for a position on the X/Z plane, such as a world column or a chunk.
+pub fn position_seed(seed: u64, x: i32, z: i32) -> u64 {
+    mix_seed(seed, ((x as u32 as u64) << 32) | z as u32 as u64)
+}
+
+/// Seeded Perlin gradient noise.
+#[derive(Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    /// Creates a new `Perlin` noise function whose permutation table is shuffled by `seed`.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        for i in (1..table.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        Self {
+            perm: std::array::from_fn(|i| table[i & 255]),
+        }
+    }
+
+    /// Samples the noise at a 2D position. The result lies in `[-1, 1]` and is zero at
+    /// integer coordinates.
+    pub fn sample2(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (xi, yi) = ((x0 as i64 & 255) as usize, (y0 as i64 & 255) as usize);
+        let (x, y) = (x - x0, y - y0);
+        let (u, v) = (fade(x), fade(y));
+
+        let p = &self.perm;
+        let (a, b) = (p[xi] as usize + yi, p[xi + 1] as usize + yi);
+
+        lerp(
+            v,
+            lerp(u, grad2(p[a], x, y), grad2(p[b], x - 1.0, y)),
+            lerp(u, grad2(p[a + 1], x, y - 1.0), grad2(p[b + 1], x - 1.0, y - 1.0)),
+        )
+    }
+
+    /// Samples the noise at a 3D position. The result lies roughly in `[-1, 1]` and is
+    /// zero at integer coordinates.
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let xi = (x0 as i64 & 255) as usize;
+        let yi = (y0 as i64 & 255) as usize;
+        let zi = (z0 as i64 & 255) as usize;
+        let (x, y, z) = (x - x0, y - y0, z - z0);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.perm;
+        let (a, b) = (p[xi] as usize + yi, p[xi + 1] as usize + yi);
+        let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+        let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad3(p[aa], x, y, z), grad3(p[ba], x - 1.0, y, z)),
+                lerp(u, grad3(p[ab], x, y - 1.0, z), grad3(p[bb], x - 1.0, y - 1.0, z)),
+            ),
+            lerp(
+                v,
+                lerp(u, grad3(p[aa + 1], x, y, z - 1.0), grad3(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, grad3(p[ab + 1], x, y - 1.0, z - 1.0), grad3(p[bb + 1], x - 1.0, y - 1.0, z - 1.0)),
+            ),
+        )
+    }
+}
+
+/// The smoothstep curve `6t^5 - 15t^4 + 10t^3` used to interpolate between lattice points.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Returns the dot product of the lattice gradient selected by `hash` and the offset `(x, y)`.
+fn grad2(hash: u8, x: f64, y: f64) -> f64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Returns the dot product of the lattice gradient selected by `hash` and the offset
+/// `(x, y, z)`. The gradients point to the 12 edges of a cube.
+fn grad3(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// The parameters of a fractal noise sum.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FractalParams {
+    /// The number of noise layers.
+    pub octaves: u32,
+    /// The frequency of the first octave, in cycles per block.
+    pub frequency: f64,
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f64,
+    /// The amplitude multiplier between successive octaves.
+    pub persistence: f64,
+}
+
+/// Fractal Brownian motion: a sum of Perlin octaves of increasing frequency and
+/// decreasing amplitude, each with its own permutation.
+#[derive(Clone)]
+pub struct FractalNoise {
+    octaves: Vec<Perlin>,
+    params: FractalParams,
+}
+
+impl FractalNoise {
+    /// Creates a new `FractalNoise` seeded by `seed`.
+    pub fn new(seed: u64, params: FractalParams) -> Self {
+        Self {
+            octaves: (0..params.octaves).map(|i| Perlin::new(mix_seed(seed, i as u64))).collect(),
+            params,
+        }
+    }
+
+    /// Samples the noise at a 2D position, normalized to `[-1, 1]`.
+    pub fn sample2(&self, x: f64, y: f64) -> f64 {
+        self.sum(|octave, frequency| octave.sample2(x * frequency, y * frequency))
+    }
+
+    /// Samples the noise at a 3D position, normalized to roughly `[-1, 1]`.
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.sum(|octave, frequency| octave.sample3(x * frequency, y * frequency, z * frequency))
+    }
+
+    /// Sums the octaves sampled by `sample(octave, frequency)`, weighted by their amplitude.
+    fn sum(&self, sample: impl Fn(&Perlin, f64) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut frequency = self.params.frequency;
+        let mut amplitude = 1.0;
+
+        for octave in &self.octaves {
+            sum += sample(octave, frequency) * amplitude;
+            total += amplitude;
+            frequency *= self.params.lacunarity;
+            amplitude *= self.params.persistence;
+        }
+
+        if total > 0.0 {
+            sum / total
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: FractalParams = FractalParams {
+        octaves: 4,
+        frequency: 0.05,
+        lacunarity: 2.0,
+        persistence: 0.5,
+    };
+
+    /// Returns sample positions spread over positive and negative coordinates.
+    fn positions() -> impl Iterator<Item = (f64, f64)> {
+        (-50..50).flat_map(|i| (-5..5).map(move |j| (i as f64 * 3.7 + 0.3, j as f64 * 11.3 - 0.6)))
+    }
+
+    #[test]
+    fn same_seed_gives_same_values() {
+        let (a, b) = (FractalNoise::new(42, PARAMS), FractalNoise::new(42, PARAMS));
+        for (x, y) in positions() {
+            assert_eq!(a.sample2(x, y).to_bits(), b.sample2(x, y).to_bits());
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_values() {
+        let (a, b) = (FractalNoise::new(1, PARAMS), FractalNoise::new(2, PARAMS));
+        assert!(positions().any(|(x, y)| a.sample2(x, y) != b.sample2(x, y)));
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        let perlin = Perlin::new(7);
+        let fractal = FractalNoise::new(7, PARAMS);
+        for (x, y) in positions() {
+            assert!((-1.0..=1.0).contains(&perlin.sample2(x, y)));
+            assert!((-1.0..=1.0).contains(&fractal.sample2(x, y)));
+        }
+    }
+
+    #[test]
+    fn noise_3d_is_deterministic_and_bounded() {
+        let (a, b) = (FractalNoise::new(8, PARAMS), FractalNoise::new(8, PARAMS));
+        let perlin = Perlin::new(8);
+        let mut max: f64 = 0.0;
+        for (x, z) in positions() {
+            for y in [-7.3, 0.2, 19.9, 64.5] {
+                assert_eq!(a.sample3(x, y, z).to_bits(), b.sample3(x, y, z).to_bits());
+                max = max.max(perlin.sample3(x * 0.37, y * 0.37, z * 0.37).abs());
+                assert!(a.sample3(x, y, z).abs() <= 1.1);
+            }
+        }
+        assert!(max > 0.3 && max <= 1.1, "max {}", max);
+        assert_eq!(perlin.sample3(3.0, -4.0, 5.0), 0.0);
+    }
+
+    #[test]
+    fn perlin_is_zero_on_the_lattice_and_continuous_between() {
+        let perlin = Perlin::new(3);
+        for i in -20..20 {
+            let x = i as f64;
+            assert_eq!(perlin.sample2(x, -x), 0.0);
+            assert!((perlin.sample2(x + 0.5, 0.25) - perlin.sample2(x + 0.501, 0.25)).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn next_f64_is_uniform_and_stable() {
+        let mut rng = SplitMix64::new(11);
+        let values: Vec<f64> = (0..10_000).map(|_| rng.next_f64()).collect();
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!((mean - 0.5).abs() < 0.02, "mean {}", mean);
+
+        assert_eq!(SplitMix64::new(position_seed(11, -3, 4)).next_f64(), SplitMix64::new(position_seed(11, -3, 4)).next_f64());
+        assert_ne!(position_seed(11, -3, 4), position_seed(11, 4, -3));
+    }
+
+    #[test]
+    fn mixed_seeds_differ_per_salt() {
+        assert_ne!(mix_seed(5, 0), mix_seed(5, 1));
+        assert_ne!(mix_seed(5, 0), mix_seed(6, 0));
+        assert_eq!(mix_seed(5, 9), mix_seed(5, 9));
+    }
+}
@@ -0,0 +1,245 @@
+//! # Ore Veins
+//! 
+//! This module defines the generation stage that scatters veins of ore through the
+//! stone of each chunk. Veins are configured in `assets/ores.ron`.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::world::{BlockId, BlockRegistry, Chunk, PendingBlocks, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::worldgen::noise::{mix_seed, position_seed, SplitMix64};
+use crate::worldgen::{GenerationStage, GeneratorError};
+
+/// Salt of the ore placement seed.
+const ORE_SALT: u64 = 8;
+
+/// The configuration of one kind of ore vein.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct OreVein {
+    /// The name of the ore block.
+    pub block: String,
+    /// The maximum number of blocks in a vein.
+    pub vein_size: u32,
+    /// The lowest height of the vein's blocks.
+    pub min_height: i32,
+    /// The highest height of the vein's blocks.
+    pub max_height: i32,
+    /// The number of veins attempted in each chunk.
+    pub veins_per_chunk: u32,
+}
+
+impl OreVein {
+    /// Loads vein configurations from a RON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<Self>, GeneratorError> {
+        let source = std::fs::read_to_string(path).map_err(GeneratorError::Io)?;
+        Self::parse(&source)
+    }
+
+    /// Parses vein configurations from a RON string.
+    pub fn parse(source: &str) -> Result<Vec<Self>, GeneratorError> {
+        let veins: Vec<Self> = ron::from_str(source).map_err(GeneratorError::Parse)?;
+        for vein in &veins {
+            if vein.min_height < 0 || vein.min_height > vein.max_height || vein.max_height >= CHUNK_HEIGHT as i32 {
+                return Err(GeneratorError::InvalidHeightRange(vein.block.clone()));
+            }
+        }
+        Ok(veins)
+    }
+
+    /// Returns the veins defined by the bundled `assets/ores.ron`.
+    #[cfg(test)]
+    pub fn builtin() -> Vec<Self> {
+        Self::parse(include_str!("../../assets/ores.ron")).unwrap()
+    }
+}
+
+/// A vein configuration with its block resolved.
+#[derive(Clone, Debug)]
+struct ResolvedVein {
+    block: BlockId,
+    config: OreVein,
+}
+
+/// Replaces stone with veins of ore.
+pub struct OreGenerator {
+    seed: u64,
+    stone: BlockId,
+    veins: Vec<ResolvedVein>,
+}
+
+impl OreGenerator {
+    /// Creates a new `OreGenerator` seeded by `seed`, resolving the ore blocks from the registry.
+    pub fn new(seed: u64, registry: &BlockRegistry, veins: &[OreVein]) -> Result<Self, GeneratorError> {
+        let block = |name: &str| registry.id_of(name).ok_or_else(|| GeneratorError::MissingBlock(name.to_string()));
+        let veins = veins
+            .iter()
+            .map(|config| Ok(ResolvedVein { block: block(&config.block)?, config: config.clone() }))
+            .collect::<Result<_, GeneratorError>>()?;
+
+        Ok(Self {
+            seed: mix_seed(seed, ORE_SALT),
+            stone: block("stone")?,
+            veins,
+        })
+    }
+
+    /// Places one vein as a random walk from a random start, clamped to the chunk
+    /// and the vein's height range.
+    fn place_vein(&self, chunk: &mut Chunk, vein: &ResolvedVein, rng: &mut SplitMix64) {
+        let OreVein { vein_size, min_height, max_height, .. } = vein.config;
+        let last = CHUNK_SIZE as i32 - 1;
+        let mut p = [rng.next_in(0, last), rng.next_in(min_height, max_height), rng.next_in(0, last)];
+
+        for _ in 0..vein_size {
+            let (x, y, z) = (p[0] as usize, p[1] as usize, p[2] as usize);
+            if chunk.get(x, y, z) == self.stone {
+                chunk.set(x, y, z, vein.block);
+            }
+
+            let axis = rng.next_below(3) as usize;
+            p[axis] += if rng.next_below(2) == 0 { -1 } else { 1 };
+            p[0] = p[0].clamp(0, last);
+            p[1] = p[1].clamp(min_height, max_height);
+            p[2] = p[2].clamp(0, last);
+        }
+    }
+}
+
+impl GenerationStage for OreGenerator {
+    fn name(&self) -> &'static str {
+        "ores"
+    }
+
+    fn apply(&self, chunk: &mut Chunk, _pending: &mut PendingBlocks) {
+        let pos = chunk.pos();
+        let chunk_seed = position_seed(self.seed, pos.x, pos.z);
+
+        // Every vein kind draws from its own generator, so adding or removing one kind
+        // does not move the veins of the others.
+        for (i, vein) in self.veins.iter().enumerate() {
+            let mut rng = SplitMix64::new(mix_seed(chunk_seed, i as u64));
+            for _ in 0..vein.config.veins_per_chunk {
+                self.place_vein(chunk, vein, &mut rng);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::ChunkPos;
+
+    const SEED: u64 = 2024;
+
+    /// Returns a chunk filled with stone below height 100.
+    fn stone_chunk(pos: ChunkPos) -> Chunk {
+        let stone = BlockRegistry::builtin().id_of("stone").unwrap();
+        let mut chunk = Chunk::new(pos);
+        for y in 0..100 {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    chunk.set(x, y, z, stone);
+                }
+            }
+        }
+        chunk
+    }
+
+    /// Returns the positions of every block of the given kind.
+    fn find(chunk: &Chunk, block: BlockId) -> Vec<(usize, usize, usize)> {
+        let mut found = Vec::new();
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if chunk.get(x, y, z) == block {
+                        found.push((x, y, z));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn generate(generator: &OreGenerator, pos: ChunkPos) -> Chunk {
+        let mut chunk = stone_chunk(pos);
+        generator.apply(&mut chunk, &mut PendingBlocks::new());
+        chunk
+    }
+
+    #[test]
+    fn bundled_veins_load() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let veins = OreVein::load(root.join("assets/ores.ron")).unwrap();
+        assert!(veins.iter().any(|vein| vein.block == "diamond_ore"));
+        assert!(OreGenerator::new(SEED, &BlockRegistry::builtin(), &veins).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_height_ranges() {
+        let result = OreVein::parse(r#"[(block: "coal_ore", vein_size: 4, min_height: 30, max_height: 10, veins_per_chunk: 1)]"#);
+        assert!(matches!(result, Err(GeneratorError::InvalidHeightRange(block)) if block == "coal_ore"));
+    }
+
+    #[test]
+    fn rejects_unknown_ore_blocks() {
+        let veins = OreVein::parse(r#"[(block: "mithril", vein_size: 4, min_height: 0, max_height: 10, veins_per_chunk: 1)]"#).unwrap();
+        let result = OreGenerator::new(SEED, &BlockRegistry::builtin(), &veins);
+        assert!(matches!(result, Err(GeneratorError::MissingBlock(block)) if block == "mithril"));
+    }
+
+    #[test]
+    fn veins_stay_in_their_height_range_and_size() {
+        let registry = BlockRegistry::builtin();
+        let veins = OreVein::builtin();
+        let generator = OreGenerator::new(SEED, &registry, &veins).unwrap();
+
+        for cx in -3..3 {
+            let chunk = generate(&generator, ChunkPos::new(cx, 4));
+            for vein in &veins {
+                let ores = find(&chunk, registry.id_of(&vein.block).unwrap());
+                assert!(ores.len() <= (vein.vein_size * vein.veins_per_chunk) as usize);
+                assert!(ores.iter().all(|&(_, y, _)| (vein.min_height..=vein.max_height).contains(&(y as i32))));
+            }
+        }
+    }
+
+    #[test]
+    fn ores_only_replace_stone() {
+        let registry = BlockRegistry::builtin();
+        let generator = OreGenerator::new(SEED, &registry, &OreVein::builtin()).unwrap();
+
+        // A chunk of dirt receives no ore at all.
+        let dirt = registry.id_of("dirt").unwrap();
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        for y in 0..100 {
+            chunk.set(3, y, 3, dirt);
+        }
+        generator.apply(&mut chunk, &mut PendingBlocks::new());
+        assert_eq!(find(&chunk, dirt).len(), 100);
+
+        // A stone chunk receives some.
+        let chunk = generate(&generator, ChunkPos::new(0, 0));
+        assert!(!find(&chunk, registry.id_of("coal_ore").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn placement_is_deterministic_and_independent_per_vein() {
+        let registry = BlockRegistry::builtin();
+        let veins = OreVein::builtin();
+        let coal = registry.id_of("coal_ore").unwrap();
+        let pos = ChunkPos::new(-7, 2);
+
+        let a = generate(&OreGenerator::new(SEED, &registry, &veins).unwrap(), pos);
+        let b = generate(&OreGenerator::new(SEED, &registry, &veins).unwrap(), pos);
+        assert_eq!(find(&a, coal), find(&b, coal));
+
+        // Coal is placed first, so dropping the other veins leaves it in place.
+        let coal_only = generate(&OreGenerator::new(SEED, &registry, &veins[..1]).unwrap(), pos);
+        assert_eq!(find(&coal_only, coal), find(&a, coal));
+
+        let other_seed = generate(&OreGenerator::new(SEED + 1, &registry, &veins).unwrap(), pos);
+        assert_ne!(find(&other_seed, coal), find(&a, coal));
+    }
+}
@@ -0,0 +1,246 @@
+//! # Terrain Generator
+//! 
+//! This module defines the default `WorldGenerator`, which shapes the terrain from a
+//! fractal noise heightmap scaled by the blended biome parameters: stone below a few
+//! layers of soil topped with the biome's surface block, sandy beaches and sea
+//! floors, and water filling everything below sea level.
+
+use crate::world::{Biome, BlockId, BlockRegistry, Chunk, PendingBlocks, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::worldgen::biome::{BiomeBlend, BiomeParams, BiomeSource};
+use crate::worldgen::noise::{mix_seed, FractalNoise, FractalParams};
+use crate::worldgen::{GenerationStage, GeneratorError};
+
+/// Salt of the heightmap noise seed.
+const HEIGHT_SALT: u64 = 1;
+
+/// The parameters of the terrain shape.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TerrainSettings {
+    /// The height of the water surface. Columns below it are flooded.
+    pub sea_level: i32,
+    /// The average terrain height, before the biome offsets are applied.
+    pub base_height: f64,
+    /// The number of soil layers between the surface and the stone.
+    pub soil_depth: i32,
+    /// The noise used for the heightmap.
+    pub height_noise: FractalParams,
+    /// The noise used for the temperature map that selects biomes.
+    pub temperature_noise: FractalParams,
+    /// The noise used for the humidity map that selects biomes.
+    pub humidity_noise: FractalParams,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        let climate = FractalParams {
+            octaves: 3,
+            frequency: 1.0 / 512.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        };
+        Self {
+            sea_level: 48,
+            base_height: 46.0,
+            soil_depth: 3,
+            height_noise: FractalParams {
+                octaves: 5,
+                frequency: 1.0 / 192.0,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            temperature_noise: climate,
+            humidity_noise: climate,
+        }
+    }
+}
+
+/// The surface blocks of a biome.
+#[derive(Copy, Clone, Debug)]
+struct SurfaceBlocks {
+    surface: BlockId,
+    soil: BlockId,
+}
+
+/// The blocks placed by the terrain generator.
+#[derive(Copy, Clone, Debug)]
+struct TerrainBlocks {
+    stone: BlockId,
+    sand: BlockId,
+    water: BlockId,
+    biomes: [SurfaceBlocks; Biome::ALL.len()],
+}
+
+/// Generates heightmap terrain with biomes from a 64-bit seed.
+pub struct TerrainGenerator {
+    settings: TerrainSettings,
+    blocks: TerrainBlocks,
+    height_noise: FractalNoise,
+    biomes: BiomeSource,
+}
+
+impl TerrainGenerator {
+    /// Creates a new `TerrainGenerator`, resolving the blocks it places from the registry.
+    pub fn new(seed: u64, registry: &BlockRegistry, settings: TerrainSettings) -> Result<Self, GeneratorError> {
+        let block = |name: &str| registry.id_of(name).ok_or_else(|| GeneratorError::MissingBlock(name.to_string()));
+        let mut biomes = Vec::with_capacity(Biome::ALL.len());
+        for biome in Biome::ALL {
+            let params = BiomeParams::of(biome);
+            biomes.push(SurfaceBlocks {
+                surface: block(params.surface)?,
+                soil: block(params.soil)?,
+            });
+        }
+        let blocks = TerrainBlocks {
+            stone: block("stone")?,
+            sand: block("sand")?,
+            water: block("water")?,
+            biomes: biomes.try_into().unwrap(),
+        };
+
+        Ok(Self {
+            settings,
+            blocks,
+            height_noise: FractalNoise::new(mix_seed(seed, HEIGHT_SALT), settings.height_noise),
+            biomes: BiomeSource::new(seed, settings.temperature_noise, settings.humidity_noise),
+        })
+    }
+
+    /// Returns the height of the topmost terrain block of a world column.
+    fn height_at(&self, x: i32, z: i32, blend: &BiomeBlend) -> i32 {
+        let offset = blend.mix(|biome| BiomeParams::of(biome).height_offset);
+        let variation = blend.mix(|biome| BiomeParams::of(biome).height_variation);
+        let noise = self.height_noise.sample2(x as f64, z as f64);
+        let height = self.settings.base_height + offset + noise * variation;
+        (height.round() as i32).clamp(1, CHUNK_HEIGHT as i32 - 2)
+    }
+
+    /// Returns the block at height `y` of a column whose surface is at `height`.
+    fn block_at(&self, y: i32, height: i32, surface: SurfaceBlocks) -> Option<BlockId> {
+        let TerrainSettings { sea_level, soil_depth, .. } = self.settings;
+        // Columns at or just above the water line are beaches.
+        let beach = height <= sea_level + 1;
+
+        if y > height {
+            (y <= sea_level).then_some(self.blocks.water)
+        } else if y == height {
+            Some(if beach { self.blocks.sand } else { surface.surface })
+        } else if y > height - soil_depth {
+            Some(if beach { self.blocks.sand } else { surface.soil })
+        } else {
+            Some(self.blocks.stone)
+        }
+    }
+}
+
+impl GenerationStage for TerrainGenerator {
+    fn name(&self) -> &'static str {
+        "terrain"
+    }
+
+    fn apply(&self, chunk: &mut Chunk, _pending: &mut PendingBlocks) {
+        let pos = chunk.pos();
+        let (origin_x, origin_z) = pos.origin();
+        let blends = self.biomes.blend_chunk(pos);
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let (world_x, world_z) = (origin_x + x as i32, origin_z + z as i32);
+                let blend = &blends[z * CHUNK_SIZE + x];
+                let column = blend.column_info();
+                chunk.set_column(x, z, column);
+
+                let height = self.height_at(world_x, world_z, blend);
+                let surface = self.blocks.biomes[column.biome.index()];
+                for y in 0..=height.max(self.settings.sea_level) {
+                    if let Some(block) = self.block_at(y, height, surface) {
+                        chunk.set(x, y as usize, z, block);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{ChunkPos, AIR};
+
+    const SEED: u64 = 0x5EED_1234_ABCD;
+
+    fn generator(seed: u64) -> TerrainGenerator {
+        TerrainGenerator::new(seed, &BlockRegistry::builtin(), TerrainSettings::default()).unwrap()
+    }
+
+    fn generate(generator: &TerrainGenerator, pos: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(pos);
+        generator.apply(&mut chunk, &mut PendingBlocks::new());
+        chunk
+    }
+
+    fn positions() -> Vec<ChunkPos> {
+        (-3..3).flat_map(|z| (-3..3).map(move |x| ChunkPos::new(x, z))).collect()
+    }
+
+    #[test]
+    fn columns_are_layered() {
+        let generator = generator(SEED);
+        let registry = BlockRegistry::builtin();
+        let id = |name| registry.id_of(name).unwrap();
+        let TerrainSettings { sea_level, soil_depth, .. } = TerrainSettings::default();
+        let (mut dry, mut flooded) = (0, 0);
+
+        for pos in positions() {
+            let chunk = generate(&generator, pos);
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let block = |y: i32| chunk.get(x, y as usize, z);
+                    let height = (0..CHUNK_HEIGHT as i32)
+                        .rev()
+                        .find(|&y| ![AIR, id("water")].contains(&block(y)))
+                        .unwrap();
+                    let params = BiomeParams::of(chunk.column(x, z).biome);
+
+                    if height > sea_level + 1 {
+                        dry += 1;
+                        assert_eq!(block(height), id(params.surface));
+                        assert_eq!(block(height - 1), id(params.soil));
+                    } else {
+                        assert_eq!(block(height), id("sand"));
+                    }
+                    assert_eq!(block(height - soil_depth), id("stone"));
+                    assert_eq!(block(0), id("stone"));
+
+                    if height < sea_level {
+                        flooded += 1;
+                        assert!((height + 1..=sea_level).all(|y| block(y) == id("water")));
+                    }
+                    assert_eq!(block(height.max(sea_level) + 1), AIR);
+                }
+            }
+        }
+
+        // The sampled area should contain both land and sea.
+        assert!(dry > 0 && flooded > 0, "dry: {}, flooded: {}", dry, flooded);
+    }
+
+    #[test]
+    fn columns_record_blended_biome_colours() {
+        let generator = generator(SEED);
+        let chunk = generate(&generator, ChunkPos::new(1, -2));
+        let blends = generator.biomes.blend_chunk(ChunkPos::new(1, -2));
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                assert_eq!(*chunk.column(x, z), blends[z * CHUNK_SIZE + x].column_info());
+            }
+        }
+    }
+
+    #[test]
+    fn missing_blocks_are_reported() {
+        let registry = BlockRegistry::parse(r#"[(id: 1, name: "stone", textures: All("stone"))]"#).unwrap();
+        let result = TerrainGenerator::new(SEED, &registry, TerrainSettings::default());
+        assert!(matches!(result, Err(GeneratorError::MissingBlock(name)) if name == "sand"));
+    }
+}